@@ -0,0 +1,6 @@
+#![no_main]
+
+use htlc_fuzz::{native_htlc::NativeHtlc, run, Action};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|actions: Vec<Action>| run::<NativeHtlc>(&actions));
@@ -0,0 +1,459 @@
+//! The `anchor_htlc` program, locking tokens of an spl token mint.
+
+use std::collections::HashMap;
+
+use anchor_lang::{
+    solana_program::{
+        instruction::Instruction, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+        system_instruction,
+    },
+    AnchorDeserialize,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id, spl_associated_token_account,
+    },
+    token::spl_token,
+};
+use htlc_client::anchor_htlc::{
+    self, CommitParams, LockParams, RedeemBatchItem, RedeemParams, RefundBatchItem, TokenAccounts,
+};
+use sol::{HashAlgorithm, HtlcStatus, TimelockKind};
+
+use crate::{
+    actor_address, actor_keypair, htlc_ids,
+    svm::{self, Svm},
+    AddLockSig, BatchOutcome, BatchRedeem, Lock, Outcome, Program, RedeemTo, State, ACTORS,
+    MAX_TIMELOCK_DELTA, MIN_TIMELOCK_DELTA,
+};
+
+/// Tokens each actor starts with.
+const FUNDS: u64 = 1 << 50;
+
+/// The hash algorithm of the model as the one of `anchor_htlc`.
+fn to_anchor(hash_algorithm: HashAlgorithm) -> anchor_htlc::HashAlgorithm {
+    match hash_algorithm {
+        HashAlgorithm::Sha256 => anchor_htlc::HashAlgorithm::Sha256,
+        HashAlgorithm::Keccak256 => anchor_htlc::HashAlgorithm::Keccak256,
+    }
+}
+
+/// The timelock kind of the model as the one of `anchor_htlc`.
+fn timelock_kind_to_anchor(timelock_kind: TimelockKind) -> anchor_htlc::TimelockKind {
+    match timelock_kind {
+        TimelockKind::Timestamp => anchor_htlc::TimelockKind::Timestamp,
+        TimelockKind::Slot => anchor_htlc::TimelockKind::Slot,
+    }
+}
+
+pub struct AnchorHtlc {
+    svm: Svm,
+    /// Admin of the config, pausing and unpausing the program.
+    admin: Pubkey,
+    actors: Vec<Pubkey>,
+    token: TokenAccounts,
+    /// Associated token accounts of the actors.
+    token_accounts: Vec<Pubkey>,
+    /// HTLC and escrow token accounts by sender and id, derived once as the invariants
+    /// look them up often.
+    htlcs: HashMap<(usize, [u8; 32]), (Pubkey, Pubkey)>,
+}
+
+impl AnchorHtlc {
+    fn process(&mut self, instruction: Instruction, signer: usize) -> Outcome {
+        self.process_all(&[instruction], signer).map(|_| ())
+    }
+
+    /// Processes `instructions` in one transaction, returning the return data of the last.
+    fn process_all(
+        &mut self,
+        instructions: &[Instruction],
+        signer: usize,
+    ) -> Result<Vec<u8>, ProgramError> {
+        let signer = self.actors[signer];
+        self.svm.process(instructions, &[signer])
+    }
+
+    /// The token accounts of a redeem: the sender's for a reward paid back, the signer's for
+    /// a reward paid to the signer.
+    fn redeem_params(
+        &self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        secret: [u8; 32],
+    ) -> RedeemParams {
+        RedeemParams {
+            id,
+            secret,
+            sender_token_account: Some(self.token_account(sender)),
+            user_signing_token_account: Some(self.token_account(signer)),
+        }
+    }
+
+    fn token_account(&self, actor: usize) -> Pubkey {
+        self.token_accounts[actor]
+    }
+
+    fn htlc(&self, sender: usize, id: [u8; 32]) -> Option<anchor_htlc::HTLC> {
+        let account = self.svm.account(&self.htlcs[&(sender, id)].0)?;
+        anchor_htlc::decode_htlc(&account.data).ok()
+    }
+
+    fn token_balance(&self, account: &Pubkey) -> u64 {
+        self.svm
+            .account(account)
+            .filter(|account| account.lamports > 0)
+            .map_or(0, |account| {
+                spl_token::state::Account::unpack(&account.data)
+                    .unwrap()
+                    .amount
+            })
+    }
+}
+
+impl Program for AnchorHtlc {
+    fn new() -> Self {
+        let mut svm = Svm::new(&[(anchor_htlc::ID, native::entry)]);
+        let admin = Pubkey::new_unique();
+        svm.airdrop(&admin, 1_000_000_000);
+        svm.set_upgrade_authority(&anchor_htlc::ID, &admin);
+        let instruction =
+            anchor_htlc::initialize_config(&admin, MIN_TIMELOCK_DELTA, MAX_TIMELOCK_DELTA);
+        svm.process(&[instruction], &[admin]).unwrap();
+        let actors: Vec<Pubkey> = (0..ACTORS).map(actor_address).collect();
+        for actor in &actors {
+            svm.airdrop(actor, 10_000_000_000);
+        }
+        let mint = Pubkey::new_unique();
+        let authority = actors[0];
+        let token_accounts: Vec<Pubkey> = actors
+            .iter()
+            .map(|actor| get_associated_token_address_with_program_id(actor, &mint, &spl_token::ID))
+            .collect();
+        let mut instructions = vec![
+            system_instruction::create_account(
+                &authority,
+                &mint,
+                1_000_000_000,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(&spl_token::ID, &mint, &authority, None, 6)
+                .unwrap(),
+        ];
+        for (actor, token_account) in actors.iter().zip(&token_accounts) {
+            instructions.extend([
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &authority,
+                    actor,
+                    &mint,
+                    &spl_token::ID,
+                ),
+                spl_token::instruction::mint_to(
+                    &spl_token::ID,
+                    &mint,
+                    token_account,
+                    &authority,
+                    &[],
+                    FUNDS,
+                )
+                .unwrap(),
+            ]);
+        }
+        svm.process(&instructions, &[authority, mint]).unwrap();
+        let htlcs = (0..ACTORS)
+            .flat_map(|sender| htlc_ids().map(move |id| (sender, id)))
+            .map(|(sender, id)| {
+                let htlc = anchor_htlc::htlc_address(&actors[sender], &id).0;
+                let escrow = anchor_htlc::htlc_token_account_address(&actors[sender], &id).0;
+                ((sender, id), (htlc, escrow))
+            })
+            .collect();
+
+        AnchorHtlc {
+            svm,
+            admin,
+            actors,
+            token: TokenAccounts {
+                mint,
+                token_program: spl_token::ID,
+            },
+            token_accounts,
+            htlcs,
+        }
+    }
+
+    fn svm(&mut self) -> &mut Svm {
+        &mut self.svm
+    }
+
+    fn actor(&self, index: usize) -> Pubkey {
+        self.actors[index]
+    }
+
+    fn commit(
+        &mut self,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        amount: u64,
+        timelock: u64,
+        timelock_kind: TimelockKind,
+    ) -> Outcome {
+        let params = CommitParams {
+            id,
+            src_receiver: self.actors[receiver],
+            timelock,
+            timelock_kind: timelock_kind_to_anchor(timelock_kind),
+            amount,
+            ..CommitParams::default()
+        };
+        let instruction = anchor_htlc::commit(
+            &self.actors[sender],
+            &self.token_account(sender),
+            self.token,
+            params,
+        );
+        self.process(instruction, sender)
+    }
+
+    fn lock(&mut self, lock: Lock) -> Outcome {
+        let params = LockParams {
+            id: lock.id,
+            hashlock: lock.hashlock,
+            hash_algorithm: to_anchor(lock.hash_algorithm),
+            reward: lock.reward,
+            reward_timelock: lock.reward_timelock,
+            timelock: lock.timelock,
+            timelock_kind: timelock_kind_to_anchor(lock.timelock_kind),
+            amount: lock.amount,
+            src_receiver: self.actors[lock.receiver],
+            ..LockParams::default()
+        };
+        let instruction = anchor_htlc::lock(
+            &self.actors[lock.sender],
+            &self.token_account(lock.sender),
+            self.token,
+            params,
+        );
+        self.process(instruction, lock.sender)
+    }
+
+    fn add_lock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: u64,
+    ) -> Outcome {
+        let mut instruction = anchor_htlc::add_lock(
+            &self.actors[sender],
+            id,
+            hashlock,
+            to_anchor(hash_algorithm),
+            timelock,
+        );
+        instruction.accounts[0].pubkey = self.actors[signer];
+        self.process(instruction, signer)
+    }
+
+    fn add_lock_sig(&mut self, add_lock: AddLockSig) -> Outcome {
+        let hash_algorithm = to_anchor(add_lock.hash_algorithm);
+        let mut instructions = vec![];
+        // Signs the HTLC as committed, as a client reading it would; without an HTLC the
+        // call fails on its account anyway.
+        if let Some(htlc) = self.htlc(add_lock.sender, add_lock.id) {
+            let message = anchor_htlc::add_lock_message(
+                &add_lock.id,
+                &htlc,
+                &add_lock.hashlock,
+                hash_algorithm,
+                add_lock.timelock,
+            );
+            instructions.push(svm::ed25519_instruction(
+                &actor_keypair(add_lock.signature),
+                &message,
+            ));
+        }
+        instructions.push(anchor_htlc::add_lock_sig(
+            &self.actors[add_lock.payer],
+            &self.actors[add_lock.sender],
+            add_lock.id,
+            add_lock.hashlock,
+            hash_algorithm,
+            add_lock.timelock,
+        ));
+        self.process_all(&instructions, add_lock.payer).map(|_| ())
+    }
+
+    fn extend_timelock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        new_timelock: u64,
+    ) -> Outcome {
+        let mut instruction = anchor_htlc::extend_timelock(&self.actors[sender], id, new_timelock);
+        instruction.accounts[0].pubkey = self.actors[signer];
+        self.process(instruction, signer)
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Outcome {
+        let instruction = if paused {
+            anchor_htlc::pause(&self.admin)
+        } else {
+            anchor_htlc::unpause(&self.admin)
+        };
+        let admin = self.admin;
+        self.svm.process(&[instruction], &[admin]).map(|_| ())
+    }
+
+    fn redeem(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        secret: [u8; 32],
+    ) -> Outcome {
+        let instruction = anchor_htlc::redeem(
+            &self.actors[signer],
+            &self.actors[sender],
+            &self.actors[receiver],
+            self.token,
+            self.redeem_params(signer, sender, id, secret),
+        );
+        self.process(instruction, signer)
+    }
+
+    fn redeem_to(&mut self, redeem: RedeemTo) -> Outcome {
+        let sender = self.actors[redeem.sender];
+        let destination = self.token_account(redeem.destination);
+        let mut instructions = vec![];
+        if let Some(signature) = redeem.signature {
+            // Signs the hashlock and amount the HTLC holds, as a client reading it would.
+            let (hashlock, amount) = self
+                .state(redeem.sender, redeem.id)
+                .map_or(([0; 32], 0), |htlc| (htlc.hashlock, htlc.amount));
+            let message = anchor_htlc::redeem_to_message(
+                &sender,
+                &redeem.id,
+                &hashlock,
+                amount,
+                &destination,
+            );
+            instructions.push(svm::ed25519_instruction(
+                &actor_keypair(signature),
+                &message,
+            ));
+        }
+        instructions.push(anchor_htlc::redeem_to(
+            &self.actors[redeem.signer],
+            &sender,
+            &self.actors[redeem.receiver],
+            &destination,
+            self.token,
+            self.redeem_params(redeem.signer, redeem.sender, redeem.id, redeem.secret),
+        ));
+        self.process_all(&instructions, redeem.signer).map(|_| ())
+    }
+
+    fn redeem_batch(&mut self, signer: usize, items: &[BatchRedeem], atomic: bool) -> BatchOutcome {
+        let items: Vec<RedeemBatchItem> = items
+            .iter()
+            .map(|item| RedeemBatchItem {
+                id: item.id,
+                secret: item.secret,
+                sender: self.actors[item.sender],
+                src_receiver: self.actors[item.receiver],
+                // Left out when no reward is due, as a client reading the HTLC would.
+                sender_token_account: self
+                    .htlc(item.sender, item.id)
+                    .filter(|htlc| htlc.reward > 0)
+                    .map(|_| self.token_account(item.sender)),
+            })
+            .collect();
+        let instruction = anchor_htlc::redeem_batch(
+            &self.actors[signer],
+            Some(self.token_account(signer)),
+            self.token,
+            &items,
+            atomic,
+        );
+        let data = self.process_all(&[instruction], signer)?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn refund(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome {
+        let instruction = anchor_htlc::refund(
+            &self.actors[signer],
+            &self.actors[sender],
+            Some(self.token_account(sender)),
+            self.token,
+            id,
+        );
+        self.process(instruction, signer)
+    }
+
+    fn refund_batch(&mut self, signer: usize, items: &[(usize, [u8; 32])]) -> BatchOutcome {
+        let items: Vec<RefundBatchItem> = items
+            .iter()
+            .map(|&(sender, id)| RefundBatchItem {
+                id,
+                sender: self.actors[sender],
+                sender_token_account: self.token_account(sender),
+            })
+            .collect();
+        let instruction = anchor_htlc::refund_batch(&self.actors[signer], self.token, &items);
+        let data = self.process_all(&[instruction], signer)?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn close(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome {
+        let instruction =
+            anchor_htlc::close(&self.actors[signer], &self.actors[sender], self.token, id);
+        self.process(instruction, signer)
+    }
+
+    fn balance(&self, actor: usize) -> u64 {
+        self.token_balance(&self.token_account(actor))
+    }
+
+    fn held(&self, sender: usize, id: [u8; 32]) -> u64 {
+        self.escrowed(sender, id)
+    }
+
+    fn escrowed(&self, sender: usize, id: [u8; 32]) -> u64 {
+        self.token_balance(&self.htlcs[&(sender, id)].1)
+    }
+
+    fn state(&self, sender: usize, id: [u8; 32]) -> Option<State> {
+        let htlc = self.htlc(sender, id)?;
+        Some(State {
+            src_receiver: htlc.src_receiver,
+            hashlock: htlc.hashlock,
+            hash_algorithm: match htlc.hash_algorithm {
+                anchor_htlc::HashAlgorithm::Sha256 => HashAlgorithm::Sha256,
+                anchor_htlc::HashAlgorithm::Keccak256 => HashAlgorithm::Keccak256,
+            },
+            secret: htlc.secret,
+            amount: htlc.amount,
+            timelock: htlc.timelock,
+            timelock_kind: match htlc.timelock_kind {
+                anchor_htlc::TimelockKind::Timestamp => TimelockKind::Timestamp,
+                anchor_htlc::TimelockKind::Slot => TimelockKind::Slot,
+            },
+            reward: htlc.reward,
+            reward_timelock: htlc.reward_timelock,
+            status: match htlc.status {
+                anchor_htlc::HtlcStatus::Committed => HtlcStatus::Committed,
+                anchor_htlc::HtlcStatus::Locked => HtlcStatus::Locked,
+                anchor_htlc::HtlcStatus::Redeemed => HtlcStatus::Redeemed,
+                anchor_htlc::HtlcStatus::Refunded => HtlcStatus::Refunded,
+            },
+        })
+    }
+}
@@ -0,0 +1,934 @@
+//! Fuzzing of the HTLC state machine.
+//!
+//! A fuzz input is a sequence of [`Action`]s run against one of the programs in the
+//! in-process SVM of the integration tests. Actions cover every instruction on HTLCs,
+//! including the signed `add_lock_sig` and `redeem_to`, the batches and timelocks counted in
+//! seconds or slots. A model of the HTLCs predicts whether every instruction has to succeed,
+//! which HTLCs of a batch go through and what is paid out, and after every action the
+//! invariants are checked:
+//!
+//! - funds are conserved: what the actors hold plus what the HTLC accounts hold is constant,
+//! - an open HTLC holds exactly its amount and reward, a settled one nothing, so no HTLC pays
+//!   out twice,
+//! - the secret of a redeemed HTLC hashes to its hashlock,
+//! - a refund only succeeds once the timelock is reached, in the unit of the HTLC,
+//! - while the program is paused no HTLC is created or locked, but redeem and refund work.
+
+#[path = "../../tests/svm/mod.rs"]
+pub mod svm;
+
+pub mod anchor_htlc;
+pub mod native_htlc;
+
+use std::collections::HashMap;
+
+use anchor_lang::solana_program::{keccak, program_error::ProgramError, pubkey::Pubkey};
+use arbitrary::Arbitrary;
+use ed25519_dalek::Keypair;
+use sha2::{Digest, Sha256};
+use sol::{HashAlgorithm, HtlcStatus, TimelockKind};
+use svm::Svm;
+
+/// Number of accounts acting as senders, receivers and signers.
+pub const ACTORS: usize = 4;
+/// Number of ids each sender can use.
+pub const IDS: u8 = 4;
+/// Secrets redeems choose from; hashlocks are only ever set to the hash of the first two.
+pub const SECRETS: [[u8; 32]; 3] = [[1; 32], [2; 32], [3; 32]];
+/// Timelock bounds of the config, in seconds from now; timelock offsets fall on both sides.
+pub const MIN_TIMELOCK_DELTA: u64 = 60;
+pub const MAX_TIMELOCK_DELTA: u64 = 40_000;
+/// Maximum number of HTLCs in a batch.
+pub const MAX_BATCH_ITEMS: usize = 4;
+
+pub type Outcome = Result<(), ProgramError>;
+/// Outcome of a batch, whether each HTLC went through.
+pub type BatchOutcome = Result<Vec<bool>, ProgramError>;
+
+/// An HTLC as stored in its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub src_receiver: Pubkey,
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub secret: [u8; 32],
+    pub amount: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub status: HtlcStatus,
+}
+
+/// Arguments of a `lock`, actors given by index.
+#[derive(Clone, Copy, Debug)]
+pub struct Lock {
+    pub sender: usize,
+    pub receiver: usize,
+    pub id: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub amount: u64,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+}
+
+/// Arguments of an `add_lock_sig` paid by `payer`, with the message signed by the actor
+/// `signature`; actors given by index.
+#[derive(Clone, Copy, Debug)]
+pub struct AddLockSig {
+    pub payer: usize,
+    pub sender: usize,
+    pub id: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub timelock: u64,
+    pub signature: usize,
+}
+
+/// Arguments of a `redeem_to` signed by `signer`, with the destination authorized by a
+/// signature of the actor `signature` if any; actors given by index.
+#[derive(Clone, Copy, Debug)]
+pub struct RedeemTo {
+    pub signer: usize,
+    pub sender: usize,
+    pub receiver: usize,
+    pub destination: usize,
+    pub id: [u8; 32],
+    pub secret: [u8; 32],
+    pub signature: Option<usize>,
+}
+
+/// An HTLC of a `redeem_batch`, actors given by index.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchRedeem {
+    pub sender: usize,
+    pub receiver: usize,
+    pub id: [u8; 32],
+    pub secret: [u8; 32],
+}
+
+/// One of the HTLC programs running in an [`Svm`], with `ACTORS` funded actors.
+pub trait Program {
+    fn new() -> Self;
+
+    fn svm(&mut self) -> &mut Svm;
+
+    fn actor(&self, index: usize) -> Pubkey;
+
+    fn commit(
+        &mut self,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        amount: u64,
+        timelock: u64,
+        timelock_kind: TimelockKind,
+    ) -> Outcome;
+
+    fn lock(&mut self, lock: Lock) -> Outcome;
+
+    fn add_lock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: u64,
+    ) -> Outcome;
+
+    /// Adds the hashlock by a signature verified in the same transaction.
+    fn add_lock_sig(&mut self, add_lock: AddLockSig) -> Outcome;
+
+    fn extend_timelock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        new_timelock: u64,
+    ) -> Outcome;
+
+    /// Pauses or unpauses the program as the admin of the config.
+    fn set_paused(&mut self, paused: bool) -> Outcome;
+
+    fn redeem(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        secret: [u8; 32],
+    ) -> Outcome;
+
+    /// Redeems to the destination, preceded by the signature of the destination if any.
+    fn redeem_to(&mut self, redeem: RedeemTo) -> Outcome;
+
+    fn redeem_batch(&mut self, signer: usize, items: &[BatchRedeem], atomic: bool) -> BatchOutcome;
+
+    fn refund(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome;
+
+    /// Refunds the HTLCs given by sender and id.
+    fn refund_batch(&mut self, signer: usize, items: &[(usize, [u8; 32])]) -> BatchOutcome;
+
+    fn close(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome;
+
+    /// Funds of the actor in the currency the program locks.
+    fn balance(&self, actor: usize) -> u64;
+
+    /// Funds held by the accounts of an HTLC, rent included if it is paid in the same
+    /// currency.
+    fn held(&self, sender: usize, id: [u8; 32]) -> u64;
+
+    /// Funds an HTLC holds to pay out.
+    fn escrowed(&self, sender: usize, id: [u8; 32]) -> u64;
+
+    fn state(&self, sender: usize, id: [u8; 32]) -> Option<State>;
+}
+
+/// An HTLC of a [`Action::RedeemBatch`], given by indexes like the actions.
+#[derive(Arbitrary, Clone, Debug)]
+pub struct RedeemItem {
+    pub sender: u8,
+    pub receiver: u8,
+    pub id: u8,
+    pub secret: u8,
+}
+
+/// An HTLC of a [`Action::RefundBatch`], given by indexes like the actions.
+#[derive(Arbitrary, Clone, Debug)]
+pub struct RefundItem {
+    pub sender: u8,
+    pub id: u8,
+}
+
+/// An instruction of the fuzzed sequence. Actors, ids, secrets and hashlocks are indexes
+/// reduced into their domain; timelocks are offsets from the current time in the unit of
+/// the HTLC, slots if `slot` is set on its creation.
+#[derive(Arbitrary, Clone, Debug)]
+pub enum Action {
+    Commit {
+        sender: u8,
+        receiver: u8,
+        id: u8,
+        amount: u32,
+        timelock: u16,
+        slot: bool,
+    },
+    Lock {
+        sender: u8,
+        receiver: u8,
+        id: u8,
+        hashlock: u8,
+        keccak: bool,
+        amount: u32,
+        reward: u16,
+        reward_timelock: u16,
+        timelock: u16,
+        slot: bool,
+    },
+    AddLock {
+        signer: u8,
+        sender: u8,
+        id: u8,
+        hashlock: u8,
+        keccak: bool,
+        timelock: u16,
+    },
+    AddLockSig {
+        payer: u8,
+        sender: u8,
+        id: u8,
+        hashlock: u8,
+        keccak: bool,
+        timelock: u16,
+        signature: u8,
+    },
+    ExtendTimelock {
+        signer: u8,
+        sender: u8,
+        id: u8,
+        timelock: u16,
+    },
+    Redeem {
+        signer: u8,
+        sender: u8,
+        receiver: u8,
+        id: u8,
+        secret: u8,
+    },
+    RedeemTo {
+        signer: u8,
+        sender: u8,
+        receiver: u8,
+        destination: u8,
+        id: u8,
+        secret: u8,
+        signature: Option<u8>,
+    },
+    RedeemBatch {
+        signer: u8,
+        items: Vec<RedeemItem>,
+        atomic: bool,
+    },
+    Refund {
+        signer: u8,
+        sender: u8,
+        id: u8,
+    },
+    RefundBatch {
+        signer: u8,
+        items: Vec<RefundItem>,
+    },
+    Close {
+        signer: u8,
+        sender: u8,
+        id: u8,
+    },
+    Warp {
+        seconds: u32,
+    },
+    /// Moves the slot of the clock on, leaving its unix timestamp as it is.
+    WarpSlots {
+        slots: u32,
+    },
+    SetPaused {
+        paused: bool,
+    },
+    /// Moves the clock next to the timelock, the reward timelock or the end of the close
+    /// grace period of an HTLC, where the instructions change behaviour, in the unit of the
+    /// HTLC.
+    WarpTo {
+        sender: u8,
+        id: u8,
+        deadline: u8,
+        offset: i8,
+    },
+}
+
+fn actor(index: u8) -> usize {
+    index as usize % ACTORS
+}
+
+/// The keypair of an actor, signing the messages of `add_lock_sig` and `redeem_to`.
+pub fn actor_keypair(index: usize) -> Keypair {
+    svm::ed25519_keypair([index as u8 + 1; 32])
+}
+
+/// The address of an actor, the public key of its keypair.
+pub fn actor_address(index: usize) -> Pubkey {
+    Pubkey::new_from_array(actor_keypair(index).public.to_bytes())
+}
+
+fn htlc_id(index: u8) -> [u8; 32] {
+    [index % IDS + 1; 32]
+}
+
+/// The ids each sender can use.
+pub fn htlc_ids() -> impl Iterator<Item = [u8; 32]> {
+    (0..IDS).map(htlc_id)
+}
+
+fn secret(index: u8) -> [u8; 32] {
+    SECRETS[index as usize % SECRETS.len()]
+}
+
+/// The hash of one of the first two secrets, or the unset hashlock.
+fn hashlock(index: u8, hash_algorithm: HashAlgorithm) -> [u8; 32] {
+    match index % 3 {
+        2 => [0; 32],
+        index => hash(hash_algorithm, &SECRETS[index as usize]),
+    }
+}
+
+/// Whether a timelock `offset` from now in the unit of `timelock_kind` lies within the
+/// bounds of the config.
+fn timelock_in_bounds(timelock_kind: TimelockKind, offset: u16) -> bool {
+    let bounds =
+        timelock_kind.duration(MIN_TIMELOCK_DELTA)..=timelock_kind.duration(MAX_TIMELOCK_DELTA);
+    bounds.contains(&(offset as u64))
+}
+
+fn timelock_kind(slot: bool) -> TimelockKind {
+    if slot {
+        TimelockKind::Slot
+    } else {
+        TimelockKind::Timestamp
+    }
+}
+
+/// The time of the cluster clock in both units of a timelock.
+#[derive(Clone, Copy, Debug)]
+struct Clock {
+    unix_timestamp: u64,
+    slot: u64,
+}
+
+impl Clock {
+    fn now(&self, timelock_kind: TimelockKind) -> u64 {
+        match timelock_kind {
+            TimelockKind::Timestamp => self.unix_timestamp,
+            TimelockKind::Slot => self.slot,
+        }
+    }
+}
+
+/// The unit of the timelock of an HTLC in `model`, seconds for one that does not exist.
+fn existing_timelock_kind(
+    model: &HashMap<(usize, [u8; 32]), State>,
+    sender: usize,
+    id: [u8; 32],
+) -> TimelockKind {
+    model
+        .get(&(sender, id))
+        .map_or(TimelockKind::Timestamp, |htlc| htlc.timelock_kind)
+}
+
+/// Whether `htlc` can be redeemed now for `receiver` with `secret`.
+fn redeemable(htlc: &State, receiver: Pubkey, secret: &[u8; 32], clock: Clock) -> bool {
+    htlc.status == HtlcStatus::Locked
+        && hash(htlc.hash_algorithm, secret) == htlc.hashlock
+        && htlc.src_receiver == receiver
+        && clock.now(htlc.timelock_kind) < htlc.timelock
+}
+
+/// Whether `htlc` can be refunded now.
+fn refundable(htlc: &State, clock: Clock) -> bool {
+    !htlc.status.is_settled() && clock.now(htlc.timelock_kind) >= htlc.timelock
+}
+
+/// Adds the payout of redeeming `htlc` to `balances`: the amount goes to `destination`,
+/// the reward back to the sender before the reward timelock and afterwards to the signer,
+/// or to `destination` if the signer is the receiver.
+fn redeem_payout(
+    htlc: &State,
+    clock: Clock,
+    sender: usize,
+    signer: usize,
+    receiver: usize,
+    destination: usize,
+    balances: &mut [u64],
+) {
+    balances[destination] += htlc.amount;
+    if htlc.reward_timelock > clock.now(htlc.timelock_kind) {
+        balances[sender] += htlc.reward;
+    } else if signer == receiver {
+        balances[destination] += htlc.reward;
+    } else {
+        balances[signer] += htlc.reward;
+    }
+}
+
+fn hash_algorithm(keccak: bool) -> HashAlgorithm {
+    if keccak {
+        HashAlgorithm::Keccak256
+    } else {
+        HashAlgorithm::Sha256
+    }
+}
+
+fn hash(hash_algorithm: HashAlgorithm, data: &[u8]) -> [u8; 32] {
+    match hash_algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(data).into(),
+        HashAlgorithm::Keccak256 => keccak::hash(data).to_bytes(),
+    }
+}
+
+fn total_funds<P: Program>(program: &P) -> u64 {
+    let held: u64 = (0..ACTORS)
+        .flat_map(|sender| htlc_ids().map(move |id| (sender, id)))
+        .map(|(sender, id)| program.held(sender, id))
+        .sum();
+    (0..ACTORS).map(|actor| program.balance(actor)).sum::<u64>() + held
+}
+
+fn check_outcome<T: std::fmt::Debug>(
+    action: &Action,
+    outcome: &Result<T, ProgramError>,
+    valid: bool,
+) {
+    assert_eq!(
+        outcome.is_ok(),
+        valid,
+        "{action:?} returned {outcome:?}, but the model expected it to {}",
+        if valid { "succeed" } else { "fail" },
+    );
+}
+
+/// Runs `actions` against a fresh cluster of `P`, panicking when the program disagrees
+/// with the model or an invariant is broken.
+pub fn run<P: Program>(actions: &[Action]) {
+    let mut program = P::new();
+    let mut model: HashMap<(usize, [u8; 32]), State> = HashMap::new();
+    let total = total_funds(&program);
+    let mut paused = false;
+
+    for action in actions {
+        let clock = Clock {
+            unix_timestamp: program.svm().unix_timestamp() as u64,
+            slot: program.svm().slot(),
+        };
+        let before: Vec<u64> = (0..ACTORS).map(|actor| program.balance(actor)).collect();
+        // Actors whose balance may change, with their balance afterwards when it is known.
+        let mut expected: Vec<(usize, Option<u64>)> = vec![];
+
+        match *action {
+            Action::Commit {
+                sender,
+                receiver,
+                id,
+                amount,
+                timelock,
+                slot,
+            } => {
+                let (sender, receiver, id) = (actor(sender), actor(receiver), htlc_id(id));
+                let (offset, kind) = (timelock, timelock_kind(slot));
+                let (amount, timelock) = (amount as u64, clock.now(kind) + timelock as u64);
+                let outcome = program.commit(sender, receiver, id, amount, timelock, kind);
+                let valid = !paused
+                    && !model.contains_key(&(sender, id))
+                    && timelock_in_bounds(kind, offset)
+                    && amount != 0;
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    model.insert(
+                        (sender, id),
+                        State {
+                            src_receiver: program.actor(receiver),
+                            hashlock: [0; 32],
+                            hash_algorithm: HashAlgorithm::Sha256,
+                            secret: [0; 32],
+                            amount,
+                            timelock,
+                            timelock_kind: kind,
+                            reward: 0,
+                            reward_timelock: 0,
+                            status: HtlcStatus::Committed,
+                        },
+                    );
+                    expected.push((sender, None));
+                }
+            }
+            Action::Lock {
+                sender,
+                receiver,
+                id,
+                hashlock: hashlock_index,
+                keccak,
+                amount,
+                reward,
+                reward_timelock,
+                timelock,
+                slot,
+            } => {
+                let kind = timelock_kind(slot);
+                let lock = Lock {
+                    sender: actor(sender),
+                    receiver: actor(receiver),
+                    id: htlc_id(id),
+                    hashlock: hashlock(hashlock_index, hash_algorithm(keccak)),
+                    hash_algorithm: hash_algorithm(keccak),
+                    amount: amount as u64,
+                    reward: reward as u64,
+                    reward_timelock: clock.now(kind) + reward_timelock as u64,
+                    timelock: clock.now(kind) + timelock as u64,
+                    timelock_kind: kind,
+                };
+                let outcome = program.lock(lock);
+                let valid = !paused
+                    && !model.contains_key(&(lock.sender, lock.id))
+                    && timelock_in_bounds(kind, timelock)
+                    && lock.amount != 0
+                    && (lock.reward == 0 || lock.reward_timelock <= lock.timelock);
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    model.insert(
+                        (lock.sender, lock.id),
+                        State {
+                            src_receiver: program.actor(lock.receiver),
+                            hashlock: lock.hashlock,
+                            hash_algorithm: lock.hash_algorithm,
+                            secret: [0; 32],
+                            amount: lock.amount,
+                            timelock: lock.timelock,
+                            timelock_kind: kind,
+                            reward: lock.reward,
+                            reward_timelock: lock.reward_timelock,
+                            status: HtlcStatus::Locked,
+                        },
+                    );
+                    expected.push((lock.sender, None));
+                }
+            }
+            Action::AddLock {
+                signer,
+                sender,
+                id,
+                hashlock: hashlock_index,
+                keccak,
+                timelock,
+            } => {
+                let (signer, sender, id) = (actor(signer), actor(sender), htlc_id(id));
+                let hash_algorithm = hash_algorithm(keccak);
+                let (offset, kind) = (timelock, existing_timelock_kind(&model, sender, id));
+                let (hashlock, timelock) = (
+                    hashlock(hashlock_index, hash_algorithm),
+                    clock.now(kind) + timelock as u64,
+                );
+                let outcome =
+                    program.add_lock(signer, sender, id, hashlock, hash_algorithm, timelock);
+                let valid = !paused
+                    && signer == sender
+                    && timelock_in_bounds(kind, offset)
+                    && model
+                        .get(&(sender, id))
+                        .is_some_and(|htlc| htlc.status == HtlcStatus::Committed);
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    let htlc = model.get_mut(&(sender, id)).unwrap();
+                    htlc.hashlock = hashlock;
+                    htlc.hash_algorithm = hash_algorithm;
+                    htlc.timelock = timelock;
+                    htlc.status = HtlcStatus::Locked;
+                    expected.push((sender, None));
+                }
+            }
+            Action::AddLockSig {
+                payer,
+                sender,
+                id,
+                hashlock: hashlock_index,
+                keccak,
+                timelock,
+                signature,
+            } => {
+                let (sender, id) = (actor(sender), htlc_id(id));
+                let (offset, kind) = (timelock, existing_timelock_kind(&model, sender, id));
+                let add_lock = AddLockSig {
+                    payer: actor(payer),
+                    sender,
+                    id,
+                    hashlock: hashlock(hashlock_index, hash_algorithm(keccak)),
+                    hash_algorithm: hash_algorithm(keccak),
+                    timelock: clock.now(kind) + timelock as u64,
+                    signature: actor(signature),
+                };
+                let outcome = program.add_lock_sig(add_lock);
+                let valid = !paused
+                    && add_lock.signature == sender
+                    && timelock_in_bounds(kind, offset)
+                    && model
+                        .get(&(sender, id))
+                        .is_some_and(|htlc| htlc.status == HtlcStatus::Committed);
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    let htlc = model.get_mut(&(sender, id)).unwrap();
+                    htlc.hashlock = add_lock.hashlock;
+                    htlc.hash_algorithm = add_lock.hash_algorithm;
+                    htlc.timelock = add_lock.timelock;
+                    htlc.status = HtlcStatus::Locked;
+                }
+            }
+            Action::ExtendTimelock {
+                signer,
+                sender,
+                id,
+                timelock,
+            } => {
+                let (signer, sender, id) = (actor(signer), actor(sender), htlc_id(id));
+                let (offset, kind) = (timelock, existing_timelock_kind(&model, sender, id));
+                let (now, timelock) = (clock.now(kind), clock.now(kind) + timelock as u64);
+                let outcome = program.extend_timelock(signer, sender, id, timelock);
+                let valid = signer == sender
+                    && offset as u64 <= kind.duration(MAX_TIMELOCK_DELTA)
+                    && model.get(&(sender, id)).is_some_and(|htlc| {
+                        !htlc.status.is_settled() && now < htlc.timelock && timelock > htlc.timelock
+                    });
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    model.get_mut(&(sender, id)).unwrap().timelock = timelock;
+                }
+            }
+            Action::Redeem {
+                signer,
+                sender,
+                receiver,
+                id,
+                secret: secret_index,
+            } => {
+                let (signer, sender, receiver) = (actor(signer), actor(sender), actor(receiver));
+                let (id, secret) = (htlc_id(id), secret(secret_index));
+                let outcome = program.redeem(signer, sender, receiver, id, secret);
+                let receiver_key = program.actor(receiver);
+                let valid = model
+                    .get(&(sender, id))
+                    .is_some_and(|htlc| redeemable(htlc, receiver_key, &secret, clock));
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    let htlc = model.get_mut(&(sender, id)).unwrap();
+                    let mut after = before.clone();
+                    redeem_payout(htlc, clock, sender, signer, receiver, receiver, &mut after);
+                    htlc.status = HtlcStatus::Redeemed;
+                    htlc.secret = secret;
+                    expected.extend((0..ACTORS).map(|actor| (actor, Some(after[actor]))));
+                }
+            }
+            Action::RedeemTo {
+                signer,
+                sender,
+                receiver,
+                destination,
+                id,
+                secret: secret_index,
+                signature,
+            } => {
+                let redeem = RedeemTo {
+                    signer: actor(signer),
+                    sender: actor(sender),
+                    receiver: actor(receiver),
+                    destination: actor(destination),
+                    id: htlc_id(id),
+                    secret: secret(secret_index),
+                    signature: signature.map(actor),
+                };
+                let outcome = program.redeem_to(redeem);
+                let receiver_key = program.actor(redeem.receiver);
+                // The src_receiver authorizes the destination by signing the transaction or
+                // the message of `redeem_to`.
+                let valid = (redeem.signer == redeem.receiver
+                    || redeem.signature == Some(redeem.receiver))
+                    && model
+                        .get(&(redeem.sender, redeem.id))
+                        .is_some_and(|htlc| redeemable(htlc, receiver_key, &redeem.secret, clock));
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    let htlc = model.get_mut(&(redeem.sender, redeem.id)).unwrap();
+                    let mut after = before.clone();
+                    redeem_payout(
+                        htlc,
+                        clock,
+                        redeem.sender,
+                        redeem.signer,
+                        redeem.receiver,
+                        redeem.destination,
+                        &mut after,
+                    );
+                    htlc.status = HtlcStatus::Redeemed;
+                    htlc.secret = redeem.secret;
+                    expected.extend((0..ACTORS).map(|actor| (actor, Some(after[actor]))));
+                }
+            }
+            Action::RedeemBatch {
+                signer,
+                ref items,
+                atomic,
+            } => {
+                let signer = actor(signer);
+                let items: Vec<BatchRedeem> = items
+                    .iter()
+                    .take(MAX_BATCH_ITEMS)
+                    .map(|item| BatchRedeem {
+                        sender: actor(item.sender),
+                        receiver: actor(item.receiver),
+                        id: htlc_id(item.id),
+                        secret: secret(item.secret),
+                    })
+                    .collect();
+                let outcome = program.redeem_batch(signer, &items, atomic);
+                // The items are redeemed in order, so a repeated HTLC only goes through once.
+                let mut batch = model.clone();
+                let mut after = before.clone();
+                let redeemed: Vec<bool> = items
+                    .iter()
+                    .map(|item| {
+                        let receiver_key = program.actor(item.receiver);
+                        let Some(htlc) = batch
+                            .get_mut(&(item.sender, item.id))
+                            .filter(|htlc| redeemable(htlc, receiver_key, &item.secret, clock))
+                        else {
+                            return false;
+                        };
+                        redeem_payout(
+                            htlc,
+                            clock,
+                            item.sender,
+                            signer,
+                            item.receiver,
+                            item.receiver,
+                            &mut after,
+                        );
+                        htlc.status = HtlcStatus::Redeemed;
+                        htlc.secret = item.secret;
+                        true
+                    })
+                    .collect();
+                let valid = !atomic || redeemed.iter().all(|&redeemed| redeemed);
+                check_outcome(action, &outcome, valid);
+                if let Ok(outcome) = outcome {
+                    assert_eq!(outcome, redeemed, "{action:?} redeemed other HTLCs");
+                    model = batch;
+                    expected.extend((0..ACTORS).map(|actor| (actor, Some(after[actor]))));
+                }
+            }
+            Action::Refund { signer, sender, id } => {
+                let (signer, sender, id) = (actor(signer), actor(sender), htlc_id(id));
+                let outcome = program.refund(signer, sender, id);
+                let valid = model
+                    .get(&(sender, id))
+                    .is_some_and(|htlc| refundable(htlc, clock));
+                check_outcome(action, &outcome, valid);
+                if let (Ok(()), Some(htlc)) = (&outcome, model.get_mut(&(sender, id))) {
+                    assert!(
+                        clock.now(htlc.timelock_kind) >= htlc.timelock,
+                        "refunded before the timelock"
+                    );
+                    htlc.status = HtlcStatus::Refunded;
+                    let refund = before[sender] + htlc.amount + htlc.reward;
+                    expected.push((sender, Some(refund)));
+                }
+            }
+            Action::RefundBatch { signer, ref items } => {
+                let signer = actor(signer);
+                let items: Vec<(usize, [u8; 32])> = items
+                    .iter()
+                    .take(MAX_BATCH_ITEMS)
+                    .map(|item| (actor(item.sender), htlc_id(item.id)))
+                    .collect();
+                let outcome = program.refund_batch(signer, &items);
+                check_outcome(action, &outcome, true);
+                let mut after = before.clone();
+                let refunded: Vec<bool> = items
+                    .iter()
+                    .map(|&(sender, id)| {
+                        let Some(htlc) = model
+                            .get_mut(&(sender, id))
+                            .filter(|htlc| refundable(htlc, clock))
+                        else {
+                            return false;
+                        };
+                        htlc.status = HtlcStatus::Refunded;
+                        after[sender] += htlc.amount + htlc.reward;
+                        true
+                    })
+                    .collect();
+                assert_eq!(
+                    outcome.unwrap(),
+                    refunded,
+                    "{action:?} refunded other HTLCs"
+                );
+                expected.extend((0..ACTORS).map(|actor| (actor, Some(after[actor]))));
+            }
+            Action::Close { signer, sender, id } => {
+                let (signer, sender, id) = (actor(signer), actor(sender), htlc_id(id));
+                let outcome = program.close(signer, sender, id);
+                let valid = model.get(&(sender, id)).is_some_and(|htlc| {
+                    let kind = htlc.timelock_kind;
+                    htlc.status.is_settled()
+                        && clock.now(kind) >= htlc.timelock + kind.duration(sol::CLOSE_GRACE_PERIOD)
+                });
+                check_outcome(action, &outcome, valid);
+                if valid {
+                    model.remove(&(sender, id));
+                    expected.push((sender, None));
+                    // A keeper closing the HTLC is tipped out of its rent.
+                    expected.push((signer, None));
+                }
+            }
+            Action::Warp { seconds } => {
+                let seconds = seconds as u64 % (2 * sol::CLOSE_GRACE_PERIOD);
+                program
+                    .svm()
+                    .warp_to((clock.unix_timestamp + seconds) as i64);
+            }
+            Action::WarpSlots { slots } => {
+                let slots =
+                    slots as u64 % (2 * TimelockKind::Slot.duration(sol::CLOSE_GRACE_PERIOD));
+                program.svm().warp_to_slot(clock.slot + slots);
+            }
+            Action::SetPaused { paused: pause } => {
+                let outcome = program.set_paused(pause);
+                check_outcome(action, &outcome, true);
+                paused = pause;
+            }
+            Action::WarpTo {
+                sender,
+                id,
+                deadline,
+                offset,
+            } => {
+                if let Some(htlc) = model.get(&(actor(sender), htlc_id(id))) {
+                    let kind = htlc.timelock_kind;
+                    let deadline = match deadline % 3 {
+                        0 => htlc.timelock,
+                        1 => htlc.reward_timelock,
+                        _ => htlc.timelock + kind.duration(sol::CLOSE_GRACE_PERIOD),
+                    };
+                    let time = deadline
+                        .saturating_add_signed(offset as i64)
+                        .max(clock.now(kind));
+                    match kind {
+                        TimelockKind::Timestamp => program.svm().warp_to(time as i64),
+                        TimelockKind::Slot => program.svm().warp_to_slot(time),
+                    }
+                }
+            }
+        }
+
+        for (actor, &balance) in before.iter().enumerate() {
+            match expected.iter().find(|(changed, _)| *changed == actor) {
+                Some((_, Some(after))) => assert_eq!(
+                    program.balance(actor),
+                    *after,
+                    "{action:?} paid actor {actor} wrongly"
+                ),
+                Some((_, None)) => {}
+                None => assert_eq!(
+                    program.balance(actor),
+                    balance,
+                    "{action:?} changed the balance of actor {actor}"
+                ),
+            }
+        }
+        check_invariants(&program, &model, total);
+    }
+}
+
+fn check_invariants<P: Program>(
+    program: &P,
+    model: &HashMap<(usize, [u8; 32]), State>,
+    total: u64,
+) {
+    assert_eq!(total_funds(program), total, "funds are not conserved");
+    for sender in 0..ACTORS {
+        for id in htlc_ids() {
+            let state = program.state(sender, id);
+            assert_eq!(
+                state.as_ref(),
+                model.get(&(sender, id)),
+                "HTLC differs from model"
+            );
+            let Some(htlc) = state else {
+                continue;
+            };
+            if htlc.status == HtlcStatus::Redeemed {
+                assert_eq!(
+                    hash(htlc.hash_algorithm, &htlc.secret),
+                    htlc.hashlock,
+                    "secret does not match"
+                );
+            }
+            let escrow = if htlc.status.is_settled() {
+                0
+            } else {
+                htlc.amount + htlc.reward
+            };
+            assert_eq!(
+                program.escrowed(sender, id),
+                escrow,
+                "escrow differs from HTLC"
+            );
+        }
+    }
+}
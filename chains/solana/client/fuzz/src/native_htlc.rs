@@ -0,0 +1,328 @@
+//! The `native_htlc` program, locking lamports.
+
+use std::collections::HashMap;
+
+use anchor_lang::{
+    solana_program::{
+        instruction::Instruction, program_error::ProgramError, pubkey::Pubkey, rent::Rent,
+    },
+    AnchorDeserialize,
+};
+use htlc_client::native_htlc::{self, CommitParams, LockParams, RedeemBatchItem, RefundBatchItem};
+use sol::{HashAlgorithm, TimelockKind, HTLC};
+
+use crate::{
+    actor_address, actor_keypair, htlc_ids,
+    svm::{self, Svm},
+    AddLockSig, BatchOutcome, BatchRedeem, Lock, Outcome, Program, RedeemTo, State, ACTORS,
+    MAX_TIMELOCK_DELTA, MIN_TIMELOCK_DELTA,
+};
+
+/// Lamports each actor starts with.
+const FUNDS: u64 = 1 << 50;
+
+pub struct NativeHtlc {
+    svm: Svm,
+    /// Admin of the config, pausing and unpausing the program.
+    admin: Pubkey,
+    actors: Vec<Pubkey>,
+    /// HTLC accounts by sender and id, derived once as the invariants look them up often.
+    htlcs: HashMap<(usize, [u8; 32]), Pubkey>,
+}
+
+impl NativeHtlc {
+    fn process(&mut self, instruction: Instruction, signer: usize) -> Outcome {
+        self.process_all(&[instruction], signer).map(|_| ())
+    }
+
+    /// Processes `instructions` in one transaction, returning the return data of the last.
+    fn process_all(
+        &mut self,
+        instructions: &[Instruction],
+        signer: usize,
+    ) -> Result<Vec<u8>, ProgramError> {
+        let signer = self.actors[signer];
+        self.svm.process(instructions, &[signer])
+    }
+
+    fn htlc_address(&self, sender: usize, id: [u8; 32]) -> Pubkey {
+        self.htlcs[&(sender, id)]
+    }
+
+    fn htlc(&self, sender: usize, id: [u8; 32]) -> Option<HTLC> {
+        let account = self.svm.account(&self.htlc_address(sender, id))?;
+        native_htlc::decode_htlc(&account.data).ok()
+    }
+}
+
+impl Program for NativeHtlc {
+    fn new() -> Self {
+        let mut svm = Svm::new(&[(native_htlc::ID, sol::entry)]);
+        let admin = Pubkey::new_unique();
+        svm.airdrop(&admin, 1_000_000_000);
+        svm.set_upgrade_authority(&native_htlc::ID, &admin);
+        let instruction =
+            native_htlc::initialize_config(&admin, MIN_TIMELOCK_DELTA, MAX_TIMELOCK_DELTA);
+        svm.process(&[instruction], &[admin]).unwrap();
+        let actors: Vec<Pubkey> = (0..ACTORS)
+            .map(|index| {
+                let actor = actor_address(index);
+                svm.airdrop(&actor, FUNDS);
+                actor
+            })
+            .collect();
+        let htlcs = (0..ACTORS)
+            .flat_map(|sender| htlc_ids().map(move |id| (sender, id)))
+            .map(|(sender, id)| {
+                let htlc = native_htlc::htlc_address(&actors[sender], &id).0;
+                ((sender, id), htlc)
+            })
+            .collect();
+        NativeHtlc {
+            svm,
+            admin,
+            actors,
+            htlcs,
+        }
+    }
+
+    fn svm(&mut self) -> &mut Svm {
+        &mut self.svm
+    }
+
+    fn actor(&self, index: usize) -> Pubkey {
+        self.actors[index]
+    }
+
+    fn commit(
+        &mut self,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        amount: u64,
+        timelock: u64,
+        timelock_kind: TimelockKind,
+    ) -> Outcome {
+        let params = CommitParams {
+            id,
+            src_receiver: self.actors[receiver],
+            timelock,
+            timelock_kind,
+            amount,
+            ..CommitParams::default()
+        };
+        let instruction = native_htlc::commit(&self.actors[sender], params);
+        self.process(instruction, sender)
+    }
+
+    fn lock(&mut self, lock: Lock) -> Outcome {
+        let params = LockParams {
+            id: lock.id,
+            hashlock: lock.hashlock,
+            hash_algorithm: lock.hash_algorithm,
+            reward: lock.reward,
+            reward_timelock: lock.reward_timelock,
+            timelock: lock.timelock,
+            timelock_kind: lock.timelock_kind,
+            amount: lock.amount,
+            src_receiver: self.actors[lock.receiver],
+            ..LockParams::default()
+        };
+        let instruction = native_htlc::lock(&self.actors[lock.sender], params);
+        self.process(instruction, lock.sender)
+    }
+
+    fn add_lock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: u64,
+    ) -> Outcome {
+        let sender_key = self.actors[sender];
+        let mut instruction = native_htlc::add_lock(
+            &sender_key,
+            &sender_key,
+            id,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        );
+        instruction.accounts[0].pubkey = self.actors[signer];
+        instruction.accounts[1].pubkey = self.actors[signer];
+        self.process(instruction, signer)
+    }
+
+    fn add_lock_sig(&mut self, add_lock: AddLockSig) -> Outcome {
+        let mut instructions = vec![];
+        // Signs the HTLC as committed, as a client reading it would; without an HTLC the
+        // call fails on its account anyway.
+        if let Some(htlc) = self.htlc(add_lock.sender, add_lock.id) {
+            let message = native_htlc::add_lock_message(
+                &add_lock.id,
+                &htlc,
+                &add_lock.hashlock,
+                add_lock.hash_algorithm,
+                add_lock.timelock,
+            );
+            instructions.push(svm::ed25519_instruction(
+                &actor_keypair(add_lock.signature),
+                &message,
+            ));
+        }
+        instructions.push(native_htlc::add_lock_sig(
+            &self.actors[add_lock.payer],
+            &self.actors[add_lock.sender],
+            add_lock.id,
+            add_lock.hashlock,
+            add_lock.hash_algorithm,
+            add_lock.timelock,
+        ));
+        self.process_all(&instructions, add_lock.payer).map(|_| ())
+    }
+
+    fn extend_timelock(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        id: [u8; 32],
+        new_timelock: u64,
+    ) -> Outcome {
+        let mut instruction = native_htlc::extend_timelock(&self.actors[sender], id, new_timelock);
+        instruction.accounts[0].pubkey = self.actors[signer];
+        self.process(instruction, signer)
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Outcome {
+        let instruction = if paused {
+            native_htlc::pause(&self.admin)
+        } else {
+            native_htlc::unpause(&self.admin)
+        };
+        let admin = self.admin;
+        self.svm.process(&[instruction], &[admin]).map(|_| ())
+    }
+
+    fn redeem(
+        &mut self,
+        signer: usize,
+        sender: usize,
+        receiver: usize,
+        id: [u8; 32],
+        secret: [u8; 32],
+    ) -> Outcome {
+        let instruction = native_htlc::redeem(
+            &self.actors[signer],
+            &self.actors[sender],
+            &self.actors[receiver],
+            id,
+            secret,
+        );
+        self.process(instruction, signer)
+    }
+
+    fn redeem_to(&mut self, redeem: RedeemTo) -> Outcome {
+        let sender = self.actors[redeem.sender];
+        let destination = self.actors[redeem.destination];
+        let mut instructions = vec![];
+        if let Some(signature) = redeem.signature {
+            // Signs the hashlock and amount the HTLC holds, as a client reading it would.
+            let (hashlock, amount) = self
+                .state(redeem.sender, redeem.id)
+                .map_or(([0; 32], 0), |htlc| (htlc.hashlock, htlc.amount));
+            let message = native_htlc::redeem_to_message(
+                &sender,
+                &redeem.id,
+                &hashlock,
+                amount,
+                &destination,
+            );
+            instructions.push(svm::ed25519_instruction(
+                &actor_keypair(signature),
+                &message,
+            ));
+        }
+        instructions.push(native_htlc::redeem_to(
+            &self.actors[redeem.signer],
+            &sender,
+            &self.actors[redeem.receiver],
+            &destination,
+            redeem.id,
+            redeem.secret,
+        ));
+        self.process_all(&instructions, redeem.signer).map(|_| ())
+    }
+
+    fn redeem_batch(&mut self, signer: usize, items: &[BatchRedeem], atomic: bool) -> BatchOutcome {
+        let items: Vec<RedeemBatchItem> = items
+            .iter()
+            .map(|item| RedeemBatchItem {
+                id: item.id,
+                secret: item.secret,
+                sender: self.actors[item.sender],
+                src_receiver: self.actors[item.receiver],
+            })
+            .collect();
+        let instruction = native_htlc::redeem_batch(&self.actors[signer], &items, atomic);
+        let data = self.process_all(&[instruction], signer)?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn refund(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome {
+        let instruction = native_htlc::refund(&self.actors[signer], &self.actors[sender], id);
+        self.process(instruction, signer)
+    }
+
+    fn refund_batch(&mut self, signer: usize, items: &[(usize, [u8; 32])]) -> BatchOutcome {
+        let items: Vec<RefundBatchItem> = items
+            .iter()
+            .map(|&(sender, id)| RefundBatchItem {
+                id,
+                sender: self.actors[sender],
+            })
+            .collect();
+        let instruction = native_htlc::refund_batch(&self.actors[signer], &items);
+        let data = self.process_all(&[instruction], signer)?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn close(&mut self, signer: usize, sender: usize, id: [u8; 32]) -> Outcome {
+        let instruction = native_htlc::close(&self.actors[signer], &self.actors[sender], id);
+        self.process(instruction, signer)
+    }
+
+    fn balance(&self, actor: usize) -> u64 {
+        self.svm.lamports(&self.actors[actor])
+    }
+
+    fn held(&self, sender: usize, id: [u8; 32]) -> u64 {
+        self.svm.lamports(&self.htlc_address(sender, id))
+    }
+
+    fn escrowed(&self, sender: usize, id: [u8; 32]) -> u64 {
+        self.svm
+            .account(&self.htlc_address(sender, id))
+            .filter(|account| account.lamports > 0)
+            .map_or(0, |account| {
+                account.lamports - Rent::default().minimum_balance(account.data.len())
+            })
+    }
+
+    fn state(&self, sender: usize, id: [u8; 32]) -> Option<State> {
+        let htlc = self.htlc(sender, id)?;
+        Some(State {
+            src_receiver: htlc.src_receiver,
+            hashlock: htlc.hashlock,
+            hash_algorithm: htlc.hash_algorithm,
+            secret: htlc.secret,
+            amount: htlc.amount,
+            timelock: htlc.timelock,
+            timelock_kind: htlc.timelock_kind,
+            reward: htlc.reward,
+            reward_timelock: htlc.reward_timelock,
+            status: htlc.status,
+        })
+    }
+}
@@ -0,0 +1,319 @@
+//! Runs fixed action sequences through the fuzzing model, so the harness keeps building and
+//! agreeing with the programs under `cargo test`.
+
+use htlc_fuzz::{
+    anchor_htlc::AnchorHtlc, native_htlc::NativeHtlc, run, Action, RedeemItem, RefundItem,
+};
+
+/// Redeems a commit and refunds a lock with a reward and an extended timelock while the
+/// program is paused, both closed afterwards, with invalid calls in between.
+fn scenario() -> Vec<Action> {
+    vec![
+        Action::Commit {
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            amount: 1_000,
+            timelock: 600,
+            slot: false,
+        },
+        Action::Redeem {
+            signer: 2,
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            secret: 0,
+        },
+        Action::AddLock {
+            signer: 1,
+            sender: 0,
+            id: 0,
+            hashlock: 0,
+            keccak: false,
+            timelock: 900,
+        },
+        Action::AddLock {
+            signer: 0,
+            sender: 0,
+            id: 0,
+            hashlock: 0,
+            keccak: false,
+            timelock: 900,
+        },
+        Action::Lock {
+            sender: 1,
+            receiver: 2,
+            id: 0,
+            hashlock: 1,
+            keccak: true,
+            amount: 2_000,
+            reward: 50,
+            reward_timelock: 300,
+            timelock: 600,
+            slot: false,
+        },
+        Action::ExtendTimelock {
+            signer: 2,
+            sender: 1,
+            id: 0,
+            timelock: 700,
+        },
+        Action::ExtendTimelock {
+            signer: 1,
+            sender: 1,
+            id: 0,
+            timelock: 700,
+        },
+        Action::ExtendTimelock {
+            signer: 1,
+            sender: 1,
+            id: 0,
+            timelock: 650,
+        },
+        Action::SetPaused { paused: true },
+        Action::Commit {
+            sender: 2,
+            receiver: 3,
+            id: 0,
+            amount: 1_000,
+            timelock: 600,
+            slot: false,
+        },
+        Action::Redeem {
+            signer: 3,
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            secret: 1,
+        },
+        Action::WarpTo {
+            sender: 0,
+            id: 0,
+            deadline: 0,
+            offset: -1,
+        },
+        Action::Refund {
+            signer: 3,
+            sender: 1,
+            id: 0,
+        },
+        Action::Redeem {
+            signer: 3,
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            secret: 0,
+        },
+        Action::Redeem {
+            signer: 3,
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            secret: 0,
+        },
+        Action::Refund {
+            signer: 2,
+            sender: 1,
+            id: 0,
+        },
+        Action::SetPaused { paused: false },
+        Action::Commit {
+            sender: 2,
+            receiver: 3,
+            id: 0,
+            amount: 1_000,
+            timelock: 600,
+            slot: false,
+        },
+        Action::Close {
+            signer: 2,
+            sender: 1,
+            id: 0,
+        },
+        Action::WarpTo {
+            sender: 0,
+            id: 0,
+            deadline: 2,
+            offset: 0,
+        },
+        Action::Close {
+            signer: 2,
+            sender: 0,
+            id: 0,
+        },
+        Action::Close {
+            signer: 2,
+            sender: 1,
+            id: 0,
+        },
+        Action::Commit {
+            sender: 0,
+            receiver: 3,
+            id: 0,
+            amount: 1,
+            timelock: 1,
+            slot: false,
+        },
+    ]
+}
+
+/// Adds a hashlock by signature and redeems to an authorized destination, then redeems and
+/// refunds in batches, atomic and not, with HTLCs timed in slots that only the slot clock
+/// moves on.
+fn signed_batch_slot_scenario() -> Vec<Action> {
+    let redeem_item = |sender, receiver, id, secret| RedeemItem {
+        sender,
+        receiver,
+        id,
+        secret,
+    };
+    let refund_item = |sender, id| RefundItem { sender, id };
+    vec![
+        Action::Commit {
+            sender: 0,
+            receiver: 1,
+            id: 0,
+            amount: 1_000,
+            timelock: 600,
+            slot: true,
+        },
+        Action::AddLockSig {
+            payer: 2,
+            sender: 0,
+            id: 0,
+            hashlock: 0,
+            keccak: false,
+            timelock: 900,
+            signature: 1,
+        },
+        Action::AddLockSig {
+            payer: 2,
+            sender: 0,
+            id: 0,
+            hashlock: 0,
+            keccak: false,
+            timelock: 900,
+            signature: 0,
+        },
+        Action::Lock {
+            sender: 1,
+            receiver: 2,
+            id: 1,
+            hashlock: 1,
+            keccak: true,
+            amount: 2_000,
+            reward: 50,
+            reward_timelock: 0,
+            timelock: 700,
+            slot: false,
+        },
+        Action::Lock {
+            sender: 2,
+            receiver: 3,
+            id: 2,
+            hashlock: 0,
+            keccak: false,
+            amount: 3_000,
+            reward: 0,
+            reward_timelock: 0,
+            timelock: 600,
+            slot: true,
+        },
+        Action::RedeemTo {
+            signer: 3,
+            sender: 1,
+            receiver: 2,
+            destination: 0,
+            id: 1,
+            secret: 1,
+            signature: None,
+        },
+        Action::RedeemTo {
+            signer: 3,
+            sender: 1,
+            receiver: 2,
+            destination: 0,
+            id: 1,
+            secret: 1,
+            signature: Some(3),
+        },
+        Action::RedeemTo {
+            signer: 3,
+            sender: 1,
+            receiver: 2,
+            destination: 0,
+            id: 1,
+            secret: 1,
+            signature: Some(2),
+        },
+        Action::RedeemBatch {
+            signer: 3,
+            items: vec![
+                redeem_item(0, 1, 0, 0),
+                redeem_item(0, 1, 0, 0),
+                redeem_item(2, 3, 2, 1),
+            ],
+            atomic: true,
+        },
+        Action::RedeemBatch {
+            signer: 3,
+            items: vec![
+                redeem_item(0, 1, 0, 0),
+                redeem_item(0, 1, 0, 0),
+                redeem_item(2, 3, 2, 1),
+            ],
+            atomic: false,
+        },
+        Action::Warp { seconds: 1_000 },
+        Action::RefundBatch {
+            signer: 1,
+            items: vec![refund_item(2, 2)],
+        },
+        Action::WarpTo {
+            sender: 2,
+            id: 2,
+            deadline: 0,
+            offset: 0,
+        },
+        Action::RefundBatch {
+            signer: 1,
+            items: vec![refund_item(2, 2), refund_item(2, 2), refund_item(0, 0)],
+        },
+        Action::WarpSlots { slots: 300_000 },
+        Action::Close {
+            signer: 1,
+            sender: 1,
+            id: 1,
+        },
+        Action::Close {
+            signer: 0,
+            sender: 0,
+            id: 0,
+        },
+        Action::Close {
+            signer: 1,
+            sender: 2,
+            id: 2,
+        },
+    ]
+}
+
+#[test]
+fn native_htlc_scenario() {
+    run::<NativeHtlc>(&scenario());
+}
+
+#[test]
+fn anchor_htlc_scenario() {
+    run::<AnchorHtlc>(&scenario());
+}
+
+#[test]
+fn native_htlc_signed_batch_slot_scenario() {
+    run::<NativeHtlc>(&signed_batch_slot_scenario());
+}
+
+#[test]
+fn anchor_htlc_signed_batch_slot_scenario() {
+    run::<AnchorHtlc>(&signed_batch_slot_scenario());
+}
@@ -0,0 +1,802 @@
+//! Client for the `anchor_htlc` program locking spl tokens.
+//!
+//! `token_program` is the program owning the mint, Token or Token-2022. Accounts required
+//! by a transfer hook of the mint have to be appended to the accounts of the built
+//! `commit`, `lock`, `redeem`, `redeem_to`, `redeem_batch`, `refund`, `refund_batch` and
+//! `close` instructions.
+
+use anchor_lang::{
+    solana_program::{
+        bpf_loader_upgradeable,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+    AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id};
+use native::{accounts, instruction};
+use sha2::{Digest, Sha256};
+
+pub use native::{
+    AllowedMint, BatchBumps, Config, HashAlgorithm, HtlcStatus, Reward, TimelockKind, HTLC, ID,
+};
+
+/// Token accounts moving funds in and out of an HTLC.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenAccounts {
+    /// The mint of the locked token.
+    pub mint: Pubkey,
+    /// The program owning `mint`.
+    pub token_program: Pubkey,
+}
+
+/// Arguments of [`commit`].
+#[derive(Clone, Debug, Default)]
+pub struct CommitParams {
+    pub id: [u8; 32],
+    pub hop_chains: Vec<String>,
+    pub hop_assets: Vec<String>,
+    pub hop_addresses: Vec<String>,
+    pub dst_chain: String,
+    pub dst_asset: String,
+    pub dst_address: String,
+    pub src_asset: String,
+    pub src_receiver: Pubkey,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+    pub amount: u64,
+}
+
+/// Arguments of [`lock`].
+#[derive(Clone, Debug, Default)]
+pub struct LockParams {
+    pub id: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+    pub dst_chain: String,
+    pub dst_address: String,
+    pub dst_asset: String,
+    pub src_asset: String,
+    pub src_receiver: Pubkey,
+    pub amount: u64,
+}
+
+/// Arguments of [`redeem`].
+#[derive(Clone, Debug, Default)]
+pub struct RedeemParams {
+    pub id: [u8; 32],
+    pub secret: [u8; 32],
+    /// Token account of the sender, required when a reward is paid back to the sender.
+    pub sender_token_account: Option<Pubkey>,
+    /// Token account of the caller, required when the caller collects the reward.
+    pub user_signing_token_account: Option<Pubkey>,
+}
+
+/// One HTLC redeemed by [`redeem_batch`].
+#[derive(Clone, Copy, Debug)]
+pub struct RedeemBatchItem {
+    pub id: [u8; 32],
+    pub secret: [u8; 32],
+    pub sender: Pubkey,
+    pub src_receiver: Pubkey,
+    /// Token account of the sender, receiving a reward paid back to the sender; it can be
+    /// left out unless such a reward is due.
+    pub sender_token_account: Option<Pubkey>,
+}
+
+/// One HTLC refunded by [`refund_batch`].
+#[derive(Clone, Copy, Debug)]
+pub struct RefundBatchItem {
+    pub id: [u8; 32],
+    pub sender: Pubkey,
+    /// Token account of the sender, receiving the refund; its associated token account
+    /// unless the sender signs.
+    pub sender_token_account: Pubkey,
+}
+
+/// Derives the config account, together with its bump.
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], &ID)
+}
+
+/// Derives the program data account holding the upgrade authority of the program.
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// Derives the allowlist entry of `mint`, together with its bump.
+pub fn allowed_mint_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"allowed_mint", mint.as_ref()], &ID)
+}
+
+/// Derives the HTLC account of `sender` for `id`, together with its bump.
+pub fn htlc_address(sender: &Pubkey, id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"htlc", sender.as_ref(), id], &ID)
+}
+
+/// Derives the escrow token account of `sender` for `id`, together with its bump.
+pub fn htlc_token_account_address(sender: &Pubkey, id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"htlc_token_account", sender.as_ref(), id], &ID)
+}
+
+/// Derives the associated token account of `owner` for the mint of `token`, together with
+/// its bump.
+pub fn associated_token_address(owner: &Pubkey, token: TokenAccounts) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            owner.as_ref(),
+            token.token_program.as_ref(),
+            token.mint.as_ref(),
+        ],
+        &associated_token::ID,
+    )
+}
+
+/// Computes the id `get_commit_id` returns on-chain, `time` being the unix timestamp of
+/// the cluster clock when it is executed.
+pub fn commit_id(
+    sender: &Pubkey,
+    receiver: &Pubkey,
+    amount: u64,
+    time: u64,
+    timelock: u64,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(ID);
+    hasher.update(sender);
+    hasher.update(receiver);
+    hasher.update(amount.to_be_bytes());
+    hasher.update(time.to_be_bytes());
+    hasher.update(timelock.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Decodes the data of the config account, checking its discriminator.
+pub fn decode_config(data: &[u8]) -> anchor_lang::Result<Config> {
+    Config::try_deserialize(&mut &data[..])
+}
+
+/// Decodes the data of an allowlist entry, checking its discriminator.
+pub fn decode_allowed_mint(data: &[u8]) -> anchor_lang::Result<AllowedMint> {
+    AllowedMint::try_deserialize(&mut &data[..])
+}
+
+/// Decodes the data of an HTLC account, checking its discriminator.
+pub fn decode_htlc(data: &[u8]) -> anchor_lang::Result<HTLC> {
+    HTLC::try_deserialize(&mut &data[..])
+}
+
+/// Builds an `initialize_config` instruction; `authority`, the upgrade authority of the
+/// program, signs, pays and becomes the admin.
+pub fn initialize_config(
+    authority: &Pubkey,
+    min_timelock_delta: u64,
+    max_timelock_delta: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::InitializeConfig {
+            authority: *authority,
+            config: config_address().0,
+            program: ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeConfig {
+            min_timelock_delta,
+            max_timelock_delta,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `update_config` instruction; `admin` signs.
+pub fn update_config(
+    admin: &Pubkey,
+    min_timelock_delta: u64,
+    max_timelock_delta: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::UpdateConfig {
+            admin: *admin,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::UpdateConfig {
+            min_timelock_delta,
+            max_timelock_delta,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `pause` instruction; `admin` signs.
+pub fn pause(admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::UpdateConfig {
+            admin: *admin,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::Pause {}.data(),
+    }
+}
+
+/// Builds an `unpause` instruction; `admin` signs.
+pub fn unpause(admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::UpdateConfig {
+            admin: *admin,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::Unpause {}.data(),
+    }
+}
+
+/// Builds a `transfer_admin` instruction; `admin` signs.
+pub fn transfer_admin(admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::UpdateConfig {
+            admin: *admin,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::TransferAdmin {
+            new_admin: *new_admin,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `set_allowlist_enabled` instruction; `admin` signs.
+pub fn set_allowlist_enabled(admin: &Pubkey, enabled: bool) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::UpdateConfig {
+            admin: *admin,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::SetAllowlistEnabled { enabled }.data(),
+    }
+}
+
+/// Builds an `allow_mint` instruction; `admin` signs and pays for a new entry.
+pub fn allow_mint(admin: &Pubkey, mint: &Pubkey, min_amount: u64, max_amount: u64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::AllowMint {
+            admin: *admin,
+            config: config_address().0,
+            token_contract: *mint,
+            allowed_mint: allowed_mint_address(mint).0,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::AllowMint {
+            min_amount,
+            max_amount,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `disallow_mint` instruction; `admin` signs and receives the rent of the entry.
+pub fn disallow_mint(admin: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::DisallowMint {
+            admin: *admin,
+            config: config_address().0,
+            token_contract: *mint,
+            allowed_mint: allowed_mint_address(mint).0,
+        }
+        .to_account_metas(None),
+        data: instruction::DisallowMint {}.data(),
+    }
+}
+
+/// Builds a `get_commit_id` instruction; the id is returned as the return data.
+pub fn get_commit_id(
+    sender: &Pubkey,
+    receiver: &Pubkey,
+    amount: u64,
+    timelock: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::GetCommitId {
+            sender: *sender,
+            receiver: *receiver,
+        }
+        .to_account_metas(None),
+        data: instruction::GetCommitId { amount, timelock }.data(),
+    }
+}
+
+/// Builds a `commit` instruction; `sender` signs, pays and funds it from
+/// `sender_token_account`.
+pub fn commit(
+    sender: &Pubkey,
+    sender_token_account: &Pubkey,
+    token: TokenAccounts,
+    params: CommitParams,
+) -> Instruction {
+    let (htlc, commit_bump) = htlc_address(sender, &params.id);
+    Instruction {
+        program_id: ID,
+        accounts: accounts::Commit {
+            sender: *sender,
+            htlc,
+            config: config_address().0,
+            htlc_token_account: htlc_token_account_address(sender, &params.id).0,
+            token_contract: token.mint,
+            allowed_mint: allowed_mint_address(&token.mint).0,
+            sender_token_account: *sender_token_account,
+            token_program: token.token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Commit {
+            Id: params.id,
+            hopChains: params.hop_chains,
+            hopAssets: params.hop_assets,
+            hopAddresses: params.hop_addresses,
+            dst_chain: params.dst_chain,
+            dst_asset: params.dst_asset,
+            dst_address: params.dst_address,
+            src_asset: params.src_asset,
+            src_receiver: params.src_receiver,
+            timelock: params.timelock,
+            timelock_kind: params.timelock_kind,
+            amount: params.amount,
+            commit_bump,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `lock` instruction; `sender` signs, pays and funds it from
+/// `sender_token_account`.
+pub fn lock(
+    sender: &Pubkey,
+    sender_token_account: &Pubkey,
+    token: TokenAccounts,
+    params: LockParams,
+) -> Instruction {
+    let (htlc, lock_bump) = htlc_address(sender, &params.id);
+    Instruction {
+        program_id: ID,
+        accounts: accounts::Lock {
+            sender: *sender,
+            htlc,
+            config: config_address().0,
+            htlc_token_account: htlc_token_account_address(sender, &params.id).0,
+            token_contract: token.mint,
+            allowed_mint: allowed_mint_address(&token.mint).0,
+            sender_token_account: *sender_token_account,
+            token_program: token.token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Lock {
+            Id: params.id,
+            hashlock: params.hashlock,
+            hash_algorithm: params.hash_algorithm,
+            reward: params.reward,
+            reward_timelock: params.reward_timelock,
+            timelock: params.timelock,
+            timelock_kind: params.timelock_kind,
+            dst_chain: params.dst_chain,
+            dst_address: params.dst_address,
+            dst_asset: params.dst_asset,
+            src_asset: params.src_asset,
+            src_receiver: params.src_receiver,
+            amount: params.amount,
+            lock_bump,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `add_lock` instruction; `sender` signs and pays.
+pub fn add_lock(
+    sender: &Pubkey,
+    id: [u8; 32],
+    hashlock: [u8; 32],
+    hash_algorithm: HashAlgorithm,
+    timelock: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::AddLock {
+            sender: *sender,
+            htlc: htlc_address(sender, &id).0,
+            config: config_address().0,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::AddLock {
+            Id: id,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        }
+        .data(),
+    }
+}
+
+/// The message the sender signs to authorize [`add_lock_sig`] on `htlc`, the committed
+/// HTLC with `id` as decoded by [`decode_htlc`].
+pub fn add_lock_message(
+    id: &[u8; 32],
+    htlc: &HTLC,
+    hashlock: &[u8; 32],
+    hash_algorithm: HashAlgorithm,
+    timelock: u64,
+) -> Vec<u8> {
+    [
+        ID.as_ref(),
+        htlc.sender.as_ref(),
+        id,
+        htlc.src_receiver.as_ref(),
+        &htlc.amount.to_be_bytes(),
+        &htlc.timelock.to_be_bytes(),
+        hashlock,
+        &timelock.to_be_bytes(),
+        &[hash_algorithm as u8],
+    ]
+    .concat()
+}
+
+/// Builds an `add_lock_sig` instruction; `payer` signs and pays. The sender authorizes
+/// the hashlock by an ed25519 signature over [`add_lock_message`] verified by an Ed25519
+/// program instruction right before this one.
+pub fn add_lock_sig(
+    payer: &Pubkey,
+    sender: &Pubkey,
+    id: [u8; 32],
+    hashlock: [u8; 32],
+    hash_algorithm: HashAlgorithm,
+    timelock: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::AddLockSig {
+            payer: *payer,
+            sender: *sender,
+            htlc: htlc_address(sender, &id).0,
+            config: config_address().0,
+            ix_sysvar: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::AddLockSig {
+            Id: id,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `extend_timelock` instruction; `sender` signs.
+pub fn extend_timelock(sender: &Pubkey, id: [u8; 32], new_timelock: u64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::ExtendTimelock {
+            sender: *sender,
+            htlc: htlc_address(sender, &id).0,
+            config: config_address().0,
+        }
+        .to_account_metas(None),
+        data: instruction::ExtendTimelock {
+            Id: id,
+            new_timelock,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `redeem` instruction paying to the associated token account of
+/// `src_receiver`, which is created if needed; `user_signing` signs and pays.
+pub fn redeem(
+    user_signing: &Pubkey,
+    sender: &Pubkey,
+    src_receiver: &Pubkey,
+    token: TokenAccounts,
+    params: RedeemParams,
+) -> Instruction {
+    let (htlc, htlc_bump) = htlc_address(sender, &params.id);
+    Instruction {
+        program_id: ID,
+        accounts: accounts::Redeem {
+            user_signing: *user_signing,
+            htlc,
+            htlc_token_account: htlc_token_account_address(sender, &params.id).0,
+            src_receiver_token_account: get_associated_token_address_with_program_id(
+                src_receiver,
+                &token.mint,
+                &token.token_program,
+            ),
+            sender_token_account: params.sender_token_account,
+            user_signing_token_account: params.user_signing_token_account,
+            sender: *sender,
+            src_receiver: *src_receiver,
+            token_contract: token.mint,
+            system_program: system_program::ID,
+            token_program: token.token_program,
+            associated_token_program: associated_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Redeem {
+            Id: params.id,
+            secret: params.secret,
+            htlc_bump,
+        }
+        .data(),
+    }
+}
+
+/// The message the src_receiver signs to authorize `destination_token_account` for
+/// [`redeem_to`] of the HTLC with `hashlock` and `amount`.
+pub fn redeem_to_message(
+    sender: &Pubkey,
+    id: &[u8; 32],
+    hashlock: &[u8; 32],
+    amount: u64,
+    destination_token_account: &Pubkey,
+) -> Vec<u8> {
+    [
+        ID.as_ref(),
+        sender.as_ref(),
+        id,
+        hashlock,
+        &amount.to_be_bytes(),
+        destination_token_account.as_ref(),
+    ]
+    .concat()
+}
+
+/// Builds a `redeem_to` instruction paying to `destination_token_account`, an existing
+/// token account of the mint; `user_signing` signs. The src_receiver authorizes the
+/// destination by signing as `user_signing`, or by an ed25519 signature over
+/// [`redeem_to_message`] verified by an Ed25519 program instruction right before this one.
+pub fn redeem_to(
+    user_signing: &Pubkey,
+    sender: &Pubkey,
+    src_receiver: &Pubkey,
+    destination_token_account: &Pubkey,
+    token: TokenAccounts,
+    params: RedeemParams,
+) -> Instruction {
+    let (htlc, htlc_bump) = htlc_address(sender, &params.id);
+    Instruction {
+        program_id: ID,
+        accounts: accounts::RedeemTo {
+            user_signing: *user_signing,
+            htlc,
+            htlc_token_account: htlc_token_account_address(sender, &params.id).0,
+            destination_token_account: *destination_token_account,
+            sender_token_account: params.sender_token_account,
+            user_signing_token_account: params.user_signing_token_account,
+            sender: *sender,
+            src_receiver: *src_receiver,
+            token_contract: token.mint,
+            ix_sysvar: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            token_program: token.token_program,
+        }
+        .to_account_metas(None),
+        data: instruction::RedeemTo {
+            Id: params.id,
+            secret: params.secret,
+            htlc_bump,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `redeem_batch` instruction paying to the associated token accounts of the
+/// src_receivers, which have to exist; `user_signing` signs and collects due rewards into
+/// `user_signing_token_account`. With `atomic` unset, HTLCs that can not be redeemed are
+/// skipped instead of failing the instruction.
+pub fn redeem_batch(
+    user_signing: &Pubkey,
+    user_signing_token_account: Option<Pubkey>,
+    token: TokenAccounts,
+    items: &[RedeemBatchItem],
+    atomic: bool,
+) -> Instruction {
+    let mut accounts = accounts::RedeemBatch {
+        user_signing: *user_signing,
+        user_signing_token_account,
+        token_contract: token.mint,
+        system_program: system_program::ID,
+        token_program: token.token_program,
+    }
+    .to_account_metas(None);
+    let mut bumps = Vec::with_capacity(items.len());
+    for item in items {
+        let (htlc, htlc_bump) = htlc_address(&item.sender, &item.id);
+        let (receiver_token_account, receiver_token_account_bump) =
+            associated_token_address(&item.src_receiver, token);
+        bumps.push(BatchBumps {
+            htlc: htlc_bump,
+            associated_token_account: receiver_token_account_bump,
+        });
+        accounts.extend([
+            AccountMeta::new(htlc, false),
+            AccountMeta::new(htlc_token_account_address(&item.sender, &item.id).0, false),
+            AccountMeta::new(item.sender, false),
+            AccountMeta::new(receiver_token_account, false),
+            // The program id stands in for a left out account, as for optional accounts.
+            item.sender_token_account
+                .map_or(AccountMeta::new_readonly(ID, false), |account| {
+                    AccountMeta::new(account, false)
+                }),
+        ]);
+    }
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction::RedeemBatch {
+            ids: items.iter().map(|item| item.id).collect(),
+            secrets: items.iter().map(|item| item.secret).collect(),
+            bumps,
+            atomic,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `refund` instruction paying back to `sender_token_account`, any token account
+/// of `sender` if the sender signs and its associated token account for a keeper, or
+/// without it to the associated token account of `sender`, which is created if needed;
+/// `user_signing`, the sender or a keeper, signs and pays.
+pub fn refund(
+    user_signing: &Pubkey,
+    sender: &Pubkey,
+    sender_token_account: Option<Pubkey>,
+    token: TokenAccounts,
+    id: [u8; 32],
+) -> Instruction {
+    let (htlc, htlc_bump) = htlc_address(sender, &id);
+    Instruction {
+        program_id: ID,
+        accounts: accounts::Refund {
+            user_signing: *user_signing,
+            htlc,
+            htlc_token_account: htlc_token_account_address(sender, &id).0,
+            sender: *sender,
+            token_contract: token.mint,
+            sender_token_account,
+            sender_associated_token_account: sender_token_account.is_none().then(|| {
+                get_associated_token_address_with_program_id(
+                    sender,
+                    &token.mint,
+                    &token.token_program,
+                )
+            }),
+            system_program: system_program::ID,
+            token_program: token.token_program,
+            associated_token_program: associated_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Refund { Id: id, htlc_bump }.data(),
+    }
+}
+
+/// Builds a `refund_batch` instruction paying back to the token accounts of the items;
+/// `user_signing` signs. HTLCs that can not be refunded are skipped.
+pub fn refund_batch(
+    user_signing: &Pubkey,
+    token: TokenAccounts,
+    items: &[RefundBatchItem],
+) -> Instruction {
+    let mut accounts = accounts::RefundBatch {
+        user_signing: *user_signing,
+        token_contract: token.mint,
+        system_program: system_program::ID,
+        token_program: token.token_program,
+    }
+    .to_account_metas(None);
+    let mut bumps = Vec::with_capacity(items.len());
+    for item in items {
+        let (htlc, htlc_bump) = htlc_address(&item.sender, &item.id);
+        bumps.push(BatchBumps {
+            htlc: htlc_bump,
+            associated_token_account: associated_token_address(&item.sender, token).1,
+        });
+        accounts.extend([
+            AccountMeta::new(htlc, false),
+            AccountMeta::new(htlc_token_account_address(&item.sender, &item.id).0, false),
+            AccountMeta::new(item.sender, false),
+            AccountMeta::new(item.sender_token_account, false),
+        ]);
+    }
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction::RefundBatch {
+            ids: items.iter().map(|item| item.id).collect(),
+            bumps,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `close` instruction; `user_signing` signs and is paid the keeper tip unless it
+/// is the sender. Tokens left in the escrow are swept to the associated token account of
+/// `sender`, which is created if needed at the expense of `user_signing`.
+pub fn close(
+    user_signing: &Pubkey,
+    sender: &Pubkey,
+    token: TokenAccounts,
+    id: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::Close {
+            user_signing: *user_signing,
+            htlc: htlc_address(sender, &id).0,
+            htlc_token_account: htlc_token_account_address(sender, &id).0,
+            sender: *sender,
+            token_contract: token.mint,
+            sender_token_account: Some(get_associated_token_address_with_program_id(
+                sender,
+                &token.mint,
+                &token.token_program,
+            )),
+            system_program: system_program::ID,
+            token_program: token.token_program,
+            associated_token_program: associated_token::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Close { Id: id }.data(),
+    }
+}
+
+/// Builds a `getDetails` instruction; the HTLC is returned as the return data.
+pub fn get_details(sender: &Pubkey, id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::GetDetails {
+            sender: *sender,
+            htlc: htlc_address(sender, &id).0,
+        }
+        .to_account_metas(None),
+        data: instruction::GetDetails { Id: id }.data(),
+    }
+}
+
+/// Builds a `getRewardDetails` instruction; the reward is returned as the return data.
+pub fn get_reward_details(sender: &Pubkey, id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts::GetDetails {
+            sender: *sender,
+            htlc: htlc_address(sender, &id).0,
+        }
+        .to_account_metas(None),
+        data: instruction::GetRewardDetails { Id: id }.data(),
+    }
+}
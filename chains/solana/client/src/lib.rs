@@ -0,0 +1,9 @@
+//! Off-chain helpers for calling the Solana HTLC programs from Rust.
+//!
+//! [`native_htlc`] wraps the sol program and [`anchor_htlc`] the spl token program. Both
+//! expose typed instruction builders, PDA derivation, decoding of the `HTLC` account and
+//! an off-chain `get_commit_id`. The builders only assemble instructions; signing and
+//! sending the transaction is left to the caller.
+
+pub mod anchor_htlc;
+pub mod native_htlc;
@@ -0,0 +1,2022 @@
+//! Integration tests of the `anchor_htlc` program.
+//!
+//! As for `native_htlc`, the HTLC account is derived from the sender, so passing another
+//! sender fails the seeds constraint before `NotSender` is checked on the HTLC account.
+//! `NotSender` and `UnauthorizedAccess` are still returned for token accounts of the wrong
+//! owner. `NotOwner` is not returned by any instruction.
+//!
+//! Redeem and refund close the escrow token account, so calling either again on a settled
+//! HTLC fails to load the escrow before `AlreadyRedeemed` or `AlreadyRefunded` is checked.
+
+mod svm;
+
+use anchor_lang::{
+    error::ErrorCode,
+    prelude::ProgramError,
+    solana_program::{
+        account_info::AccountInfo,
+        clock::DEFAULT_MS_PER_SLOT,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        keccak,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction::{self, SystemError},
+    },
+    AnchorDeserialize,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id, spl_associated_token_account,
+    },
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            confidential_transfer, transfer_fee, transfer_fee::TransferFeeConfig, transfer_hook,
+            BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        state::{Account as TokenAccount, Mint},
+    },
+};
+use htlc_client::anchor_htlc::{
+    self, CommitParams, HashAlgorithm, HtlcStatus, LockParams, RedeemBatchItem, RedeemParams,
+    RefundBatchItem, Reward, TimelockKind, TokenAccounts, HTLC,
+};
+use native::{
+    AdminTransferred, AllowlistUpdated, ConfigUpdated, HTLCError, MintAllowed, MintDisallowed,
+    Paused, TokenClosed, TokenCommitted, TokenLockAdded, TokenLocked, TokenRedeemed, TokenRefunded,
+    TokenTimelockExtended, Unpaused, CLOSE_GRACE_PERIOD, KEEPER_TIP, MAX_HOPS, MAX_STRING_LENGTH,
+};
+use sha2::{Digest, Sha256};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::{
+    error::TransferHookError,
+    get_extra_account_metas_address,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+use svm::Account;
+use svm::Svm;
+
+const AMOUNT: u64 = 1_000_000;
+const REWARD: u64 = 10_000;
+const SECRET: [u8; 32] = [7; 32];
+const DECIMALS: u8 = 6;
+const BALANCE: u64 = 100 * AMOUNT;
+/// Transfer fee of the Token-2022 fee mints, in basis points.
+const FEE_BASIS_POINTS: u16 = 100;
+const MIN_TIMELOCK_DELTA: u64 = 60;
+const MAX_TIMELOCK_DELTA: u64 = 30 * 24 * 60 * 60;
+/// The transfer hook program of mints with the `TransferHook` extension.
+const HOOK_PROGRAM: Pubkey = Pubkey::new_from_array([9; 32]);
+/// The extra account the transfer hook requires, counting the tokens it saw transferred.
+const HOOK_COUNTER: Pubkey = Pubkey::new_from_array([10; 32]);
+
+struct Test {
+    svm: Svm,
+    admin: Pubkey,
+    mint_authority: Pubkey,
+    token: TokenAccounts,
+    sender: Pubkey,
+    src_receiver: Pubkey,
+    solver: Pubkey,
+    timelock: u64,
+}
+
+impl Test {
+    fn new() -> Self {
+        Self::with_mint(spl_token::ID, &[])
+    }
+
+    /// Creates a cluster with a mint of `token_program` carrying `extensions`, and funds
+    /// the sender with `BALANCE` tokens.
+    fn with_mint(token_program: Pubkey, extensions: &[ExtensionType]) -> Self {
+        let mut svm = Svm::new(&[
+            (anchor_htlc::ID, native::entry),
+            (HOOK_PROGRAM, transfer_hook),
+        ]);
+        let admin = svm.new_funded_account();
+        svm.set_upgrade_authority(&anchor_htlc::ID, &admin);
+        let instruction =
+            anchor_htlc::initialize_config(&admin, MIN_TIMELOCK_DELTA, MAX_TIMELOCK_DELTA);
+        svm.process(&[instruction], &[admin]).unwrap();
+        let mint_authority = svm.new_funded_account();
+        let sender = svm.new_funded_account();
+        let src_receiver = svm.new_funded_account();
+        let solver = svm.new_funded_account();
+        let timelock = svm.unix_timestamp() as u64 + 3600;
+        let mut test = Test {
+            svm,
+            admin,
+            mint_authority,
+            token: TokenAccounts {
+                mint: Pubkey::default(),
+                token_program,
+            },
+            sender,
+            src_receiver,
+            solver,
+            timelock,
+        };
+        test.token = test.create_mint(token_program, extensions);
+        let sender_token_account = test.create_token_account(&sender, test.token);
+        test.mint_to(&sender_token_account, test.token, BALANCE);
+        test
+    }
+
+    fn process(
+        &mut self,
+        instruction: Instruction,
+        signers: &[Pubkey],
+    ) -> Result<Vec<u8>, ProgramError> {
+        self.svm.process(&[instruction], signers)
+    }
+
+    fn create_mint(
+        &mut self,
+        token_program: Pubkey,
+        extensions: &[ExtensionType],
+    ) -> TokenAccounts {
+        let mint = Pubkey::new_unique();
+        let authority = self.mint_authority;
+        let space = ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap();
+        let mut instructions = vec![system_instruction::create_account(
+            &authority,
+            &mint,
+            1_000_000_000,
+            space as u64,
+            &token_program,
+        )];
+        for extension in extensions {
+            instructions.push(match extension {
+                ExtensionType::TransferFeeConfig => {
+                    transfer_fee::instruction::initialize_transfer_fee_config(
+                        &token_program,
+                        &mint,
+                        None,
+                        None,
+                        FEE_BASIS_POINTS,
+                        u64::MAX,
+                    )
+                    .unwrap()
+                }
+                ExtensionType::TransferHook => transfer_hook::instruction::initialize(
+                    &token_program,
+                    &mint,
+                    None,
+                    Some(HOOK_PROGRAM),
+                )
+                .unwrap(),
+                ExtensionType::NonTransferable => {
+                    spl_token_2022::instruction::initialize_non_transferable_mint(
+                        &token_program,
+                        &mint,
+                    )
+                    .unwrap()
+                }
+                ExtensionType::ConfidentialTransferMint => {
+                    confidential_transfer::instruction::initialize_mint(
+                        &token_program,
+                        &mint,
+                        None,
+                        true,
+                        None,
+                    )
+                    .unwrap()
+                }
+                extension => panic!("unsupported extension {extension:?}"),
+            });
+        }
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(
+                &token_program,
+                &mint,
+                &authority,
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        );
+        self.svm.process(&instructions, &[authority, mint]).unwrap();
+        if extensions.contains(&ExtensionType::TransferHook) {
+            let metas = [ExtraAccountMeta::new_with_pubkey(&HOOK_COUNTER, false, true).unwrap()];
+            let mut data = vec![0; ExtraAccountMetaList::size_of(metas.len()).unwrap()];
+            ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &metas).unwrap();
+            let validation = get_extra_account_metas_address(&mint, &HOOK_PROGRAM);
+            self.set_hook_account(validation, data);
+            self.set_hook_account(HOOK_COUNTER, vec![0; 8]);
+        }
+        TokenAccounts {
+            mint,
+            token_program,
+        }
+    }
+
+    fn set_hook_account(&mut self, key: Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: HOOK_PROGRAM,
+            executable: false,
+        };
+        self.svm.set_account(key, account);
+    }
+
+    /// The accounts a transfer of a `TransferHook` mint passes on to the hook.
+    fn hook_accounts(&self) -> [AccountMeta; 3] {
+        [
+            AccountMeta::new_readonly(
+                get_extra_account_metas_address(&self.token.mint, &HOOK_PROGRAM),
+                false,
+            ),
+            AccountMeta::new_readonly(HOOK_PROGRAM, false),
+            AccountMeta::new(HOOK_COUNTER, false),
+        ]
+    }
+
+    /// Tokens the transfer hook saw transferred.
+    fn hook_count(&self) -> u64 {
+        let data = &self.svm.account(&HOOK_COUNTER).unwrap().data;
+        u64::from_le_bytes(data[..8].try_into().unwrap())
+    }
+
+    /// Creates the associated token account of `owner`.
+    fn create_token_account(&mut self, owner: &Pubkey, token: TokenAccounts) -> Pubkey {
+        let payer = self.mint_authority;
+        let instruction =
+            spl_associated_token_account::instruction::create_associated_token_account(
+                &payer,
+                owner,
+                &token.mint,
+                &token.token_program,
+            );
+        self.process(instruction, &[payer]).unwrap();
+        self.token_account(owner, token)
+    }
+
+    fn token_account(&self, owner: &Pubkey, token: TokenAccounts) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &token.mint, &token.token_program)
+    }
+
+    fn mint_to(&mut self, account: &Pubkey, token: TokenAccounts, amount: u64) {
+        let authority = self.mint_authority;
+        let instruction = spl_token_2022::instruction::mint_to(
+            &token.token_program,
+            &token.mint,
+            account,
+            &authority,
+            &[],
+            amount,
+        )
+        .unwrap();
+        self.process(instruction, &[authority]).unwrap();
+    }
+
+    /// Token balance of `account`, 0 if it does not exist.
+    fn balance(&self, account: &Pubkey) -> u64 {
+        self.svm.account(account).map_or(0, |account| {
+            StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                .unwrap()
+                .base
+                .amount
+        })
+    }
+
+    fn sender_token_account(&self) -> Pubkey {
+        self.token_account(&self.sender, self.token)
+    }
+
+    fn receiver_token_account(&self) -> Pubkey {
+        self.token_account(&self.src_receiver, self.token)
+    }
+
+    fn commit_params(&self, id: [u8; 32]) -> CommitParams {
+        CommitParams {
+            id,
+            hop_chains: vec!["SOLANA".into()],
+            hop_assets: vec!["USDC".into()],
+            hop_addresses: vec!["0x1".into()],
+            dst_chain: "ETHEREUM_SEPOLIA".into(),
+            dst_asset: "USDC".into(),
+            dst_address: "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3".into(),
+            src_asset: "USDC".into(),
+            src_receiver: self.src_receiver,
+            timelock: self.timelock,
+            timelock_kind: TimelockKind::Timestamp,
+            amount: AMOUNT,
+        }
+    }
+
+    fn lock_params(&self, id: [u8; 32]) -> LockParams {
+        LockParams {
+            id,
+            hashlock: hashlock(),
+            hash_algorithm: HashAlgorithm::Sha256,
+            reward: 0,
+            reward_timelock: 0,
+            timelock: self.timelock,
+            timelock_kind: TimelockKind::Timestamp,
+            dst_chain: "ETHEREUM_SEPOLIA".into(),
+            dst_address: "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3".into(),
+            dst_asset: "USDC".into(),
+            src_asset: "USDC".into(),
+            src_receiver: self.src_receiver,
+            amount: AMOUNT,
+        }
+    }
+
+    fn commit(&mut self, params: CommitParams) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        let instruction =
+            anchor_htlc::commit(&sender, &self.sender_token_account(), self.token, params);
+        self.process(instruction, &[sender])
+    }
+
+    fn lock(&mut self, params: LockParams) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        let instruction =
+            anchor_htlc::lock(&sender, &self.sender_token_account(), self.token, params);
+        self.process(instruction, &[sender])
+    }
+
+    fn lock_with_reward(&mut self, id: [u8; 32], reward_timelock: u64) {
+        let mut params = self.lock_params(id);
+        params.reward = REWARD;
+        params.reward_timelock = reward_timelock;
+        self.lock(params).unwrap();
+    }
+
+    fn add_lock(&mut self, id: [u8; 32], timelock: u64) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        self.process(
+            anchor_htlc::add_lock(&sender, id, hashlock(), HashAlgorithm::Sha256, timelock),
+            &[sender],
+        )
+    }
+
+    fn redeem_with(
+        &mut self,
+        user_signing: Pubkey,
+        params: RedeemParams,
+    ) -> Result<Vec<u8>, ProgramError> {
+        let instruction = anchor_htlc::redeem(
+            &user_signing,
+            &self.sender,
+            &self.src_receiver,
+            self.token,
+            params,
+        );
+        self.process(instruction, &[user_signing])
+    }
+
+    fn redeem(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        self.redeem_with(self.solver, redeem_params(id))
+    }
+
+    fn batch_item(&self, id: [u8; 32], secret: [u8; 32]) -> RedeemBatchItem {
+        RedeemBatchItem {
+            id,
+            secret,
+            sender: self.sender,
+            src_receiver: self.src_receiver,
+            sender_token_account: Some(self.sender_token_account()),
+        }
+    }
+
+    fn redeem_batch(
+        &mut self,
+        items: &[RedeemBatchItem],
+        atomic: bool,
+    ) -> Result<Vec<bool>, ProgramError> {
+        let solver = self.solver;
+        let instruction = anchor_htlc::redeem_batch(&solver, None, self.token, items, atomic);
+        let data = self.process(instruction, &[solver])?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn refund(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        let (solver, sender) = (self.solver, self.sender);
+        let instruction = anchor_htlc::refund(&solver, &sender, None, self.token, id);
+        self.process(instruction, &[solver])
+    }
+
+    fn close(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        let (solver, sender) = (self.solver, self.sender);
+        let instruction = anchor_htlc::close(&solver, &sender, self.token, id);
+        self.process(instruction, &[solver])
+    }
+
+    fn add_lock_sig(
+        &mut self,
+        id: [u8; 32],
+        timelock: u64,
+        ed25519: Option<Instruction>,
+    ) -> Result<Vec<u8>, ProgramError> {
+        let add_lock_sig = anchor_htlc::add_lock_sig(
+            &self.solver,
+            &self.sender,
+            id,
+            hashlock(),
+            HashAlgorithm::Sha256,
+            timelock,
+        );
+        let instructions: Vec<Instruction> = ed25519.into_iter().chain([add_lock_sig]).collect();
+        self.svm.process(&instructions, &[self.solver])
+    }
+
+    /// The message the sender signs to authorize `add_lock_sig` on the HTLC with `id`.
+    fn add_lock_message(&mut self, id: [u8; 32], timelock: u64) -> Vec<u8> {
+        let htlc = self.details(id);
+        anchor_htlc::add_lock_message(&id, &htlc, &hashlock(), HashAlgorithm::Sha256, timelock)
+    }
+
+    fn details(&mut self, id: [u8; 32]) -> HTLC {
+        let instruction = anchor_htlc::get_details(&self.sender, id);
+        let data = self.process(instruction, &[]).unwrap();
+        HTLC::try_from_slice(&data).unwrap()
+    }
+
+    fn htlc_account(&self, id: [u8; 32]) -> Pubkey {
+        anchor_htlc::htlc_address(&self.sender, &id).0
+    }
+
+    fn htlc_token_account(&self, id: [u8; 32]) -> Pubkey {
+        anchor_htlc::htlc_token_account_address(&self.sender, &id).0
+    }
+}
+
+/// The transfer hook of `HOOK_PROGRAM`: checks the extra accounts against the validation
+/// account of the mint and adds the amount of the transfer to `HOOK_COUNTER`.
+fn transfer_hook<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let TransferHookInstruction::Execute { amount } = TransferHookInstruction::unpack(data)? else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    ExtraAccountMetaList::check_account_infos::<ExecuteInstruction>(
+        accounts,
+        data,
+        program_id,
+        &accounts[4].try_borrow_data()?,
+    )?;
+    let mut counter = accounts[5].try_borrow_mut_data()?;
+    let count = u64::from_le_bytes(counter[..8].try_into().unwrap()) + amount;
+    counter[..8].copy_from_slice(&count.to_le_bytes());
+    Ok(())
+}
+
+fn hashlock() -> [u8; 32] {
+    Sha256::digest(SECRET).into()
+}
+
+fn redeem_params(id: [u8; 32]) -> RedeemParams {
+    RedeemParams {
+        id,
+        secret: SECRET,
+        ..RedeemParams::default()
+    }
+}
+
+fn htlc_error(error: HTLCError) -> ProgramError {
+    ProgramError::Custom(error.into())
+}
+
+fn anchor_error(error: ErrorCode) -> ProgramError {
+    ProgramError::Custom(error.into())
+}
+
+/// Fee of a transfer of `amount` out of a fee mint.
+fn fee(amount: u64) -> u64 {
+    (amount * FEE_BASIS_POINTS as u64).div_ceil(10_000)
+}
+
+#[test]
+fn get_commit_id_matches_client() {
+    let mut test = Test::new();
+    let (sender, src_receiver, timelock) = (test.sender, test.src_receiver, test.timelock);
+    let instruction = anchor_htlc::get_commit_id(&sender, &src_receiver, AMOUNT, timelock);
+
+    let id = test.process(instruction.clone(), &[]).unwrap();
+
+    let now = test.svm.unix_timestamp() as u64;
+    assert_eq!(
+        id,
+        anchor_htlc::commit_id(&sender, &src_receiver, AMOUNT, now, timelock)
+    );
+
+    // The id covers the time of the clock, so the same call yields a new id later on.
+    test.svm.warp_to(now as i64 + 60);
+    let later = test.process(instruction, &[]).unwrap();
+    assert_ne!(later, id);
+    assert_eq!(
+        later,
+        anchor_htlc::commit_id(&sender, &src_receiver, AMOUNT, now + 60, timelock)
+    );
+}
+
+#[test]
+fn commit_escrows_tokens_and_stores_route() {
+    let mut test = Test::new();
+
+    let id = test.commit(test.commit_params([1; 32])).unwrap();
+
+    assert_eq!(id, [1; 32]);
+    let event = test.svm.event::<TokenCommitted>();
+    assert_eq!(
+        (
+            event.Id,
+            event.sender,
+            event.src_receiver,
+            event.token_contract
+        ),
+        ([1; 32], test.sender, test.src_receiver, test.token.mint)
+    );
+    assert_eq!(
+        (event.amount, event.timelock, event.timelock_kind),
+        (AMOUNT, test.timelock, TimelockKind::Timestamp)
+    );
+    assert_eq!(event.hopAssets, vec!["USDC".to_string()]);
+    assert_eq!(event.hopAddresses, vec!["0x1".to_string()]);
+    assert_eq!(test.balance(&test.sender_token_account()), BALANCE - AMOUNT);
+    assert_eq!(test.balance(&test.htlc_token_account([1; 32])), AMOUNT);
+    let htlc_account = test.svm.account(&test.htlc_account([1; 32])).unwrap();
+    let htlc = anchor_htlc::decode_htlc(&htlc_account.data).unwrap();
+    assert_eq!(htlc.sender, test.sender);
+    assert_eq!(htlc.src_receiver, test.src_receiver);
+    assert_eq!(htlc.token_contract, test.token.mint);
+    assert_eq!(htlc.token_wallet, test.htlc_token_account([1; 32]));
+    assert_eq!(htlc.hop_assets, vec!["USDC".to_string()]);
+    assert_eq!(htlc.hashlock, [0; 32]);
+    assert_eq!(htlc.status, HtlcStatus::Committed);
+    assert_eq!(test.details([1; 32]).amount, AMOUNT);
+}
+
+#[test]
+fn commit_rejects_invalid_arguments() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+
+    let mut params = test.commit_params([1; 32]);
+    params.timelock = now;
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.amount = 0;
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::FundsNotSent))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.hop_assets = vec!["USDC".into(); MAX_HOPS + 1];
+    assert_eq!(test.commit(params), Err(htlc_error(HTLCError::TooManyHops)));
+
+    let mut params = test.commit_params([1; 32]);
+    params.src_asset = "a".repeat(MAX_STRING_LENGTH + 1);
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::StringTooLong))
+    );
+
+    let (sender, solver) = (test.sender, test.solver);
+    let solver_token_account = test.create_token_account(&solver, test.token);
+    let instruction = anchor_htlc::commit(
+        &sender,
+        &solver_token_account,
+        test.token,
+        test.commit_params([1; 32]),
+    );
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::NotSender))
+    );
+
+    let other_token = test.create_mint(spl_token::ID, &[]);
+    let other_token_account = test.create_token_account(&sender, other_token);
+    let instruction = anchor_htlc::commit(
+        &sender,
+        &other_token_account,
+        test.token,
+        test.commit_params([1; 32]),
+    );
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::NoToken))
+    );
+}
+
+#[test]
+fn lock_rejects_invalid_arguments() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+
+    let mut params = test.lock_params([1; 32]);
+    params.timelock = now;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.amount = 0;
+    assert_eq!(test.lock(params), Err(htlc_error(HTLCError::FundsNotSent)));
+
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock + 1;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::InvalidRewardTimelock))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.amount = u64::MAX;
+    params.reward = 1;
+    params.reward_timelock = test.timelock - 100;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::AmountOverflow))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.dst_chain = "a".repeat(MAX_STRING_LENGTH + 1);
+    assert_eq!(test.lock(params), Err(htlc_error(HTLCError::StringTooLong)));
+
+    let sender = test.sender;
+    let other_token = test.create_mint(spl_token::ID, &[]);
+    let other_token_account = test.create_token_account(&sender, other_token);
+    let instruction = anchor_htlc::lock(
+        &sender,
+        &other_token_account,
+        test.token,
+        test.lock_params([1; 32]),
+    );
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::NoToken))
+    );
+}
+
+#[test]
+fn lock_and_redeem_pays_src_receiver() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    let event = test.svm.event::<TokenLocked>();
+    assert_eq!(
+        (event.Id, event.hashlock, event.sender, event.token_contract),
+        ([1; 32], hashlock(), test.sender, test.token.mint)
+    );
+    assert_eq!(
+        (event.amount, event.reward, event.timelock),
+        (AMOUNT, 0, test.timelock)
+    );
+    let escrow_rent = test.svm.lamports(&test.htlc_token_account([1; 32]));
+    let sender_before = test.svm.lamports(&test.sender);
+
+    test.redeem([1; 32]).unwrap();
+
+    let event = test.svm.event::<TokenRedeemed>();
+    assert_eq!(
+        (
+            event.Id,
+            event.sender,
+            event.redeem_address,
+            event.destination
+        ),
+        (
+            [1; 32],
+            test.sender,
+            test.solver,
+            test.receiver_token_account()
+        )
+    );
+    assert_eq!((event.secret, event.hashlock), (SECRET, hashlock()));
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([1; 32])), 0);
+    assert_eq!(test.svm.lamports(&test.sender), sender_before + escrow_rent);
+    let htlc = test.details([1; 32]);
+    assert_eq!(htlc.status, HtlcStatus::Redeemed);
+    assert_eq!(htlc.secret, SECRET);
+}
+
+#[test]
+fn redeem_checks_keccak_hashlock() {
+    let mut test = Test::new();
+    let keccak = keccak::hash(&SECRET).to_bytes();
+    let mut params = test.lock_params([1; 32]);
+    params.hash_algorithm = HashAlgorithm::Keccak256;
+    test.lock(params.clone()).unwrap();
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+
+    params.id = [2; 32];
+    params.hashlock = keccak;
+    test.lock(params).unwrap();
+    test.redeem([2; 32]).unwrap();
+    let htlc = test.details([2; 32]);
+    assert_eq!(htlc.hash_algorithm, HashAlgorithm::Keccak256);
+    assert_eq!(htlc.status, HtlcStatus::Redeemed);
+
+    test.commit(test.commit_params([3; 32])).unwrap();
+    assert_eq!(test.details([3; 32]).hash_algorithm, HashAlgorithm::Sha256);
+    let (sender, timelock) = (test.sender, test.timelock);
+    test.process(
+        anchor_htlc::add_lock(&sender, [3; 32], keccak, HashAlgorithm::Keccak256, timelock),
+        &[sender],
+    )
+    .unwrap();
+    assert_eq!(
+        test.details([3; 32]).hash_algorithm,
+        HashAlgorithm::Keccak256
+    );
+    test.redeem([3; 32]).unwrap();
+}
+
+#[test]
+fn redeem_rejects_invalid_calls() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::HashlockNotSet))
+    );
+
+    test.lock(test.lock_params([2; 32])).unwrap();
+    let solver = test.solver;
+    let mut params = redeem_params([2; 32]);
+    params.secret = [8; 32];
+    assert_eq!(
+        test.redeem_with(solver, params),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+
+    let other_receiver = Pubkey::new_unique();
+    let instruction = anchor_htlc::redeem(
+        &solver,
+        &test.sender,
+        &other_receiver,
+        test.token,
+        redeem_params([2; 32]),
+    );
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NotReciever))
+    );
+
+    let other_token = test.create_mint(spl_token::ID, &[]);
+    let instruction = anchor_htlc::redeem(
+        &solver,
+        &test.sender,
+        &test.src_receiver,
+        other_token,
+        redeem_params([2; 32]),
+    );
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NoToken))
+    );
+
+    let other_sender = test.svm.new_funded_account();
+    let mut instruction = anchor_htlc::redeem(
+        &solver,
+        &other_sender,
+        &test.src_receiver,
+        test.token,
+        redeem_params([2; 32]),
+    );
+    instruction.accounts[1].pubkey = test.htlc_account([2; 32]);
+    instruction.accounts[2].pubkey = test.htlc_token_account([2; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+
+    test.redeem([2; 32]).unwrap();
+    assert_eq!(
+        test.redeem([2; 32]),
+        Err(anchor_error(ErrorCode::AccountNotInitialized))
+    );
+
+    test.lock(test.lock_params([3; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    test.refund([3; 32]).unwrap();
+    assert_eq!(
+        test.redeem([3; 32]),
+        Err(anchor_error(ErrorCode::AccountNotInitialized))
+    );
+}
+
+#[test]
+fn redeem_to_pays_destination_authorized_by_src_receiver() {
+    let mut test = Test::new();
+    let receiver_key = svm::ed25519_keypair([5; 32]);
+    test.src_receiver = Pubkey::from(receiver_key.public.to_bytes());
+    test.svm.airdrop(&test.src_receiver, 10_000_000_000);
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    let (sender, src_receiver, solver, token) =
+        (test.sender, test.src_receiver, test.solver, test.token);
+    let destination = test.create_token_account(&Pubkey::new_unique(), token);
+
+    let instruction = anchor_htlc::redeem_to(
+        &src_receiver,
+        &sender,
+        &src_receiver,
+        &destination,
+        token,
+        redeem_params([1; 32]),
+    );
+    test.process(instruction, &[src_receiver]).unwrap();
+    assert_eq!(test.balance(&destination), AMOUNT);
+    assert_eq!(test.balance(&test.receiver_token_account()), 0);
+
+    let redeem_to = |destination: &Pubkey| {
+        anchor_htlc::redeem_to(
+            &solver,
+            &sender,
+            &src_receiver,
+            destination,
+            token,
+            redeem_params([2; 32]),
+        )
+    };
+    assert_eq!(
+        test.process(redeem_to(&destination), &[solver]),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let other_destination = test.create_token_account(&solver, token);
+    let message =
+        anchor_htlc::redeem_to_message(&sender, &[2; 32], &hashlock(), AMOUNT, &destination);
+    let signature = svm::ed25519_instruction(&receiver_key, &message);
+    assert_eq!(
+        test.svm.process(
+            &[signature.clone(), redeem_to(&other_destination)],
+            &[solver]
+        ),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    test.svm
+        .process(&[signature.clone(), redeem_to(&destination)], &[solver])
+        .unwrap();
+
+    assert_eq!(test.balance(&destination), 2 * AMOUNT);
+    assert_eq!(test.balance(&other_destination), 0);
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Redeemed);
+
+    // The signature does not carry over to an HTLC recreated under the same Id.
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.close([2; 32]).unwrap();
+    test.timelock += CLOSE_GRACE_PERIOD + 3600;
+    let secret = [9; 32];
+    let mut params = test.lock_params([2; 32]);
+    params.hashlock = Sha256::digest(secret).into();
+    test.lock(params).unwrap();
+    let replay = anchor_htlc::redeem_to(
+        &solver,
+        &sender,
+        &src_receiver,
+        &destination,
+        token,
+        RedeemParams {
+            id: [2; 32],
+            secret,
+            ..RedeemParams::default()
+        },
+    );
+    assert_eq!(
+        test.svm.process(&[signature, replay], &[solver]),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    assert_eq!(test.balance(&destination), 2 * AMOUNT);
+}
+
+#[test]
+fn redeem_batch_pays_every_src_receiver() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock_with_reward([2; 32], test.timelock - 100);
+    let src_receiver = test.src_receiver;
+    test.create_token_account(&src_receiver, test.token);
+    let escrow_rent = test.svm.lamports(&test.htlc_token_account([1; 32]));
+    let sender_before = test.svm.lamports(&test.sender);
+
+    let items = [
+        test.batch_item([1; 32], SECRET),
+        test.batch_item([2; 32], SECRET),
+    ];
+    assert_eq!(test.redeem_batch(&items, true), Ok(vec![true, true]));
+
+    assert_eq!(test.balance(&test.receiver_token_account()), 2 * AMOUNT);
+    assert_eq!(
+        test.balance(&test.sender_token_account()),
+        BALANCE - 2 * AMOUNT
+    );
+    assert_eq!(
+        test.svm.lamports(&test.sender),
+        sender_before + 2 * escrow_rent
+    );
+    for id in [[1; 32], [2; 32]] {
+        assert_eq!(test.svm.lamports(&test.htlc_token_account(id)), 0);
+        assert_eq!(test.details(id).status, HtlcStatus::Redeemed);
+    }
+}
+
+#[test]
+fn redeem_batch_fails_or_skips_invalid_items() {
+    let mut test = Test::new();
+    for id in [[1; 32], [2; 32], [3; 32]] {
+        test.lock(test.lock_params(id)).unwrap();
+    }
+    let src_receiver = test.src_receiver;
+    test.create_token_account(&src_receiver, test.token);
+    let items = [
+        test.batch_item([1; 32], SECRET),
+        test.batch_item([2; 32], [8; 32]),
+        test.batch_item([3; 32], SECRET),
+    ];
+
+    assert_eq!(
+        test.redeem_batch(&items, true),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Locked);
+
+    assert_eq!(
+        test.redeem_batch(&items, false),
+        Ok(vec![true, false, true])
+    );
+    assert_eq!(test.balance(&test.receiver_token_account()), 2 * AMOUNT);
+    assert_eq!(test.balance(&test.htlc_token_account([2; 32])), AMOUNT);
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Locked);
+
+    let mut wrong_receiver = test.batch_item([2; 32], SECRET);
+    wrong_receiver.src_receiver = test.sender;
+    assert_eq!(
+        test.redeem_batch(&[wrong_receiver], true),
+        Err(htlc_error(HTLCError::NotReciever))
+    );
+    let mut wrong_sender_account = test.batch_item([2; 32], SECRET);
+    wrong_sender_account.sender_token_account = Some(test.receiver_token_account());
+    assert_eq!(
+        test.redeem_batch(&[wrong_sender_account], true),
+        Err(htlc_error(HTLCError::NotSender))
+    );
+
+    let solver = test.solver;
+    let mut instruction = anchor_htlc::redeem_batch(&solver, None, test.token, &items[1..2], true);
+    instruction.accounts.pop();
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::InvalidBatch))
+    );
+}
+
+#[test]
+fn redeem_batch_needs_sender_token_account_only_for_a_reward() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock_with_reward([2; 32], test.timelock - 100);
+    let src_receiver = test.src_receiver;
+    test.create_token_account(&src_receiver, test.token);
+    let mut items = [
+        test.batch_item([1; 32], SECRET),
+        test.batch_item([2; 32], SECRET),
+    ];
+    for item in &mut items {
+        item.sender_token_account = None;
+    }
+
+    assert_eq!(
+        test.redeem_batch(&items, true),
+        Err(htlc_error(HTLCError::NoRewardAccount))
+    );
+    assert_eq!(test.redeem_batch(&items, false), Ok(vec![true, false]));
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Locked);
+}
+
+#[test]
+fn redeem_and_refund_meet_at_the_timelock() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+
+    test.svm.warp_to(test.timelock as i64 - 1);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([2; 32]).unwrap();
+
+    test.svm.warp_to(test.timelock as i64);
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    test.refund([1; 32]).unwrap();
+    assert_eq!(test.balance(&test.sender_token_account()), BALANCE - AMOUNT);
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Refunded);
+
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(anchor_error(ErrorCode::AccountNotInitialized))
+    );
+    assert_eq!(
+        test.refund([2; 32]),
+        Err(anchor_error(ErrorCode::AccountNotInitialized))
+    );
+}
+
+#[test]
+fn refund_rejects_token_account_of_other_owner() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+
+    let (solver, sender) = (test.solver, test.sender);
+    let solver_token_account = test.create_token_account(&solver, test.token);
+    let instruction = anchor_htlc::refund(
+        &solver,
+        &sender,
+        Some(solver_token_account),
+        test.token,
+        [1; 32],
+    );
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NotSender))
+    );
+
+    let other_token = test.create_mint(spl_token::ID, &[]);
+    let other_token_account = test.create_token_account(&sender, other_token);
+    let instruction = anchor_htlc::refund(
+        &solver,
+        &sender,
+        Some(other_token_account),
+        test.token,
+        [1; 32],
+    );
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NoToken))
+    );
+
+    let sender_token_account = test.sender_token_account();
+    let mut instruction = anchor_htlc::refund(&solver, &sender, None, test.token, [1; 32]);
+    for meta in &mut instruction.accounts {
+        if meta.pubkey == sender_token_account {
+            meta.pubkey = solver_token_account;
+        }
+    }
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintTokenOwner))
+    );
+
+    let mut instruction = anchor_htlc::refund(&solver, &sender, None, test.token, [1; 32]);
+    for meta in &mut instruction.accounts {
+        if meta.pubkey == sender_token_account {
+            meta.pubkey = anchor_htlc::ID;
+            meta.is_writable = false;
+        }
+    }
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NoRefundAccount))
+    );
+}
+
+#[test]
+fn refund_pays_any_token_account_of_signing_sender() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+
+    let (solver, sender, token) = (test.solver, test.sender, test.token);
+    let token_account = Pubkey::new_unique();
+    let authority = test.mint_authority;
+    test.svm
+        .process(
+            &[
+                system_instruction::create_account(
+                    &authority,
+                    &token_account,
+                    1_000_000_000,
+                    ExtensionType::try_calculate_account_len::<TokenAccount>(&[]).unwrap() as u64,
+                    &token.token_program,
+                ),
+                spl_token_2022::instruction::initialize_account3(
+                    &token.token_program,
+                    &token_account,
+                    &token.mint,
+                    &sender,
+                )
+                .unwrap(),
+            ],
+            &[authority, token_account],
+        )
+        .unwrap();
+    let instruction = anchor_htlc::refund(&solver, &sender, Some(token_account), token, [1; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    let instruction = anchor_htlc::refund(&sender, &sender, Some(token_account), token, [1; 32]);
+    test.process(instruction, &[sender]).unwrap();
+
+    assert_eq!(test.balance(&token_account), AMOUNT);
+    assert_eq!(test.balance(&test.sender_token_account()), BALANCE - AMOUNT);
+}
+
+#[test]
+fn keeper_refund_recreates_sender_token_account() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    let (sender, sender_token_account) = (test.sender, test.sender_token_account());
+    let drain = test.create_token_account(&Pubkey::new_unique(), test.token);
+    let token_program = test.token.token_program;
+    test.svm
+        .process(
+            &[
+                spl_token_2022::instruction::transfer_checked(
+                    &token_program,
+                    &sender_token_account,
+                    &test.token.mint,
+                    &drain,
+                    &sender,
+                    &[],
+                    BALANCE - AMOUNT,
+                    DECIMALS,
+                )
+                .unwrap(),
+                spl_token_2022::instruction::close_account(
+                    &token_program,
+                    &sender_token_account,
+                    &sender,
+                    &sender,
+                    &[],
+                )
+                .unwrap(),
+            ],
+            &[sender],
+        )
+        .unwrap();
+    assert!(test.svm.account(&sender_token_account).is_none());
+
+    test.svm.warp_to(test.timelock as i64);
+    let sender_lamports = test.svm.lamports(&sender);
+    let escrow_rent = test.svm.lamports(&test.htlc_token_account([1; 32]));
+    test.refund([1; 32]).unwrap();
+
+    assert_eq!(test.balance(&sender_token_account), AMOUNT);
+    assert_eq!(test.svm.lamports(&sender), sender_lamports + escrow_rent);
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Refunded);
+}
+
+#[test]
+fn reward_goes_to_sender_before_reward_timelock() {
+    let mut test = Test::new();
+    test.lock_with_reward([1; 32], test.timelock - 100);
+    assert_eq!(
+        test.balance(&test.sender_token_account()),
+        BALANCE - AMOUNT - REWARD
+    );
+
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::NoRewardAccount))
+    );
+    let solver = test.solver;
+    let solver_token_account = test.create_token_account(&solver, test.token);
+    let mut params = redeem_params([1; 32]);
+    params.sender_token_account = Some(solver_token_account);
+    assert_eq!(
+        test.redeem_with(solver, params),
+        Err(htlc_error(HTLCError::NotSender))
+    );
+
+    let mut params = redeem_params([1; 32]);
+    params.sender_token_account = Some(test.sender_token_account());
+    test.redeem_with(solver, params).unwrap();
+
+    assert_eq!(test.balance(&test.sender_token_account()), BALANCE - AMOUNT);
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+}
+
+#[test]
+fn reward_goes_to_redeemer_after_reward_timelock() {
+    let mut test = Test::new();
+    test.lock_with_reward([1; 32], test.timelock - 100);
+    test.lock_with_reward([2; 32], test.timelock - 100);
+    test.svm.warp_to(test.timelock as i64 - 100);
+
+    let solver = test.solver;
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::NoRewardAccount))
+    );
+    let mut params = redeem_params([1; 32]);
+    params.user_signing_token_account = Some(test.sender_token_account());
+    assert_eq!(
+        test.redeem_with(solver, params),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+
+    let solver_token_account = test.create_token_account(&solver, test.token);
+    let mut params = redeem_params([1; 32]);
+    params.user_signing_token_account = Some(solver_token_account);
+    test.redeem_with(solver, params).unwrap();
+    assert_eq!(test.balance(&solver_token_account), REWARD);
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+
+    let src_receiver = test.src_receiver;
+    test.redeem_with(src_receiver, redeem_params([2; 32]))
+        .unwrap();
+    assert_eq!(
+        test.balance(&test.receiver_token_account()),
+        2 * AMOUNT + REWARD
+    );
+}
+
+#[test]
+fn refund_returns_amount_and_reward() {
+    let mut test = Test::new();
+    test.lock_with_reward([1; 32], test.timelock);
+    test.svm.warp_to(test.timelock as i64);
+
+    test.refund([1; 32]).unwrap();
+
+    let event = test.svm.event::<TokenRefunded>();
+    assert_eq!((event.Id, event.sender), ([1; 32], test.sender));
+    assert_eq!(test.balance(&test.sender_token_account()), BALANCE);
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([1; 32])), 0);
+}
+
+#[test]
+fn refund_batch_refunds_expired_htlcs() {
+    let mut test = Test::new();
+    test.lock_with_reward([1; 32], test.timelock);
+    let mut params = test.lock_params([2; 32]);
+    params.timelock += 1000;
+    test.lock(params).unwrap();
+    test.lock(test.lock_params([3; 32])).unwrap();
+    test.redeem([3; 32]).unwrap();
+    test.commit(test.commit_params([4; 32])).unwrap();
+    test.lock(test.lock_params([5; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    let sender_before = test.balance(&test.sender_token_account());
+
+    let (sender, sender_token_account) = (test.sender, test.sender_token_account());
+    let mut items: Vec<RefundBatchItem> = [[1; 32], [2; 32], [3; 32], [4; 32]]
+        .into_iter()
+        .map(|id| RefundBatchItem {
+            id,
+            sender,
+            sender_token_account,
+        })
+        .collect();
+    let receiver_token_account = test.receiver_token_account();
+    items.push(RefundBatchItem {
+        id: [2; 32],
+        sender,
+        sender_token_account: receiver_token_account,
+    });
+    let solver = test.solver;
+    let other_token_account = test.create_token_account(&solver, test.token);
+    let instruction = spl_token_2022::instruction::set_authority(
+        &test.token.token_program,
+        &other_token_account,
+        Some(&sender),
+        spl_token_2022::instruction::AuthorityType::AccountOwner,
+        &solver,
+        &[],
+    )
+    .unwrap();
+    test.process(instruction, &[solver]).unwrap();
+    items.push(RefundBatchItem {
+        id: [5; 32],
+        sender,
+        sender_token_account: other_token_account,
+    });
+    let data = test
+        .process(
+            anchor_htlc::refund_batch(&solver, test.token, &items),
+            &[solver],
+        )
+        .unwrap();
+
+    assert_eq!(
+        Vec::<bool>::try_from_slice(&data).unwrap(),
+        vec![true, false, false, true, false, false]
+    );
+    assert_eq!(
+        test.balance(&test.sender_token_account()),
+        sender_before + 2 * AMOUNT + REWARD
+    );
+    for id in [[1; 32], [4; 32]] {
+        assert_eq!(test.svm.lamports(&test.htlc_token_account(id)), 0);
+        assert_eq!(test.details(id).status, HtlcStatus::Refunded);
+    }
+    assert_eq!(test.balance(&test.htlc_token_account([2; 32])), AMOUNT);
+    assert_eq!(test.balance(&test.htlc_token_account([5; 32])), AMOUNT);
+
+    let mut instruction = anchor_htlc::refund_batch(&solver, test.token, &items[1..2]);
+    instruction.accounts.pop();
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::InvalidBatch))
+    );
+}
+
+#[test]
+fn add_lock_sets_hashlock_of_commit() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let now = test.svm.unix_timestamp() as u64;
+
+    assert_eq!(
+        test.add_lock([1; 32], now),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+    test.add_lock([1; 32], test.timelock + 10).unwrap();
+    let event = test.svm.event::<TokenLockAdded>();
+    assert_eq!(
+        (event.Id, event.sender, event.hashlock, event.timelock),
+        ([1; 32], test.sender, hashlock(), test.timelock + 10)
+    );
+
+    let htlc = test.details([1; 32]);
+    assert_eq!(htlc.hashlock, hashlock());
+    assert_eq!(htlc.timelock, test.timelock + 10);
+    assert_eq!(htlc.status, HtlcStatus::Locked);
+    assert_eq!(
+        test.add_lock([1; 32], test.timelock),
+        Err(htlc_error(HTLCError::HashlockAlreadySet))
+    );
+    test.redeem([1; 32]).unwrap();
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+}
+
+#[test]
+fn add_lock_rejects_claimed_htlcs() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    test.redeem([2; 32]).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    test.refund([1; 32]).unwrap();
+
+    let timelock = test.timelock + 10;
+    assert_eq!(
+        test.add_lock([1; 32], timelock),
+        Err(htlc_error(HTLCError::AlreadyRefunded))
+    );
+    assert_eq!(
+        test.add_lock([2; 32], timelock),
+        Err(htlc_error(HTLCError::AlreadyRedeemed))
+    );
+
+    let solver = test.solver;
+    let mut instruction = anchor_htlc::add_lock(
+        &solver,
+        [1; 32],
+        hashlock(),
+        HashAlgorithm::Sha256,
+        timelock,
+    );
+    instruction.accounts[1].pubkey = test.htlc_account([1; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+}
+
+#[test]
+fn add_lock_sig_accepts_signature_of_sender() {
+    let sender_key = svm::ed25519_keypair([9; 32]);
+    let mut test = Test::new();
+    test.sender = Pubkey::from(sender_key.public.to_bytes());
+    test.svm.airdrop(&test.sender, 10_000_000_000);
+    let sender = test.sender;
+    let sender_token_account = test.create_token_account(&sender, test.token);
+    test.mint_to(&sender_token_account, test.token, BALANCE);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let timelock = test.timelock + 10;
+    let message = test.add_lock_message([1; 32], timelock);
+
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, None),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let other_key = svm::ed25519_keypair([10; 32]);
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            timelock,
+            Some(svm::ed25519_instruction(&other_key, &message))
+        ),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    test.add_lock_sig(
+        [1; 32],
+        timelock,
+        Some(svm::ed25519_instruction(&sender_key, &message)),
+    )
+    .unwrap();
+
+    let htlc = test.details([1; 32]);
+    assert_eq!(htlc.hashlock, hashlock());
+    assert_eq!(htlc.timelock, timelock);
+}
+
+#[test]
+fn add_lock_sig_rejects_replay_on_recreated_htlc() {
+    let sender_key = svm::ed25519_keypair([9; 32]);
+    let mut test = Test::new();
+    test.sender = Pubkey::from(sender_key.public.to_bytes());
+    test.svm.airdrop(&test.sender, 10_000_000_000);
+    let sender = test.sender;
+    let sender_token_account = test.create_token_account(&sender, test.token);
+    test.mint_to(&sender_token_account, test.token, BALANCE);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    // A signature the sender handed out but that was never submitted.
+    let timelock = test.timelock + CLOSE_GRACE_PERIOD + 7200;
+    let message = test.add_lock_message([1; 32], timelock);
+    let signature = svm::ed25519_instruction(&sender_key, &message);
+
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.refund([1; 32]).unwrap();
+    test.close([1; 32]).unwrap();
+    test.timelock += CLOSE_GRACE_PERIOD + 3600;
+    test.commit(test.commit_params([1; 32])).unwrap();
+
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, Some(signature)),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Committed);
+}
+
+#[test]
+fn extend_timelock_pushes_back_redeem_and_refund() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.commit(test.commit_params([2; 32])).unwrap();
+    let (sender, solver) = (test.sender, test.solver);
+
+    let instruction = anchor_htlc::extend_timelock(&sender, [1; 32], test.timelock);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimelockNotIncreased))
+    );
+    let mut instruction = anchor_htlc::extend_timelock(&solver, [1; 32], test.timelock + 100);
+    instruction.accounts[1].pubkey = test.htlc_account([1; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+    for (id, status) in [
+        ([1; 32], HtlcStatus::Locked),
+        ([2; 32], HtlcStatus::Committed),
+    ] {
+        let instruction = anchor_htlc::extend_timelock(&sender, id, test.timelock + 100);
+        test.process(instruction, &[sender]).unwrap();
+        let event = test.svm.event::<TokenTimelockExtended>();
+        assert_eq!(
+            (event.Id, event.sender, event.timelock),
+            (id, test.sender, test.timelock + 100)
+        );
+        let details = test.details(id);
+        assert_eq!(
+            (details.timelock, details.status),
+            (test.timelock + 100, status)
+        );
+    }
+
+    test.svm.warp_to(test.timelock as i64);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([1; 32]).unwrap();
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+
+    test.svm.warp_to(test.timelock as i64 + 100);
+    let instruction = anchor_htlc::extend_timelock(&sender, [2; 32], test.timelock + 200);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    test.refund([2; 32]).unwrap();
+    for (id, error) in [
+        ([1; 32], HTLCError::AlreadyRedeemed),
+        ([2; 32], HTLCError::AlreadyRefunded),
+    ] {
+        let instruction = anchor_htlc::extend_timelock(&sender, id, test.timelock + 200);
+        assert_eq!(test.process(instruction, &[sender]), Err(htlc_error(error)));
+    }
+}
+
+#[test]
+fn update_config_is_restricted_to_admin() {
+    let mut test = Test::new();
+    let (admin, solver) = (test.admin, test.solver);
+    let instruction = anchor_htlc::initialize_config(&admin, 0, 7200);
+    assert_eq!(
+        test.process(instruction, &[admin]),
+        Err(ProgramError::Custom(
+            SystemError::AccountAlreadyInUse as u32
+        ))
+    );
+
+    let instruction = anchor_htlc::update_config(&solver, 0, 7200);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    let instruction = anchor_htlc::update_config(&admin, 7200, 0);
+    assert_eq!(
+        test.process(instruction, &[admin]),
+        Err(htlc_error(HTLCError::InvalidTimelockBounds))
+    );
+    let instruction = anchor_htlc::update_config(&admin, 0, 7200);
+    test.process(instruction, &[admin]).unwrap();
+    let event = test.svm.event::<ConfigUpdated>();
+    assert_eq!(
+        (
+            event.admin,
+            event.min_timelock_delta,
+            event.max_timelock_delta
+        ),
+        (admin, 0, 7200)
+    );
+    let account = test.svm.account(&anchor_htlc::config_address().0).unwrap();
+    let config = anchor_htlc::decode_config(&account.data).unwrap();
+    assert_eq!(
+        (
+            config.admin,
+            config.min_timelock_delta,
+            config.max_timelock_delta
+        ),
+        (admin, 0, 7200)
+    );
+}
+
+#[test]
+fn timelock_bounds_apply_to_commit_lock_and_add_lock() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+    let (min, max) = (now + MIN_TIMELOCK_DELTA, now + MAX_TIMELOCK_DELTA);
+
+    for (timelock, error) in [
+        (min - 1, HTLCError::TimelockTooShort),
+        (max + 1, HTLCError::TimelockTooLong),
+    ] {
+        let mut params = test.commit_params([1; 32]);
+        params.timelock = timelock;
+        assert_eq!(test.commit(params), Err(htlc_error(error)));
+        let mut params = test.lock_params([2; 32]);
+        params.timelock = timelock;
+        assert_eq!(test.lock(params), Err(htlc_error(error)));
+    }
+    let mut params = test.commit_params([1; 32]);
+    params.timelock = min;
+    test.commit(params).unwrap();
+    let mut params = test.lock_params([2; 32]);
+    params.timelock = max;
+    test.lock(params).unwrap();
+
+    assert_eq!(
+        test.add_lock([1; 32], min - 1),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    assert_eq!(
+        test.add_lock([1; 32], max + 1),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    let sender = test.sender;
+    let instruction = anchor_htlc::extend_timelock(&sender, [2; 32], max + 1);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    test.add_lock([1; 32], max).unwrap();
+}
+
+#[test]
+fn pause_blocks_new_htlcs_but_not_redeem_and_refund() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    test.lock(test.lock_params([3; 32])).unwrap();
+
+    let (admin, sender) = (test.admin, test.sender);
+    assert_eq!(
+        test.process(anchor_htlc::pause(&sender), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(anchor_htlc::pause(&admin), &[admin]).unwrap();
+    assert_eq!(test.svm.event::<Paused>().admin, admin);
+    let account = test.svm.account(&anchor_htlc::config_address().0).unwrap();
+    assert!(anchor_htlc::decode_config(&account.data).unwrap().paused);
+
+    assert_eq!(
+        test.commit(test.commit_params([4; 32])),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    assert_eq!(
+        test.lock(test.lock_params([4; 32])),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    let timelock = test.timelock;
+    assert_eq!(
+        test.add_lock([1; 32], timelock),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, None),
+        Err(htlc_error(HTLCError::Paused))
+    );
+
+    test.redeem([2; 32]).unwrap();
+    test.svm.warp_to(timelock as i64);
+    test.refund([3; 32]).unwrap();
+
+    assert_eq!(
+        test.process(anchor_htlc::unpause(&sender), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(anchor_htlc::unpause(&admin), &[admin])
+        .unwrap();
+    assert_eq!(test.svm.event::<Unpaused>().admin, admin);
+    test.timelock = timelock + 3600;
+    test.commit(test.commit_params([4; 32])).unwrap();
+}
+
+#[test]
+fn transfer_admin_hands_over_the_config() {
+    let mut test = Test::new();
+    let (admin, other) = (test.admin, test.sender);
+    assert_eq!(
+        test.process(anchor_htlc::transfer_admin(&other, &other), &[other]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(anchor_htlc::transfer_admin(&admin, &other), &[admin])
+        .unwrap();
+    let event = test.svm.event::<AdminTransferred>();
+    assert_eq!((event.previous_admin, event.new_admin), (admin, other));
+    let account = test.svm.account(&anchor_htlc::config_address().0).unwrap();
+    assert_eq!(
+        anchor_htlc::decode_config(&account.data).unwrap().admin,
+        other
+    );
+
+    assert_eq!(
+        test.process(anchor_htlc::pause(&admin), &[admin]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        test.process(anchor_htlc::update_config(&admin, 0, 7200), &[admin]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(anchor_htlc::pause(&other), &[other]).unwrap();
+    test.process(anchor_htlc::update_config(&other, 0, 7200), &[other])
+        .unwrap();
+}
+
+#[test]
+fn mint_allowlist_restricts_commit_and_lock() {
+    let mut test = Test::new();
+    let (admin, sender, mint) = (test.admin, test.sender, test.token.mint);
+    let (min, max) = (AMOUNT / 2, 2 * AMOUNT);
+    assert_eq!(
+        test.process(anchor_htlc::allow_mint(&sender, &mint, min, max), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        test.process(anchor_htlc::set_allowlist_enabled(&sender, true), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        test.process(anchor_htlc::allow_mint(&admin, &mint, max, min), &[admin]),
+        Err(htlc_error(HTLCError::InvalidAmountLimits))
+    );
+
+    test.process(anchor_htlc::set_allowlist_enabled(&admin, true), &[admin])
+        .unwrap();
+    assert!(test.svm.event::<AllowlistUpdated>().enabled);
+    assert_eq!(
+        test.commit(test.commit_params([1; 32])),
+        Err(htlc_error(HTLCError::MintNotAllowed))
+    );
+
+    test.process(anchor_htlc::allow_mint(&admin, &mint, min, max), &[admin])
+        .unwrap();
+    let event = test.svm.event::<MintAllowed>();
+    assert_eq!(
+        (event.token_contract, event.min_amount, event.max_amount),
+        (mint, min, max)
+    );
+    let account = test
+        .svm
+        .account(&anchor_htlc::allowed_mint_address(&mint).0)
+        .unwrap();
+    let allowed_mint = anchor_htlc::decode_allowed_mint(&account.data).unwrap();
+    assert_eq!(
+        (
+            allowed_mint.token_contract,
+            allowed_mint.min_amount,
+            allowed_mint.max_amount
+        ),
+        (mint, min, max)
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.amount = min - 1;
+    assert_eq!(
+        test.commit(params.clone()),
+        Err(htlc_error(HTLCError::AmountTooSmall))
+    );
+    params.amount = min;
+    test.commit(params).unwrap();
+    let mut params = test.lock_params([2; 32]);
+    params.amount = max + 1;
+    assert_eq!(
+        test.lock(params.clone()),
+        Err(htlc_error(HTLCError::AmountTooLarge))
+    );
+    params.amount = max;
+    test.lock(params).unwrap();
+
+    test.process(anchor_htlc::disallow_mint(&admin, &mint), &[admin])
+        .unwrap();
+    assert_eq!(test.svm.event::<MintDisallowed>().token_contract, mint);
+    assert!(test
+        .svm
+        .account(&anchor_htlc::allowed_mint_address(&mint).0)
+        .is_none());
+    assert_eq!(
+        test.lock(test.lock_params([3; 32])),
+        Err(htlc_error(HTLCError::MintNotAllowed))
+    );
+    test.redeem([2; 32]).unwrap();
+
+    test.process(anchor_htlc::set_allowlist_enabled(&admin, false), &[admin])
+        .unwrap();
+    assert!(!test.svm.event::<AllowlistUpdated>().enabled);
+    test.lock(test.lock_params([3; 32])).unwrap();
+}
+
+#[test]
+fn slot_timelock_expires_at_its_slot() {
+    let mut test = Test::new();
+    let min_slots = MIN_TIMELOCK_DELTA * 1000 / DEFAULT_MS_PER_SLOT;
+    let timelock = test.svm.slot() + 1_000;
+    let mut params = test.lock_params([1; 32]);
+    params.timelock_kind = TimelockKind::Slot;
+    params.timelock = test.svm.slot() + min_slots - 1;
+    assert_eq!(
+        test.lock(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    for id in [[1; 32], [2; 32]] {
+        params.id = id;
+        params.timelock = timelock;
+        test.lock(params.clone()).unwrap();
+    }
+    let htlc = test.details([1; 32]);
+    assert_eq!(
+        (htlc.timelock_kind, htlc.timelock),
+        (TimelockKind::Slot, timelock)
+    );
+
+    // The unix timestamp passing the slot height does not expire the HTLC.
+    test.svm.warp_to((timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.svm.warp_to_slot(timelock - 1);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([2; 32]).unwrap();
+
+    test.svm.warp_to_slot(timelock);
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    test.refund([1; 32]).unwrap();
+
+    let grace_end = timelock + CLOSE_GRACE_PERIOD * 1000 / DEFAULT_MS_PER_SLOT;
+    test.svm.warp_to_slot(grace_end - 1);
+    assert_eq!(
+        test.close([1; 32]),
+        Err(htlc_error(HTLCError::NotPastGracePeriod))
+    );
+    test.svm.warp_to_slot(grace_end);
+    test.close([1; 32]).unwrap();
+}
+
+#[test]
+fn close_returns_rent_after_grace_period() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    assert_eq!(test.close([1; 32]), Err(htlc_error(HTLCError::NotClaimed)));
+
+    test.redeem([1; 32]).unwrap();
+    let grace_end = (test.timelock + CLOSE_GRACE_PERIOD) as i64;
+    test.svm.warp_to(grace_end - 1);
+    assert_eq!(
+        test.close([1; 32]),
+        Err(htlc_error(HTLCError::NotPastGracePeriod))
+    );
+
+    test.svm.warp_to(grace_end);
+    let rent = test.svm.lamports(&test.htlc_account([1; 32]));
+    let sender_before = test.svm.lamports(&test.sender);
+    let solver_before = test.svm.lamports(&test.solver);
+    test.close([1; 32]).unwrap();
+
+    let event = test.svm.event::<TokenClosed>();
+    assert_eq!(
+        (event.Id, event.sender, event.keeper_tip),
+        ([1; 32], test.sender, KEEPER_TIP)
+    );
+    assert_eq!(
+        test.svm.lamports(&test.sender),
+        sender_before + rent - KEEPER_TIP
+    );
+    assert_eq!(test.svm.lamports(&test.solver), solver_before + KEEPER_TIP);
+    assert_eq!(test.svm.lamports(&test.htlc_account([1; 32])), 0);
+}
+
+#[test]
+fn close_sweeps_tokens_left_in_escrow() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    let (escrow, token) = (test.htlc_token_account([1; 32]), test.token);
+    test.mint_to(&escrow, token, 5);
+    test.redeem([1; 32]).unwrap();
+    assert_eq!(test.balance(&escrow), 5);
+
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.close([1; 32]).unwrap();
+
+    assert_eq!(test.svm.lamports(&escrow), 0);
+    assert_eq!(
+        test.balance(&test.sender_token_account()),
+        BALANCE - AMOUNT + 5
+    );
+    test.timelock += CLOSE_GRACE_PERIOD + 3600;
+    test.lock(test.lock_params([1; 32])).unwrap();
+}
+
+#[test]
+fn token_2022_mint_locks_and_redeems() {
+    let mut test = Test::with_mint(spl_token_2022::ID, &[]);
+    test.lock(test.lock_params([1; 32])).unwrap();
+    assert_eq!(
+        test.svm
+            .account(&test.htlc_token_account([1; 32]))
+            .unwrap()
+            .owner,
+        spl_token_2022::ID
+    );
+
+    test.redeem([1; 32]).unwrap();
+
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([1; 32])), 0);
+}
+
+#[test]
+fn transfer_fee_mint_records_received_amount_and_harvests_fees() {
+    let mut test = Test::with_mint(spl_token_2022::ID, &[ExtensionType::TransferFeeConfig]);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let received = AMOUNT - fee(AMOUNT);
+    assert_eq!(test.details([1; 32]).amount, received);
+
+    let mut params = test.lock_params([2; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock;
+    test.lock(params).unwrap();
+    assert_eq!(
+        test.details([2; 32]).amount,
+        AMOUNT + REWARD - fee(AMOUNT + REWARD) - REWARD
+    );
+
+    test.add_lock([1; 32], test.timelock).unwrap();
+    test.redeem([1; 32]).unwrap();
+
+    assert_eq!(
+        test.balance(&test.receiver_token_account()),
+        received - fee(received)
+    );
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([1; 32])), 0);
+    let mint = test.svm.account(&test.token.mint).unwrap();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint.data).unwrap();
+    let withheld: u64 = mint
+        .get_extension::<TransferFeeConfig>()
+        .unwrap()
+        .withheld_amount
+        .into();
+    assert_eq!(withheld, fee(AMOUNT));
+
+    test.svm.warp_to(test.timelock as i64);
+    test.refund([2; 32]).unwrap();
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([2; 32])), 0);
+
+    // A fee taking all of the amount leaves nothing to lock.
+    assert_eq!(fee(1), 1);
+    test.timelock += 3600;
+    let mut params = test.commit_params([3; 32]);
+    params.amount = 1;
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::FundsNotSent))
+    );
+}
+
+#[test]
+fn transfer_hook_mint_passes_extra_accounts_to_the_hook() {
+    let mut test = Test::with_mint(spl_token_2022::ID, &[ExtensionType::TransferHook]);
+    let (sender, src_receiver, solver, token) =
+        (test.sender, test.src_receiver, test.solver, test.token);
+    let mut instruction = anchor_htlc::commit(
+        &sender,
+        &test.sender_token_account(),
+        token,
+        test.commit_params([1; 32]),
+    );
+    assert_eq!(
+        test.process(instruction.clone(), &[sender]),
+        Err(TransferHookError::IncorrectAccount.into())
+    );
+
+    instruction.accounts.extend(test.hook_accounts());
+    test.process(instruction, &[sender]).unwrap();
+    assert_eq!(test.hook_count(), AMOUNT);
+    assert_eq!(test.balance(&test.htlc_token_account([1; 32])), AMOUNT);
+
+    test.add_lock([1; 32], test.timelock).unwrap();
+    let mut instruction = anchor_htlc::redeem(
+        &solver,
+        &sender,
+        &src_receiver,
+        token,
+        redeem_params([1; 32]),
+    );
+    instruction.accounts.extend(test.hook_accounts());
+    test.process(instruction, &[solver]).unwrap();
+
+    assert_eq!(test.hook_count(), 2 * AMOUNT);
+    assert_eq!(test.balance(&test.receiver_token_account()), AMOUNT);
+    assert_eq!(test.svm.lamports(&test.htlc_token_account([1; 32])), 0);
+}
+
+#[test]
+fn non_transferable_and_confidential_mints_are_rejected() {
+    let mut test = Test::with_mint(spl_token_2022::ID, &[ExtensionType::NonTransferable]);
+    assert_eq!(
+        test.commit(test.commit_params([1; 32])),
+        Err(htlc_error(HTLCError::NonTransferableMint))
+    );
+    assert_eq!(
+        test.lock(test.lock_params([1; 32])),
+        Err(htlc_error(HTLCError::NonTransferableMint))
+    );
+
+    let mut test = Test::with_mint(
+        spl_token_2022::ID,
+        &[ExtensionType::ConfidentialTransferMint],
+    );
+    assert_eq!(
+        test.commit(test.commit_params([1; 32])),
+        Err(htlc_error(HTLCError::ConfidentialTransferMint))
+    );
+    assert_eq!(
+        test.lock(test.lock_params([1; 32])),
+        Err(htlc_error(HTLCError::ConfidentialTransferMint))
+    );
+}
+
+#[test]
+fn get_reward_details_returns_reward() {
+    let mut test = Test::new();
+    test.lock_with_reward([1; 32], test.timelock - 100);
+
+    let instruction = anchor_htlc::get_reward_details(&test.sender, [1; 32]);
+    let reward = Reward::try_from_slice(&test.process(instruction, &[]).unwrap()).unwrap();
+
+    assert_eq!(reward.amount, REWARD);
+    assert_eq!(reward.timelock, test.timelock - 100);
+}
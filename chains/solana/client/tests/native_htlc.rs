@@ -0,0 +1,1371 @@
+//! Integration tests of the `native_htlc` program.
+//!
+//! `NotSender` and `UnauthorizedAccess` guard the sender of an HTLC, but as the HTLC
+//! account is derived from the sender, passing another sender fails the seeds constraint
+//! before them. `NotOwner` is not returned by any instruction.
+
+mod svm;
+
+use anchor_lang::{
+    error::ErrorCode,
+    prelude::ProgramError,
+    solana_program::{
+        clock::DEFAULT_MS_PER_SLOT, instruction::Instruction, keccak, pubkey::Pubkey,
+        system_instruction::SystemError,
+    },
+    AnchorDeserialize,
+};
+use htlc_client::native_htlc::{
+    self, CommitParams, HashAlgorithm, HtlcStatus, LockParams, RedeemBatchItem, RefundBatchItem,
+    Reward, TimelockKind, HTLC,
+};
+use sha2::{Digest, Sha256};
+use sol::{
+    AdminTransferred, ConfigUpdated, HTLCError, Paused, TokenClosed, TokenCommitted,
+    TokenLockAdded, TokenLocked, TokenRedeemed, TokenRefunded, TokenTimelockExtended, Unpaused,
+    CLOSE_GRACE_PERIOD, KEEPER_TIP, MAX_HOPS, MAX_STRING_LENGTH,
+};
+use svm::Svm;
+
+const AMOUNT: u64 = 1_000_000;
+const REWARD: u64 = 10_000;
+const SECRET: [u8; 32] = [7; 32];
+const MIN_TIMELOCK_DELTA: u64 = 60;
+const MAX_TIMELOCK_DELTA: u64 = 30 * 24 * 60 * 60;
+
+struct Test {
+    svm: Svm,
+    admin: Pubkey,
+    sender: Pubkey,
+    src_receiver: Pubkey,
+    solver: Pubkey,
+    timelock: u64,
+}
+
+impl Test {
+    fn new() -> Self {
+        let mut svm = Svm::new(&[(native_htlc::ID, sol::entry)]);
+        let admin = svm.new_funded_account();
+        svm.set_upgrade_authority(&native_htlc::ID, &admin);
+        let instruction =
+            native_htlc::initialize_config(&admin, MIN_TIMELOCK_DELTA, MAX_TIMELOCK_DELTA);
+        svm.process(&[instruction], &[admin]).unwrap();
+        let sender = svm.new_funded_account();
+        let src_receiver = svm.new_funded_account();
+        let solver = svm.new_funded_account();
+        let timelock = svm.unix_timestamp() as u64 + 3600;
+        Test {
+            svm,
+            admin,
+            sender,
+            src_receiver,
+            solver,
+            timelock,
+        }
+    }
+
+    fn process(
+        &mut self,
+        instruction: Instruction,
+        signers: &[Pubkey],
+    ) -> Result<Vec<u8>, ProgramError> {
+        self.svm.process(&[instruction], signers)
+    }
+
+    fn commit_params(&self, id: [u8; 32]) -> CommitParams {
+        CommitParams {
+            id,
+            hop_chains: vec!["SOLANA".into()],
+            hop_assets: vec!["SOL".into()],
+            hop_addresses: vec!["0x1".into()],
+            dst_chain: "ETHEREUM_SEPOLIA".into(),
+            dst_asset: "ETH".into(),
+            dst_address: "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3".into(),
+            src_asset: "SOL".into(),
+            src_receiver: self.src_receiver,
+            timelock: self.timelock,
+            timelock_kind: TimelockKind::Timestamp,
+            amount: AMOUNT,
+        }
+    }
+
+    fn lock_params(&self, id: [u8; 32]) -> LockParams {
+        LockParams {
+            id,
+            hashlock: hashlock(),
+            hash_algorithm: HashAlgorithm::Sha256,
+            reward: 0,
+            reward_timelock: 0,
+            timelock: self.timelock,
+            timelock_kind: TimelockKind::Timestamp,
+            amount: AMOUNT,
+            dst_chain: "ETHEREUM_SEPOLIA".into(),
+            dst_address: "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3".into(),
+            dst_asset: "ETH".into(),
+            src_asset: "SOL".into(),
+            src_receiver: self.src_receiver,
+        }
+    }
+
+    fn commit(&mut self, params: CommitParams) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        self.process(native_htlc::commit(&sender, params), &[sender])
+    }
+
+    fn lock(&mut self, params: LockParams) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        self.process(native_htlc::lock(&sender, params), &[sender])
+    }
+
+    fn add_lock(&mut self, id: [u8; 32], timelock: u64) -> Result<Vec<u8>, ProgramError> {
+        let sender = self.sender;
+        let instruction = native_htlc::add_lock(
+            &sender,
+            &sender,
+            id,
+            hashlock(),
+            HashAlgorithm::Sha256,
+            timelock,
+        );
+        self.process(instruction, &[sender])
+    }
+
+    fn redeem_by(
+        &mut self,
+        user_signing: Pubkey,
+        id: [u8; 32],
+        secret: [u8; 32],
+    ) -> Result<Vec<u8>, ProgramError> {
+        let instruction =
+            native_htlc::redeem(&user_signing, &self.sender, &self.src_receiver, id, secret);
+        self.process(instruction, &[user_signing])
+    }
+
+    fn redeem(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        self.redeem_by(self.solver, id, SECRET)
+    }
+
+    fn batch_item(&self, id: [u8; 32], secret: [u8; 32]) -> RedeemBatchItem {
+        RedeemBatchItem {
+            id,
+            secret,
+            sender: self.sender,
+            src_receiver: self.src_receiver,
+        }
+    }
+
+    fn redeem_batch(
+        &mut self,
+        items: &[RedeemBatchItem],
+        atomic: bool,
+    ) -> Result<Vec<bool>, ProgramError> {
+        let solver = self.solver;
+        let data = self.process(native_htlc::redeem_batch(&solver, items, atomic), &[solver])?;
+        Ok(Vec::<bool>::try_from_slice(&data).unwrap())
+    }
+
+    fn refund(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        let (solver, sender) = (self.solver, self.sender);
+        self.process(native_htlc::refund(&solver, &sender, id), &[solver])
+    }
+
+    fn close(&mut self, id: [u8; 32]) -> Result<Vec<u8>, ProgramError> {
+        let (solver, sender) = (self.solver, self.sender);
+        self.process(native_htlc::close(&solver, &sender, id), &[solver])
+    }
+
+    fn add_lock_sig(
+        &mut self,
+        id: [u8; 32],
+        timelock: u64,
+        ed25519: Option<Instruction>,
+    ) -> Result<Vec<u8>, ProgramError> {
+        let add_lock_sig = native_htlc::add_lock_sig(
+            &self.solver,
+            &self.sender,
+            id,
+            hashlock(),
+            HashAlgorithm::Sha256,
+            timelock,
+        );
+        let instructions: Vec<Instruction> = ed25519.into_iter().chain([add_lock_sig]).collect();
+        self.svm.process(&instructions, &[self.solver])
+    }
+
+    /// The message the sender signs to authorize `add_lock_sig` on the HTLC with `id`.
+    fn add_lock_message(&mut self, id: [u8; 32], timelock: u64) -> Vec<u8> {
+        let htlc = self.details(id);
+        native_htlc::add_lock_message(&id, &htlc, &hashlock(), HashAlgorithm::Sha256, timelock)
+    }
+
+    fn details(&mut self, id: [u8; 32]) -> HTLC {
+        let instruction = native_htlc::get_details(&self.sender, id);
+        let data = self.process(instruction, &[]).unwrap();
+        HTLC::try_from_slice(&data).unwrap()
+    }
+
+    fn htlc_account(&self, id: [u8; 32]) -> Pubkey {
+        native_htlc::htlc_address(&self.sender, &id).0
+    }
+}
+
+fn hashlock() -> [u8; 32] {
+    Sha256::digest(SECRET).into()
+}
+
+fn htlc_error(error: HTLCError) -> ProgramError {
+    ProgramError::Custom(error.into())
+}
+
+fn anchor_error(error: ErrorCode) -> ProgramError {
+    ProgramError::Custom(error.into())
+}
+
+#[test]
+fn get_commit_id_matches_client() {
+    let mut test = Test::new();
+    let (sender, src_receiver, timelock) = (test.sender, test.src_receiver, test.timelock);
+    let instruction = native_htlc::get_commit_id(&sender, &src_receiver, AMOUNT, timelock);
+
+    let id = test.process(instruction, &[]).unwrap();
+
+    assert_eq!(
+        id,
+        native_htlc::commit_id(&sender, &src_receiver, AMOUNT, timelock)
+    );
+}
+
+#[test]
+fn commit_escrows_amount_and_stores_route() {
+    let mut test = Test::new();
+    let sender_before = test.svm.lamports(&test.sender);
+
+    let id = test.commit(test.commit_params([1; 32])).unwrap();
+
+    assert_eq!(id, [1; 32]);
+    let event = test.svm.event::<TokenCommitted>();
+    assert_eq!(
+        (event.Id, event.sender, event.src_receiver),
+        ([1; 32], test.sender, test.src_receiver)
+    );
+    assert_eq!(
+        (event.amount, event.timelock, event.timelock_kind),
+        (AMOUNT, test.timelock, TimelockKind::Timestamp)
+    );
+    assert_eq!(event.hopChains, vec!["SOLANA".to_string()]);
+    assert_eq!(event.hopAddresses, vec!["0x1".to_string()]);
+    assert_eq!(event.dst_chain, "ETHEREUM_SEPOLIA");
+    let htlc_account = test
+        .svm
+        .account(&test.htlc_account([1; 32]))
+        .unwrap()
+        .clone();
+    let htlc = native_htlc::decode_htlc(&htlc_account.data).unwrap();
+    assert_eq!(htlc.sender, test.sender);
+    assert_eq!(htlc.src_receiver, test.src_receiver);
+    assert_eq!(htlc.hop_chains, vec!["SOLANA".to_string()]);
+    assert_eq!(htlc.hashlock, [0; 32]);
+    assert_eq!(htlc.status, HtlcStatus::Committed);
+    assert_eq!(htlc.amount, AMOUNT);
+    assert_eq!(
+        test.svm.lamports(&test.sender),
+        sender_before - htlc_account.lamports
+    );
+    assert_eq!(test.details([1; 32]).amount, AMOUNT);
+}
+
+#[test]
+fn commit_rejects_invalid_arguments() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+
+    let mut params = test.commit_params([1; 32]);
+    params.timelock = now;
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.amount = 0;
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::FundsNotSent))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.hop_chains = vec!["SOLANA".into(); MAX_HOPS + 1];
+    assert_eq!(test.commit(params), Err(htlc_error(HTLCError::TooManyHops)));
+
+    let mut params = test.commit_params([1; 32]);
+    params.hop_addresses = vec!["a".repeat(MAX_STRING_LENGTH + 1)];
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::StringTooLong))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.dst_address = "a".repeat(MAX_STRING_LENGTH + 1);
+    assert_eq!(
+        test.commit(params),
+        Err(htlc_error(HTLCError::StringTooLong))
+    );
+
+    let mut params = test.commit_params([1; 32]);
+    params.dst_address = "a".repeat(MAX_STRING_LENGTH);
+    params.hop_chains = vec!["a".repeat(MAX_STRING_LENGTH); MAX_HOPS];
+    test.commit(params).unwrap();
+    assert_eq!(
+        test.details([1; 32]).dst_address,
+        "a".repeat(MAX_STRING_LENGTH)
+    );
+}
+
+#[test]
+fn lock_rejects_invalid_arguments() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+
+    let mut params = test.lock_params([1; 32]);
+    params.timelock = now;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.amount = 0;
+    assert_eq!(test.lock(params), Err(htlc_error(HTLCError::FundsNotSent)));
+
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock + 1;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::InvalidRewardTimelock))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.amount = u64::MAX;
+    params.reward = 1;
+    params.reward_timelock = test.timelock - 100;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::AmountOverflow))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = now - 1;
+    assert_eq!(
+        test.lock(params),
+        Err(htlc_error(HTLCError::InvalidRewardTimelock))
+    );
+
+    let mut params = test.lock_params([1; 32]);
+    params.src_asset = "a".repeat(MAX_STRING_LENGTH + 1);
+    assert_eq!(test.lock(params), Err(htlc_error(HTLCError::StringTooLong)));
+}
+
+#[test]
+fn lock_and_redeem_pays_src_receiver() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    let event = test.svm.event::<TokenLocked>();
+    assert_eq!(
+        (event.Id, event.hashlock, event.sender, event.src_receiver),
+        ([1; 32], hashlock(), test.sender, test.src_receiver)
+    );
+    assert_eq!(
+        (event.amount, event.reward, event.timelock),
+        (AMOUNT, 0, test.timelock)
+    );
+    let receiver_before = test.svm.lamports(&test.src_receiver);
+
+    test.redeem([1; 32]).unwrap();
+    let event = test.svm.event::<TokenRedeemed>();
+    assert_eq!(
+        (
+            event.Id,
+            event.sender,
+            event.redeem_address,
+            event.destination
+        ),
+        ([1; 32], test.sender, test.solver, test.src_receiver)
+    );
+    assert_eq!((event.secret, event.hashlock), (SECRET, hashlock()));
+
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + AMOUNT
+    );
+    let htlc = test.details([1; 32]);
+    assert_eq!(htlc.status, HtlcStatus::Redeemed);
+    assert_eq!(htlc.secret, SECRET);
+}
+
+#[test]
+fn redeem_checks_keccak_hashlock() {
+    let mut test = Test::new();
+    let keccak = keccak::hash(&SECRET).to_bytes();
+    let mut params = test.lock_params([1; 32]);
+    params.hash_algorithm = HashAlgorithm::Keccak256;
+    test.lock(params.clone()).unwrap();
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+
+    params.id = [2; 32];
+    params.hashlock = keccak;
+    test.lock(params).unwrap();
+    test.redeem([2; 32]).unwrap();
+    let htlc = test.details([2; 32]);
+    assert_eq!(htlc.hash_algorithm, HashAlgorithm::Keccak256);
+    assert_eq!(htlc.status, HtlcStatus::Redeemed);
+
+    test.commit(test.commit_params([3; 32])).unwrap();
+    assert_eq!(test.details([3; 32]).hash_algorithm, HashAlgorithm::Sha256);
+    let (sender, timelock) = (test.sender, test.timelock);
+    test.process(
+        native_htlc::add_lock(
+            &sender,
+            &sender,
+            [3; 32],
+            keccak,
+            HashAlgorithm::Keccak256,
+            timelock,
+        ),
+        &[sender],
+    )
+    .unwrap();
+    assert_eq!(
+        test.details([3; 32]).hash_algorithm,
+        HashAlgorithm::Keccak256
+    );
+    test.redeem([3; 32]).unwrap();
+}
+
+#[test]
+fn redeem_rejects_invalid_calls() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::HashlockNotSet))
+    );
+
+    test.lock(test.lock_params([2; 32])).unwrap();
+    let solver = test.solver;
+    assert_eq!(
+        test.redeem_by(solver, [2; 32], [8; 32]),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+
+    let other_receiver = Pubkey::new_unique();
+    let instruction = native_htlc::redeem(&solver, &test.sender, &other_receiver, [2; 32], SECRET);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::NotReciever))
+    );
+
+    let other_sender = test.svm.new_funded_account();
+    let mut instruction =
+        native_htlc::redeem(&solver, &other_sender, &test.src_receiver, [2; 32], SECRET);
+    instruction.accounts[1].pubkey = test.htlc_account([2; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+
+    test.redeem([2; 32]).unwrap();
+    assert_eq!(
+        test.redeem([2; 32]),
+        Err(htlc_error(HTLCError::AlreadyRedeemed))
+    );
+
+    test.lock(test.lock_params([3; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    test.refund([3; 32]).unwrap();
+    assert_eq!(
+        test.redeem([3; 32]),
+        Err(htlc_error(HTLCError::AlreadyRefunded))
+    );
+}
+
+#[test]
+fn redeem_to_pays_destination_authorized_by_src_receiver() {
+    let mut test = Test::new();
+    let receiver_key = svm::ed25519_keypair([5; 32]);
+    test.src_receiver = Pubkey::from(receiver_key.public.to_bytes());
+    test.svm.airdrop(&test.src_receiver, 10_000_000_000);
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    let (sender, src_receiver, solver) = (test.sender, test.src_receiver, test.solver);
+    let destination = test.svm.new_funded_account();
+    let receiver_before = test.svm.lamports(&src_receiver);
+    let destination_before = test.svm.lamports(&destination);
+
+    let instruction = native_htlc::redeem_to(
+        &src_receiver,
+        &sender,
+        &src_receiver,
+        &destination,
+        [1; 32],
+        SECRET,
+    );
+    test.process(instruction, &[src_receiver]).unwrap();
+    assert_eq!(test.svm.lamports(&destination), destination_before + AMOUNT);
+    assert_eq!(test.svm.lamports(&src_receiver), receiver_before);
+
+    let redeem_to = |destination: &Pubkey| {
+        native_htlc::redeem_to(
+            &solver,
+            &sender,
+            &src_receiver,
+            destination,
+            [2; 32],
+            SECRET,
+        )
+    };
+    assert_eq!(
+        test.process(redeem_to(&destination), &[solver]),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let message =
+        native_htlc::redeem_to_message(&sender, &[2; 32], &hashlock(), AMOUNT, &destination);
+    let signature = svm::ed25519_instruction(&receiver_key, &message);
+    assert_eq!(
+        test.svm
+            .process(&[signature.clone(), redeem_to(&solver)], &[solver]),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    test.svm
+        .process(&[signature.clone(), redeem_to(&destination)], &[solver])
+        .unwrap();
+
+    assert_eq!(
+        test.svm.lamports(&destination),
+        destination_before + 2 * AMOUNT
+    );
+    assert_eq!(test.svm.lamports(&src_receiver), receiver_before);
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Redeemed);
+
+    // The signature does not carry over to an HTLC recreated under the same Id.
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.close([2; 32]).unwrap();
+    test.timelock += CLOSE_GRACE_PERIOD + 3600;
+    let secret = [9; 32];
+    let mut params = test.lock_params([2; 32]);
+    params.hashlock = Sha256::digest(secret).into();
+    test.lock(params).unwrap();
+    let replay = native_htlc::redeem_to(
+        &solver,
+        &sender,
+        &src_receiver,
+        &destination,
+        [2; 32],
+        secret,
+    );
+    assert_eq!(
+        test.svm.process(&[signature, replay], &[solver]),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+}
+
+#[test]
+fn redeem_batch_pays_every_src_receiver() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.commit(test.commit_params([2; 32])).unwrap();
+    test.add_lock([2; 32], test.timelock).unwrap();
+    let receiver_before = test.svm.lamports(&test.src_receiver);
+
+    let items = [
+        test.batch_item([1; 32], SECRET),
+        test.batch_item([2; 32], SECRET),
+    ];
+    assert_eq!(test.redeem_batch(&items, true), Ok(vec![true, true]));
+
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + 2 * AMOUNT
+    );
+    for id in [[1; 32], [2; 32]] {
+        let htlc = test.details(id);
+        assert_eq!(htlc.status, HtlcStatus::Redeemed);
+        assert_eq!(htlc.secret, SECRET);
+    }
+}
+
+#[test]
+fn redeem_batch_fails_or_skips_invalid_items() {
+    let mut test = Test::new();
+    for id in [[1; 32], [2; 32], [3; 32]] {
+        test.lock(test.lock_params(id)).unwrap();
+    }
+    let items = [
+        test.batch_item([1; 32], SECRET),
+        test.batch_item([2; 32], [8; 32]),
+        test.batch_item([3; 32], SECRET),
+    ];
+    let receiver_before = test.svm.lamports(&test.src_receiver);
+
+    assert_eq!(
+        test.redeem_batch(&items, true),
+        Err(htlc_error(HTLCError::HashlockNoMatch))
+    );
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Locked);
+
+    assert_eq!(
+        test.redeem_batch(&items, false),
+        Ok(vec![true, false, true])
+    );
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + 2 * AMOUNT
+    );
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Locked);
+    assert_eq!(test.details([3; 32]).status, HtlcStatus::Redeemed);
+
+    let mut wrong_receiver = test.batch_item([2; 32], SECRET);
+    wrong_receiver.src_receiver = test.solver;
+    assert_eq!(
+        test.redeem_batch(&[wrong_receiver], true),
+        Err(htlc_error(HTLCError::NotReciever))
+    );
+
+    let solver = test.solver;
+    let mut instruction = native_htlc::redeem_batch(&solver, &items[1..2], true);
+    instruction.accounts.pop();
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::InvalidBatch))
+    );
+}
+
+#[test]
+fn redeem_and_refund_meet_at_the_timelock() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+
+    test.svm.warp_to(test.timelock as i64 - 1);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([2; 32]).unwrap();
+
+    test.svm.warp_to(test.timelock as i64);
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    let sender_before = test.svm.lamports(&test.sender);
+    test.refund([1; 32]).unwrap();
+    assert_eq!(test.svm.lamports(&test.sender), sender_before + AMOUNT);
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Refunded);
+
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::AlreadyRefunded))
+    );
+    assert_eq!(
+        test.refund([2; 32]),
+        Err(htlc_error(HTLCError::AlreadyRedeemed))
+    );
+}
+
+#[test]
+fn reward_goes_to_sender_before_reward_timelock() {
+    let mut test = Test::new();
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock - 100;
+    test.lock(params).unwrap();
+    let (sender_before, receiver_before) = (
+        test.svm.lamports(&test.sender),
+        test.svm.lamports(&test.src_receiver),
+    );
+
+    test.redeem([1; 32]).unwrap();
+
+    assert_eq!(test.svm.lamports(&test.sender), sender_before + REWARD);
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + AMOUNT
+    );
+}
+
+#[test]
+fn reward_goes_to_redeemer_after_reward_timelock() {
+    let mut test = Test::new();
+    for id in [[1; 32], [2; 32]] {
+        let mut params = test.lock_params(id);
+        params.reward = REWARD;
+        params.reward_timelock = test.timelock - 100;
+        test.lock(params).unwrap();
+    }
+    test.svm.warp_to(test.timelock as i64 - 100);
+    let (solver_before, receiver_before) = (
+        test.svm.lamports(&test.solver),
+        test.svm.lamports(&test.src_receiver),
+    );
+
+    test.redeem([1; 32]).unwrap();
+    assert_eq!(test.svm.lamports(&test.solver), solver_before + REWARD);
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + AMOUNT
+    );
+
+    let src_receiver = test.src_receiver;
+    test.redeem_by(src_receiver, [2; 32], SECRET).unwrap();
+    assert_eq!(
+        test.svm.lamports(&test.src_receiver),
+        receiver_before + 2 * AMOUNT + REWARD
+    );
+}
+
+#[test]
+fn refund_returns_amount_and_reward() {
+    let mut test = Test::new();
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock;
+    test.lock(params).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    let sender_before = test.svm.lamports(&test.sender);
+
+    test.refund([1; 32]).unwrap();
+
+    let event = test.svm.event::<TokenRefunded>();
+    assert_eq!((event.Id, event.sender), ([1; 32], test.sender));
+    assert_eq!(
+        test.svm.lamports(&test.sender),
+        sender_before + AMOUNT + REWARD
+    );
+}
+
+#[test]
+fn refund_batch_refunds_expired_htlcs() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    let mut params = test.lock_params([2; 32]);
+    params.timelock += 1000;
+    test.lock(params).unwrap();
+    test.lock(test.lock_params([3; 32])).unwrap();
+    test.redeem([3; 32]).unwrap();
+    test.commit(test.commit_params([4; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    let sender_before = test.svm.lamports(&test.sender);
+
+    let sender = test.sender;
+    let mut items: Vec<RefundBatchItem> = [[1; 32], [2; 32], [3; 32], [4; 32]]
+        .into_iter()
+        .map(|id| RefundBatchItem { id, sender })
+        .collect();
+    items.push(RefundBatchItem {
+        id: [1; 32],
+        sender: test.solver,
+    });
+    let solver = test.solver;
+    let data = test
+        .process(native_htlc::refund_batch(&solver, &items), &[solver])
+        .unwrap();
+
+    assert_eq!(
+        Vec::<bool>::try_from_slice(&data).unwrap(),
+        vec![true, false, false, true, false]
+    );
+    assert_eq!(test.svm.lamports(&test.sender), sender_before + 2 * AMOUNT);
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Refunded);
+    assert_eq!(test.details([2; 32]).status, HtlcStatus::Locked);
+    assert_eq!(test.details([4; 32]).status, HtlcStatus::Refunded);
+
+    let mut instruction = native_htlc::refund_batch(&solver, &items[1..2]);
+    instruction.accounts.pop();
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(htlc_error(HTLCError::InvalidBatch))
+    );
+}
+
+#[test]
+fn add_lock_sets_hashlock_of_commit() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let now = test.svm.unix_timestamp() as u64;
+
+    assert_eq!(
+        test.add_lock([1; 32], now),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+    test.add_lock([1; 32], test.timelock + 10).unwrap();
+    let event = test.svm.event::<TokenLockAdded>();
+    assert_eq!(
+        (event.Id, event.sender, event.hashlock, event.timelock),
+        ([1; 32], test.sender, hashlock(), test.timelock + 10)
+    );
+
+    let htlc = test.details([1; 32]);
+    assert_eq!(htlc.hashlock, hashlock());
+    assert_eq!(htlc.timelock, test.timelock + 10);
+    assert_eq!(htlc.status, HtlcStatus::Locked);
+    assert_eq!(
+        test.add_lock([1; 32], test.timelock),
+        Err(htlc_error(HTLCError::HashlockAlreadySet))
+    );
+    test.redeem([1; 32]).unwrap();
+}
+
+#[test]
+fn add_lock_rejects_claimed_htlcs() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    test.redeem([2; 32]).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    test.refund([1; 32]).unwrap();
+
+    let timelock = test.timelock + 10;
+    assert_eq!(
+        test.add_lock([1; 32], timelock),
+        Err(htlc_error(HTLCError::AlreadyRefunded))
+    );
+    assert_eq!(
+        test.add_lock([2; 32], timelock),
+        Err(htlc_error(HTLCError::AlreadyRedeemed))
+    );
+
+    let (solver, sender) = (test.solver, test.sender);
+    let mut instruction = native_htlc::add_lock(
+        &solver,
+        &solver,
+        [1; 32],
+        hashlock(),
+        HashAlgorithm::Sha256,
+        timelock,
+    );
+    instruction.accounts[2].pubkey = test.htlc_account([1; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+    let instruction = native_htlc::add_lock(
+        &sender,
+        &sender,
+        [3; 32],
+        hashlock(),
+        HashAlgorithm::Sha256,
+        timelock,
+    );
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(anchor_error(ErrorCode::AccountNotInitialized))
+    );
+}
+
+#[test]
+fn add_lock_sig_accepts_signature_of_sender() {
+    let mut test = Test::new();
+    let sender_key = svm::ed25519_keypair([9; 32]);
+    test.sender = Pubkey::from(sender_key.public.to_bytes());
+    test.svm.airdrop(&test.sender, 10_000_000_000);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let timelock = test.timelock + 10;
+
+    let message = test.add_lock_message([1; 32], timelock);
+    test.add_lock_sig(
+        [1; 32],
+        timelock,
+        Some(svm::ed25519_instruction(&sender_key, &message)),
+    )
+    .unwrap();
+
+    assert_eq!(test.details([1; 32]).hashlock, hashlock());
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            timelock,
+            Some(svm::ed25519_instruction(&sender_key, &message))
+        ),
+        Err(htlc_error(HTLCError::HashlockAlreadySet))
+    );
+}
+
+#[test]
+fn add_lock_sig_rejects_invalid_signatures() {
+    let mut test = Test::new();
+    let sender_key = svm::ed25519_keypair([9; 32]);
+    test.sender = Pubkey::from(sender_key.public.to_bytes());
+    test.svm.airdrop(&test.sender, 10_000_000_000);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    let timelock = test.timelock + 10;
+    let message = test.add_lock_message([1; 32], timelock);
+
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, None),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let other_key = svm::ed25519_keypair([10; 32]);
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            timelock,
+            Some(svm::ed25519_instruction(&other_key, &message))
+        ),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let other_message = test.add_lock_message([1; 32], timelock + 1);
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            timelock,
+            Some(svm::ed25519_instruction(&sender_key, &other_message))
+        ),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let mut keccak_message = message.clone();
+    *keccak_message.last_mut().unwrap() = HashAlgorithm::Keccak256 as u8;
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            timelock,
+            Some(svm::ed25519_instruction(&sender_key, &keccak_message))
+        ),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    let now = test.svm.unix_timestamp() as u64;
+    let message = test.add_lock_message([1; 32], now);
+    assert_eq!(
+        test.add_lock_sig(
+            [1; 32],
+            now,
+            Some(svm::ed25519_instruction(&sender_key, &message))
+        ),
+        Err(htlc_error(HTLCError::NotFutureTimeLock))
+    );
+}
+
+#[test]
+fn add_lock_sig_rejects_replay_on_recreated_htlc() {
+    let sender_key = svm::ed25519_keypair([9; 32]);
+    let mut test = Test::new();
+    test.sender = Pubkey::from(sender_key.public.to_bytes());
+    test.svm.airdrop(&test.sender, 10_000_000_000);
+    test.commit(test.commit_params([1; 32])).unwrap();
+    // A signature the sender handed out but that was never submitted.
+    let timelock = test.timelock + CLOSE_GRACE_PERIOD + 7200;
+    let message = test.add_lock_message([1; 32], timelock);
+    let signature = svm::ed25519_instruction(&sender_key, &message);
+
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.refund([1; 32]).unwrap();
+    test.close([1; 32]).unwrap();
+    test.timelock += CLOSE_GRACE_PERIOD + 3600;
+    test.commit(test.commit_params([1; 32])).unwrap();
+
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, Some(signature)),
+        Err(htlc_error(HTLCError::InvalidSignature))
+    );
+    assert_eq!(test.details([1; 32]).status, HtlcStatus::Committed);
+}
+
+#[test]
+fn extend_timelock_pushes_back_redeem_and_refund() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.commit(test.commit_params([2; 32])).unwrap();
+    let (sender, solver) = (test.sender, test.solver);
+
+    let instruction = native_htlc::extend_timelock(&sender, [1; 32], test.timelock);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimelockNotIncreased))
+    );
+    let mut instruction = native_htlc::extend_timelock(&solver, [1; 32], test.timelock + 100);
+    instruction.accounts[1].pubkey = test.htlc_account([1; 32]);
+    assert_eq!(
+        test.process(instruction, &[solver]),
+        Err(anchor_error(ErrorCode::ConstraintSeeds))
+    );
+    for (id, status) in [
+        ([1; 32], HtlcStatus::Locked),
+        ([2; 32], HtlcStatus::Committed),
+    ] {
+        let instruction = native_htlc::extend_timelock(&sender, id, test.timelock + 100);
+        test.process(instruction, &[sender]).unwrap();
+        let event = test.svm.event::<TokenTimelockExtended>();
+        assert_eq!(
+            (event.Id, event.sender, event.timelock),
+            (id, test.sender, test.timelock + 100)
+        );
+        let details = test.details(id);
+        assert_eq!(
+            (details.timelock, details.status),
+            (test.timelock + 100, status)
+        );
+    }
+
+    test.svm.warp_to(test.timelock as i64);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([1; 32]).unwrap();
+    let instruction = native_htlc::extend_timelock(&sender, [1; 32], test.timelock + 200);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::AlreadyRedeemed))
+    );
+
+    // An extension closer than the minimum delta of the config is accepted.
+    test.svm.warp_to(test.timelock as i64 + 90);
+    let instruction = native_htlc::extend_timelock(&sender, [2; 32], test.timelock + 110);
+    test.process(instruction, &[sender]).unwrap();
+
+    test.svm.warp_to(test.timelock as i64 + 110);
+    let instruction = native_htlc::extend_timelock(&sender, [2; 32], test.timelock + 200);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    test.refund([2; 32]).unwrap();
+    let instruction = native_htlc::extend_timelock(&sender, [2; 32], test.timelock + 200);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::AlreadyRefunded))
+    );
+}
+
+#[test]
+fn config_is_created_by_upgrade_authority_and_updated_by_admin() {
+    let mut svm = Svm::new(&[(native_htlc::ID, sol::entry)]);
+    let (admin, other) = (svm.new_funded_account(), svm.new_funded_account());
+    svm.set_upgrade_authority(&native_htlc::ID, &admin);
+    assert_eq!(
+        svm.process(
+            &[native_htlc::initialize_config(&other, 60, 3600)],
+            &[other]
+        ),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        svm.process(
+            &[native_htlc::initialize_config(&admin, 3600, 60)],
+            &[admin]
+        ),
+        Err(htlc_error(HTLCError::InvalidTimelockBounds))
+    );
+    svm.process(
+        &[native_htlc::initialize_config(&admin, 60, 3600)],
+        &[admin],
+    )
+    .unwrap();
+    assert_eq!(
+        svm.process(
+            &[native_htlc::initialize_config(&admin, 60, 3600)],
+            &[admin]
+        ),
+        Err(ProgramError::Custom(
+            SystemError::AccountAlreadyInUse as u32
+        ))
+    );
+
+    assert_eq!(
+        svm.process(&[native_htlc::update_config(&other, 0, 7200)], &[other]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        svm.process(&[native_htlc::update_config(&admin, 7200, 0)], &[admin]),
+        Err(htlc_error(HTLCError::InvalidTimelockBounds))
+    );
+    svm.process(&[native_htlc::update_config(&admin, 0, 7200)], &[admin])
+        .unwrap();
+    let event = svm.event::<ConfigUpdated>();
+    assert_eq!(
+        (
+            event.admin,
+            event.min_timelock_delta,
+            event.max_timelock_delta
+        ),
+        (admin, 0, 7200)
+    );
+    let account = svm.account(&native_htlc::config_address().0).unwrap();
+    let config = native_htlc::decode_config(&account.data).unwrap();
+    assert_eq!(
+        (
+            config.admin,
+            config.min_timelock_delta,
+            config.max_timelock_delta
+        ),
+        (admin, 0, 7200)
+    );
+}
+
+#[test]
+fn timelock_bounds_apply_to_commit_lock_and_add_lock() {
+    let mut test = Test::new();
+    let now = test.svm.unix_timestamp() as u64;
+    let (min, max) = (now + MIN_TIMELOCK_DELTA, now + MAX_TIMELOCK_DELTA);
+
+    let mut params = test.commit_params([1; 32]);
+    params.timelock = min - 1;
+    assert_eq!(
+        test.commit(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    params.timelock = max + 1;
+    assert_eq!(
+        test.commit(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    params.timelock = min;
+    test.commit(params).unwrap();
+
+    let mut params = test.lock_params([2; 32]);
+    params.timelock = min - 1;
+    assert_eq!(
+        test.lock(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    params.timelock = max + 1;
+    assert_eq!(
+        test.lock(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    params.timelock = max;
+    test.lock(params).unwrap();
+
+    assert_eq!(
+        test.add_lock([1; 32], min - 1),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    assert_eq!(
+        test.add_lock([1; 32], max + 1),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    let sender = test.sender;
+    let instruction = native_htlc::extend_timelock(&sender, [2; 32], max + 1);
+    assert_eq!(
+        test.process(instruction, &[sender]),
+        Err(htlc_error(HTLCError::TimelockTooLong))
+    );
+    test.add_lock([1; 32], max).unwrap();
+
+    let admin = test.admin;
+    let instruction = native_htlc::update_config(&admin, 0, 2 * MAX_TIMELOCK_DELTA);
+    test.process(instruction, &[admin]).unwrap();
+    let instruction = native_htlc::extend_timelock(&sender, [2; 32], max + 1);
+    test.process(instruction, &[sender]).unwrap();
+    let mut params = test.lock_params([3; 32]);
+    params.timelock = now + 1;
+    test.lock(params).unwrap();
+}
+
+#[test]
+fn pause_blocks_new_htlcs_but_not_redeem_and_refund() {
+    let mut test = Test::new();
+    test.commit(test.commit_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    test.lock(test.lock_params([3; 32])).unwrap();
+
+    let (admin, sender) = (test.admin, test.sender);
+    assert_eq!(
+        test.process(native_htlc::pause(&sender), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(native_htlc::pause(&admin), &[admin]).unwrap();
+    assert_eq!(test.svm.event::<Paused>().admin, admin);
+    let account = test.svm.account(&native_htlc::config_address().0).unwrap();
+    assert!(native_htlc::decode_config(&account.data).unwrap().paused);
+
+    assert_eq!(
+        test.commit(test.commit_params([4; 32])),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    assert_eq!(
+        test.lock(test.lock_params([4; 32])),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    let timelock = test.timelock;
+    assert_eq!(
+        test.add_lock([1; 32], timelock),
+        Err(htlc_error(HTLCError::Paused))
+    );
+    assert_eq!(
+        test.add_lock_sig([1; 32], timelock, None),
+        Err(htlc_error(HTLCError::Paused))
+    );
+
+    test.redeem([2; 32]).unwrap();
+    test.svm.warp_to(timelock as i64);
+    test.refund([3; 32]).unwrap();
+
+    assert_eq!(
+        test.process(native_htlc::unpause(&sender), &[sender]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(native_htlc::unpause(&admin), &[admin])
+        .unwrap();
+    assert_eq!(test.svm.event::<Unpaused>().admin, admin);
+    test.timelock = timelock + 3600;
+    test.commit(test.commit_params([4; 32])).unwrap();
+}
+
+#[test]
+fn transfer_admin_hands_over_the_config() {
+    let mut test = Test::new();
+    let (admin, other) = (test.admin, test.sender);
+    assert_eq!(
+        test.process(native_htlc::transfer_admin(&other, &other), &[other]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(native_htlc::transfer_admin(&admin, &other), &[admin])
+        .unwrap();
+    let event = test.svm.event::<AdminTransferred>();
+    assert_eq!((event.previous_admin, event.new_admin), (admin, other));
+    let account = test.svm.account(&native_htlc::config_address().0).unwrap();
+    assert_eq!(
+        native_htlc::decode_config(&account.data).unwrap().admin,
+        other
+    );
+
+    assert_eq!(
+        test.process(native_htlc::pause(&admin), &[admin]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    assert_eq!(
+        test.process(native_htlc::update_config(&admin, 0, 7200), &[admin]),
+        Err(htlc_error(HTLCError::UnauthorizedAccess))
+    );
+    test.process(native_htlc::pause(&other), &[other]).unwrap();
+    test.process(native_htlc::update_config(&other, 0, 7200), &[other])
+        .unwrap();
+}
+
+#[test]
+fn slot_timelock_expires_at_its_slot() {
+    let mut test = Test::new();
+    let min_slots = MIN_TIMELOCK_DELTA * 1000 / DEFAULT_MS_PER_SLOT;
+    let timelock = test.svm.slot() + 1_000;
+    let mut params = test.lock_params([1; 32]);
+    params.timelock_kind = TimelockKind::Slot;
+    params.timelock = test.svm.slot() + min_slots - 1;
+    assert_eq!(
+        test.lock(params.clone()),
+        Err(htlc_error(HTLCError::TimelockTooShort))
+    );
+    for id in [[1; 32], [2; 32]] {
+        params.id = id;
+        params.timelock = timelock;
+        test.lock(params.clone()).unwrap();
+    }
+    let htlc = test.details([1; 32]);
+    assert_eq!(
+        (htlc.timelock_kind, htlc.timelock),
+        (TimelockKind::Slot, timelock)
+    );
+
+    // The unix timestamp passing the slot height does not expire the HTLC.
+    test.svm.warp_to((timelock + CLOSE_GRACE_PERIOD) as i64);
+    test.svm.warp_to_slot(timelock - 1);
+    assert_eq!(
+        test.refund([1; 32]),
+        Err(htlc_error(HTLCError::NotPastTimeLock))
+    );
+    test.redeem([2; 32]).unwrap();
+
+    test.svm.warp_to_slot(timelock);
+    assert_eq!(
+        test.redeem([1; 32]),
+        Err(htlc_error(HTLCError::TimeLockExpired))
+    );
+    test.refund([1; 32]).unwrap();
+
+    let grace_end = timelock + CLOSE_GRACE_PERIOD * 1000 / DEFAULT_MS_PER_SLOT;
+    test.svm.warp_to_slot(grace_end - 1);
+    assert_eq!(
+        test.close([1; 32]),
+        Err(htlc_error(HTLCError::NotPastGracePeriod))
+    );
+    test.svm.warp_to_slot(grace_end);
+    test.close([1; 32]).unwrap();
+}
+
+#[test]
+fn close_returns_rent_after_grace_period() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    assert_eq!(test.close([1; 32]), Err(htlc_error(HTLCError::NotClaimed)));
+
+    test.redeem([1; 32]).unwrap();
+    let grace_end = (test.timelock + CLOSE_GRACE_PERIOD) as i64;
+    test.svm.warp_to(grace_end - 1);
+    assert_eq!(
+        test.close([1; 32]),
+        Err(htlc_error(HTLCError::NotPastGracePeriod))
+    );
+
+    test.svm.warp_to(grace_end);
+    let rent = test.svm.lamports(&test.htlc_account([1; 32]));
+    let sender_before = test.svm.lamports(&test.sender);
+    let solver_before = test.svm.lamports(&test.solver);
+    test.close([1; 32]).unwrap();
+
+    let event = test.svm.event::<TokenClosed>();
+    assert_eq!(
+        (event.Id, event.sender, event.keeper_tip),
+        ([1; 32], test.sender, KEEPER_TIP)
+    );
+    assert_eq!(
+        test.svm.lamports(&test.sender),
+        sender_before + rent - KEEPER_TIP
+    );
+    assert_eq!(test.svm.lamports(&test.solver), solver_before + KEEPER_TIP);
+    assert_eq!(test.svm.lamports(&test.htlc_account([1; 32])), 0);
+}
+
+#[test]
+fn keeper_refunds_and_closes_for_the_sender() {
+    let mut test = Test::new();
+    test.lock(test.lock_params([1; 32])).unwrap();
+    test.lock(test.lock_params([2; 32])).unwrap();
+    test.svm.warp_to(test.timelock as i64);
+    let sender_before = test.svm.lamports(&test.sender);
+
+    test.refund([1; 32]).unwrap();
+    assert_eq!(test.svm.lamports(&test.sender), sender_before + AMOUNT);
+
+    test.svm
+        .warp_to((test.timelock + CLOSE_GRACE_PERIOD) as i64);
+    let sender = test.sender;
+    test.refund([2; 32]).unwrap();
+    let rent = test.svm.lamports(&test.htlc_account([2; 32]));
+    let sender_before = test.svm.lamports(&sender);
+    let close = native_htlc::close(&sender, &sender, [2; 32]);
+    test.process(close, &[sender]).unwrap();
+    let event = test.svm.event::<TokenClosed>();
+    assert_eq!((event.sender, event.keeper_tip), (sender, 0));
+    assert_eq!(test.svm.lamports(&sender), sender_before + rent);
+}
+
+#[test]
+fn get_reward_details_returns_reward() {
+    let mut test = Test::new();
+    let mut params = test.lock_params([1; 32]);
+    params.reward = REWARD;
+    params.reward_timelock = test.timelock - 100;
+    test.lock(params).unwrap();
+
+    let instruction = native_htlc::get_reward_details(&test.sender, [1; 32]);
+    let reward = Reward::try_from_slice(&test.process(instruction, &[]).unwrap()).unwrap();
+
+    assert_eq!(reward.amount, REWARD);
+    assert_eq!(reward.timelock, test.timelock - 100);
+}
@@ -0,0 +1,848 @@
+//! A small in-process SVM for the integration tests.
+//!
+//! Programs run natively through their `entry` function. Each top-level instruction is
+//! serialized the way the BPF loader does it and deserialized with
+//! `solana_program::entrypoint::deserialize`, so `realloc`, `assign` and duplicate
+//! accounts behave as on-chain. Syscalls are served by `program_stubs`: cross-program
+//! invocations are dispatched to the registered programs, the clock can be warped and
+//! return data and logs are captured. The system program and the Ed25519 precompile are
+//! reimplemented here; the token, Token-2022 and associated token programs run their
+//! real processors.
+//!
+//! Around every invocation, cross-program ones included, the account rules of the runtime
+//! are checked: only the owner may change the data of an account or debit its lamports,
+//! read-only and executable accounts stay as they are, an owner is only handed over by the
+//! current owner with zeroed data, and lamports are balanced. After a transaction every
+//! account it changed has to be rent exempt, empty, or rent paying as before. Events are
+//! logged base64 encoded like on-chain and decoded by [`Svm::events`].
+//!
+//! Compute units and fees are not enforced; `tests/compute_units.ts` of each program
+//! measures compute units on a validator. `solana-program-test` 1.18 can not replace this
+//! runtime as it pins the solana crates to `=1.18.0`, while `anchor-spl` 0.30 needs
+//! `solana-zk-token-sdk` 1.18.11 or later.
+
+#![allow(dead_code)]
+
+use std::{cell::RefCell, collections::HashMap, mem::size_of, sync::Once};
+
+use anchor_lang::{
+    solana_program::{
+        account_info::AccountInfo,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        clock::Clock,
+        ed25519_program,
+        entrypoint::{self, ProgramResult, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER},
+        instruction::Instruction,
+        program_error::ProgramError,
+        program_stubs::{self, SyscallStubs},
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction::{SystemError, SystemInstruction},
+        system_program, sysvar,
+        sysvar::instructions::{
+            construct_instructions_data, store_current_index, BorrowedAccountMeta,
+            BorrowedInstruction,
+        },
+    },
+    Event,
+};
+use anchor_spl::{
+    associated_token::spl_associated_token_account, token::spl_token, token_2022::spl_token_2022,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer};
+
+/// The entrypoint of a program, as generated by Anchor.
+pub type Entry = for<'info> fn(&Pubkey, &'info [AccountInfo<'info>], &[u8]) -> ProgramResult;
+
+/// Starting point of the cluster clock.
+pub const GENESIS_UNIX_TIMESTAMP: i64 = 1_700_000_000;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+/// State the syscall stubs work on. It lives in a thread local so that tests running in
+/// parallel do not share a clock.
+#[derive(Default)]
+struct Runtime {
+    clock: Clock,
+    programs: HashMap<Pubkey, Entry>,
+    /// The running invocations with their accounts as of the start or the last
+    /// cross-program invocation they made.
+    call_stack: Vec<(Pubkey, Vec<PreAccount>)>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    logs: Vec<String>,
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Runtime> = RefCell::new(Runtime::default());
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        RUNTIME.with(|runtime| runtime.borrow_mut().logs.push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.sol_log(&format!("Program data: {}", fields.join(" ")));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = RUNTIME.with(|runtime| runtime.borrow().call_stack.last().unwrap().0);
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !signers.contains(info.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+        execute(&instruction.program_id, &accounts, &instruction.data)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = RUNTIME.with(|runtime| runtime.borrow().clock.clone());
+        unsafe { *(var_addr as *mut Clock) = clock };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RUNTIME.with(|runtime| runtime.borrow().return_data.clone())
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        RUNTIME.with(|runtime| {
+            let mut runtime = runtime.borrow_mut();
+            let program_id = runtime.call_stack.last().unwrap().0;
+            runtime.return_data = Some((program_id, data.to_vec()));
+        });
+    }
+}
+
+/// Runs `program_id` on already deserialized accounts and checks what it changed. As
+/// on-chain, the changes of a caller are checked when it invokes another program, and the
+/// changes of the callee are not held against the caller.
+fn execute(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let nested = RUNTIME.with(|runtime| !runtime.borrow().call_stack.is_empty());
+    if nested {
+        RUNTIME.with(|runtime| {
+            let runtime = runtime.borrow();
+            let (caller, pre_accounts) = runtime.call_stack.last().unwrap();
+            let pre_accounts: Vec<&PreAccount> = pre_accounts
+                .iter()
+                .filter(|pre| accounts.iter().any(|info| *info.key == pre.key))
+                .collect();
+            PreAccount::check(&pre_accounts, caller, accounts)
+        })?;
+        refresh_caller(accounts);
+    }
+
+    RUNTIME.with(|runtime| {
+        let frame = (*program_id, PreAccount::snapshot(accounts));
+        runtime.borrow_mut().call_stack.push(frame);
+    });
+    let result = if *program_id == system_program::ID {
+        process_system_instruction(accounts, data)
+    } else {
+        match RUNTIME.with(|runtime| runtime.borrow().programs.get(program_id).copied()) {
+            Some(entry) => {
+                // Every invocation gets its own `AccountInfo`s, like it would on-chain.
+                let accounts = accounts.to_vec();
+                entry(program_id, unsafe { extend_lifetime(&accounts) }, data)
+            }
+            None => Err(ProgramError::IncorrectProgramId),
+        }
+    };
+    let (_, pre_accounts) = RUNTIME.with(|runtime| runtime.borrow_mut().call_stack.pop().unwrap());
+    result?;
+    let pre_accounts: Vec<&PreAccount> = pre_accounts.iter().collect();
+    PreAccount::check(&pre_accounts, program_id, accounts)?;
+    PreAccount::check_balance(&pre_accounts, program_id, accounts)?;
+    if nested {
+        refresh_caller(accounts);
+    }
+    Ok(())
+}
+
+/// Takes the accounts of a cross-program invocation as they are now as the baseline of
+/// the caller.
+fn refresh_caller(accounts: &[AccountInfo]) {
+    RUNTIME.with(|runtime| {
+        let mut runtime = runtime.borrow_mut();
+        let (_, pre_accounts) = runtime.call_stack.last_mut().unwrap();
+        for pre in pre_accounts.iter_mut() {
+            if let Some(info) = accounts.iter().find(|info| *info.key == pre.key) {
+                pre.lamports = info.lamports();
+                pre.owner = *info.owner;
+                pre.data = account_data(info).to_vec();
+            }
+        }
+    });
+}
+
+/// An account as it was passed to an invocation.
+struct PreAccount {
+    key: Pubkey,
+    is_writable: bool,
+    executable: bool,
+    lamports: u64,
+    owner: Pubkey,
+    data: Vec<u8>,
+}
+
+impl PreAccount {
+    /// Records the accounts once per key, writable if any of their entries is.
+    fn snapshot(accounts: &[AccountInfo]) -> Vec<PreAccount> {
+        let mut pre_accounts: Vec<PreAccount> = vec![];
+        for info in accounts {
+            if let Some(pre) = pre_accounts.iter_mut().find(|pre| pre.key == *info.key) {
+                pre.is_writable |= info.is_writable;
+                continue;
+            }
+            pre_accounts.push(PreAccount {
+                key: *info.key,
+                is_writable: info.is_writable,
+                executable: info.executable,
+                lamports: info.lamports(),
+                owner: *info.owner,
+                data: account_data(info).to_vec(),
+            });
+        }
+        pre_accounts
+    }
+
+    /// Checks the changes `program_id` made to the accounts against the rules of the
+    /// runtime.
+    fn check(
+        pre_accounts: &[&PreAccount],
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        for pre in pre_accounts {
+            let info = accounts.iter().find(|info| *info.key == pre.key).unwrap();
+            let (lamports, owner, data) = (info.lamports(), *info.owner, account_data(info));
+            let owned = pre.owner == *program_id;
+            let (lamports_changed, data_changed) = (lamports != pre.lamports, data != pre.data);
+            let violation = if owner != pre.owner
+                && !(owned && pre.is_writable && !pre.executable && data.iter().all(|b| *b == 0))
+            {
+                Some(("modified the owner of", ProgramError::IllegalOwner))
+            } else if lamports < pre.lamports && !owned {
+                Some(("spent lamports of", ProgramError::IllegalOwner))
+            } else if data_changed && !owned {
+                Some(("modified data of", ProgramError::IllegalOwner))
+            } else if (lamports_changed || data_changed) && !pre.is_writable {
+                Some(("modified read-only", ProgramError::InvalidArgument))
+            } else if (lamports_changed || data_changed) && pre.executable {
+                Some(("modified executable", ProgramError::InvalidArgument))
+            } else {
+                None
+            };
+            if let Some((violation, error)) = violation {
+                let message = format!("Program {program_id} {violation} account {}", pre.key);
+                Stubs.sol_log(&message);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `program_id` neither created nor destroyed lamports.
+    fn check_balance(
+        pre_accounts: &[&PreAccount],
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let lamports_before: u128 = pre_accounts.iter().map(|pre| pre.lamports as u128).sum();
+        let lamports_after: u128 = pre_accounts
+            .iter()
+            .map(|pre| {
+                let info = accounts.iter().find(|info| *info.key == pre.key).unwrap();
+                info.lamports() as u128
+            })
+            .sum();
+        if lamports_before != lamports_after {
+            Stubs.sol_log(&format!("Program {program_id} unbalanced lamports"));
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+}
+
+/// The data of `info`, read even while the program calling out holds a borrow of it.
+fn account_data<'a>(info: &'a AccountInfo) -> &'a [u8] {
+    unsafe { &*info.data.as_ptr() }
+}
+
+/// `Entry` wants the slice and the accounts to share a lifetime, which the slice of a
+/// cross-program invocation does not have. The accounts outlive the call either way.
+unsafe fn extend_lifetime<'a, 'info>(accounts: &'a [AccountInfo<'info>]) -> &'a [AccountInfo<'a>] {
+    std::mem::transmute(accounts)
+}
+
+fn process_system_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let instruction: SystemInstruction =
+        bincode::deserialize(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let account = |index: usize| {
+        accounts
+            .get(index)
+            .ok_or(ProgramError::NotEnoughAccountKeys)
+    };
+    let check_unused = |info: &AccountInfo| {
+        if !info.data_is_empty() || *info.owner != system_program::ID {
+            return Err(ProgramError::Custom(
+                SystemError::AccountAlreadyInUse as u32,
+            ));
+        }
+        Ok(())
+    };
+    let check_signer = |info: &AccountInfo| {
+        if !info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    };
+    match instruction {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let (from, to) = (account(0)?, account(1)?);
+            check_signer(from)?;
+            check_signer(to)?;
+            if to.lamports() != 0 {
+                return Err(ProgramError::Custom(
+                    SystemError::AccountAlreadyInUse as u32,
+                ));
+            }
+            check_unused(to)?;
+            transfer_lamports(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+        }
+        SystemInstruction::Transfer { lamports } => {
+            let (from, to) = (account(0)?, account(1)?);
+            check_signer(from)?;
+            if !from.data_is_empty() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            transfer_lamports(from, to, lamports)?;
+        }
+        SystemInstruction::Allocate { space } => {
+            let info = account(0)?;
+            check_signer(info)?;
+            check_unused(info)?;
+            info.realloc(space as usize, true)?;
+        }
+        SystemInstruction::Assign { owner } => {
+            let info = account(0)?;
+            check_signer(info)?;
+            info.assign(&owner);
+        }
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if from.lamports() < lamports {
+        return Err(ProgramError::Custom(
+            SystemError::ResultWithNegativeLamports as u32,
+        ));
+    }
+    **from.try_borrow_mut_lamports()? -= lamports;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+/// Checks the signatures of an Ed25519 program instruction. Only signatures, keys and
+/// messages stored in the instruction itself are supported.
+fn verify_ed25519_instruction(data: &[u8]) -> ProgramResult {
+    let invalid = ProgramError::InvalidInstructionData;
+    let read_u16 = |at: usize| {
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or(ProgramError::InvalidInstructionData)
+    };
+    let count = *data.first().ok_or(ProgramError::InvalidInstructionData)? as usize;
+    for index in 0..count {
+        let start = 2 + index * 14;
+        let (signature, public_key, message, size) = (
+            read_u16(start)?,
+            read_u16(start + 4)?,
+            read_u16(start + 8)?,
+            read_u16(start + 10)?,
+        );
+        for instruction_index in [start + 2, start + 6, start + 12] {
+            if read_u16(instruction_index)? != u16::MAX as usize {
+                return Err(invalid);
+            }
+        }
+        let public_key: [u8; 32] = data
+            .get(public_key..public_key + 32)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let signature: [u8; 64] = data
+            .get(signature..signature + 64)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let message = data
+            .get(message..message + size)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        PublicKey::from_bytes(&public_key)
+            .and_then(|key| key.verify_strict(message, &Signature::from(signature)))
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+    }
+    Ok(())
+}
+
+/// Derives an Ed25519 keypair from `seed`.
+pub fn ed25519_keypair(seed: [u8; 32]) -> Keypair {
+    let secret = SecretKey::from_bytes(&seed).unwrap();
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+/// Builds an Ed25519 program instruction verifying `signer`'s signature over `message`.
+pub fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    let public_key_offset = 16;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.public.as_bytes());
+    data.extend_from_slice(&signer.sign(message).to_bytes());
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// The accounts and programs of a local cluster.
+pub struct Svm {
+    accounts: HashMap<Pubkey, Account>,
+}
+
+impl Svm {
+    /// Creates a cluster running `programs` next to the system, token, Token-2022 and
+    /// associated token programs.
+    pub fn new(programs: &[(Pubkey, Entry)]) -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+
+        let mut registry: HashMap<Pubkey, Entry> = HashMap::new();
+        registry.insert(spl_token::ID, spl_token::processor::Processor::process);
+        registry.insert(
+            spl_token_2022::ID,
+            spl_token_2022::processor::Processor::process,
+        );
+        registry.insert(
+            spl_associated_token_account::ID,
+            spl_associated_token_account::processor::process_instruction,
+        );
+        registry.extend(programs.iter().copied());
+
+        let mut accounts = HashMap::new();
+        for program_id in registry.keys().chain([&system_program::ID]) {
+            accounts.insert(
+                *program_id,
+                Account {
+                    lamports: 1,
+                    data: vec![],
+                    owner: Pubkey::default(),
+                    executable: true,
+                },
+            );
+        }
+        accounts.insert(
+            sysvar::rent::ID,
+            Account {
+                lamports: 1,
+                data: bincode::serialize(&Rent::default()).unwrap(),
+                owner: sysvar::ID,
+                executable: false,
+            },
+        );
+
+        RUNTIME.with(|runtime| {
+            *runtime.borrow_mut() = Runtime {
+                clock: Clock {
+                    unix_timestamp: GENESIS_UNIX_TIMESTAMP,
+                    ..Clock::default()
+                },
+                programs: registry,
+                ..Runtime::default()
+            }
+        });
+        Svm { accounts }
+    }
+
+    pub fn unix_timestamp(&self) -> i64 {
+        RUNTIME.with(|runtime| runtime.borrow().clock.unix_timestamp)
+    }
+
+    /// Moves the cluster clock to `unix_timestamp`.
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        RUNTIME.with(|runtime| runtime.borrow_mut().clock.unix_timestamp = unix_timestamp);
+    }
+
+    pub fn slot(&self) -> u64 {
+        RUNTIME.with(|runtime| runtime.borrow().clock.slot)
+    }
+
+    /// Moves the cluster clock to `slot`, leaving its unix timestamp as it is.
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        RUNTIME.with(|runtime| runtime.borrow_mut().clock.slot = slot);
+    }
+
+    /// Logs of the last processed transaction.
+    pub fn logs(&self) -> Vec<String> {
+        RUNTIME.with(|runtime| runtime.borrow().logs.clone())
+    }
+
+    /// Events of type `E` emitted by the last processed transaction, in order.
+    pub fn events<E: Event>(&self) -> Vec<E> {
+        self.logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("Program data: "))
+            .flat_map(|fields| fields.split(' '))
+            .filter_map(|field| STANDARD.decode(field).ok())
+            .filter(|data| data.starts_with(&E::DISCRIMINATOR))
+            .map(|data| E::deserialize(&mut &data[8..]).unwrap())
+            .collect()
+    }
+
+    /// The event of type `E` emitted by the last processed transaction, which has to be
+    /// its only one of that type.
+    pub fn event<E: Event>(&self) -> E {
+        let mut events = self.events::<E>();
+        assert_eq!(events.len(), 1, "expected a single event");
+        events.pop().unwrap()
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts.get(key)
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: Account) {
+        self.accounts.insert(key, account);
+    }
+
+    /// Makes `authority` the upgrade authority of `program_id`, owning the program by the
+    /// upgradeable loader with a program data account as a deployment does.
+    pub fn set_upgrade_authority(&mut self, program_id: &Pubkey, authority: &Pubkey) {
+        let programdata_address =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::ID).0;
+        self.accounts.insert(
+            *program_id,
+            Account {
+                lamports: 1,
+                data: bincode::serialize(&UpgradeableLoaderState::Program {
+                    programdata_address,
+                })
+                .unwrap(),
+                owner: bpf_loader_upgradeable::ID,
+                executable: true,
+            },
+        );
+        self.accounts.insert(
+            programdata_address,
+            Account {
+                lamports: 1,
+                data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                    slot: 0,
+                    upgrade_authority_address: Some(*authority),
+                })
+                .unwrap(),
+                owner: bpf_loader_upgradeable::ID,
+                executable: false,
+            },
+        );
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        let account = self.accounts.entry(*key).or_insert_with(|| Account {
+            owner: system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+    }
+
+    /// Creates a funded system account.
+    pub fn new_funded_account(&mut self) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.airdrop(&key, 10_000_000_000);
+        key
+    }
+
+    /// Processes `instructions` atomically as one transaction signed by `signers` and
+    /// returns the return data of the last instruction. As on-chain, accounts left without
+    /// lamports are removed afterwards.
+    pub fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[Pubkey],
+    ) -> Result<Vec<u8>, ProgramError> {
+        RUNTIME.with(|runtime| runtime.borrow_mut().logs.clear());
+        let snapshot = self.accounts.clone();
+        let mut return_data = vec![];
+        for (index, instruction) in instructions.iter().enumerate() {
+            self.store_instructions_sysvar(instructions, index);
+            match self.process_instruction(instruction, signers) {
+                Ok(data) => return_data = data,
+                Err(err) => {
+                    self.accounts = snapshot;
+                    return Err(err);
+                }
+            }
+        }
+        if let Some(key) = self.rent_violation(&snapshot) {
+            Stubs.sol_log(&format!(
+                "Account {key} left with insufficient funds for rent"
+            ));
+            self.accounts = snapshot;
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        self.accounts.retain(|_, account| account.lamports > 0);
+        Ok(return_data)
+    }
+
+    /// An account changed by the transaction that ended up neither empty nor rent exempt,
+    /// unless it was rent paying before and kept its size without gaining lamports. Sysvars
+    /// are written by the runtime and skipped.
+    fn rent_violation(&self, snapshot: &HashMap<Pubkey, Account>) -> Option<Pubkey> {
+        let rent = Rent::default();
+        let rent_paying = |account: &Account| {
+            account.lamports > 0 && !rent.is_exempt(account.lamports, account.data.len())
+        };
+        self.accounts.iter().find_map(|(key, account)| {
+            let pre = snapshot.get(key);
+            let allowed = account.owner == sysvar::ID
+                || pre == Some(account)
+                || !rent_paying(account)
+                || pre.is_some_and(|pre| {
+                    rent_paying(pre)
+                        && pre.data.len() == account.data.len()
+                        && account.lamports <= pre.lamports
+                });
+            (!allowed).then_some(*key)
+        })
+    }
+
+    fn store_instructions_sysvar(&mut self, instructions: &[Instruction], index: usize) {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
+        let mut data = construct_instructions_data(&borrowed);
+        store_current_index(&mut data, index as u16);
+        self.accounts.insert(
+            sysvar::instructions::ID,
+            Account {
+                lamports: 1,
+                data,
+                owner: sysvar::ID,
+                executable: false,
+            },
+        );
+    }
+
+    fn process_instruction(
+        &mut self,
+        instruction: &Instruction,
+        signers: &[Pubkey],
+    ) -> Result<Vec<u8>, ProgramError> {
+        if instruction.program_id == ed25519_program::ID {
+            verify_ed25519_instruction(&instruction.data)?;
+            return Ok(vec![]);
+        }
+        for meta in &instruction.accounts {
+            if meta.is_signer && !signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+
+        let mut input = Input::serialize(instruction, &self.accounts);
+        let (program_id, infos, data) = unsafe { entrypoint::deserialize(input.as_mut_ptr()) };
+        RUNTIME.with(|runtime| runtime.borrow_mut().return_data = None);
+        let result = execute(program_id, &infos, data);
+        drop(infos);
+        result?;
+
+        for (key, account) in input.deserialize() {
+            self.accounts.insert(key, account);
+        }
+        Ok(RUNTIME.with(|runtime| {
+            runtime
+                .borrow()
+                .return_data
+                .as_ref()
+                .map_or(vec![], |(_, data)| data.clone())
+        }))
+    }
+}
+
+/// The input of a program invocation in the layout of the BPF loader.
+struct Input {
+    buffer: Vec<u64>,
+    /// Key and offset of every serialized (non duplicate) account.
+    accounts: Vec<(Pubkey, usize)>,
+}
+
+impl Input {
+    fn serialize(instruction: &Instruction, accounts: &HashMap<Pubkey, Account>) -> Self {
+        let mut bytes = Vec::new();
+        let mut serialized: Vec<(Pubkey, usize)> = Vec::new();
+        bytes.extend_from_slice(&(instruction.accounts.len() as u64).to_le_bytes());
+        for (position, meta) in instruction.accounts.iter().enumerate() {
+            if let Some(original) = instruction.accounts[..position]
+                .iter()
+                .position(|other| other.pubkey == meta.pubkey)
+            {
+                bytes.push(original as u8);
+                bytes.extend_from_slice(&[0; 7]);
+                continue;
+            }
+            // A key listed several times is signer / writable if any of its entries is.
+            let (is_signer, is_writable) = instruction
+                .accounts
+                .iter()
+                .filter(|other| other.pubkey == meta.pubkey)
+                .fold((false, false), |(signer, writable), other| {
+                    (signer || other.is_signer, writable || other.is_writable)
+                });
+            let account = accounts.get(&meta.pubkey).cloned().unwrap_or(Account {
+                owner: system_program::ID,
+                ..Account::default()
+            });
+            bytes.push(NON_DUP_MARKER);
+            bytes.push(is_signer as u8);
+            bytes.push(is_writable as u8);
+            bytes.push(account.executable as u8);
+            bytes.extend_from_slice(&[0; 4]);
+            serialized.push((meta.pubkey, bytes.len()));
+            bytes.extend_from_slice(meta.pubkey.as_ref());
+            bytes.extend_from_slice(account.owner.as_ref());
+            bytes.extend_from_slice(&account.lamports.to_le_bytes());
+            bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&account.data);
+            bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            bytes.resize(bytes.len().next_multiple_of(8), 0);
+            bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(instruction.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&instruction.data);
+        bytes.extend_from_slice(instruction.program_id.as_ref());
+
+        // A u64 buffer keeps the input aligned like the loader does.
+        let mut buffer = vec![0u64; bytes.len().div_ceil(size_of::<u64>())];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                buffer.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            )
+        };
+        Input {
+            buffer,
+            accounts: serialized,
+        }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.buffer.as_mut_ptr() as *mut u8
+    }
+
+    /// Reads the accounts back after the program ran.
+    fn deserialize(&self) -> Vec<(Pubkey, Account)> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                self.buffer.as_ptr() as *const u8,
+                self.buffer.len() * size_of::<u64>(),
+            )
+        };
+        let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        self.accounts
+            .iter()
+            .map(|(key, offset)| {
+                let executable = bytes[offset - 5] != 0;
+                let owner = Pubkey::try_from(&bytes[offset + 32..offset + 64]).unwrap();
+                let lamports = read_u64(offset + 64);
+                let data_len = read_u64(offset + 72) as usize;
+                let data = bytes[offset + 80..offset + 80 + data_len].to_vec();
+                (
+                    *key,
+                    Account {
+                        lamports,
+                        data,
+                        owner,
+                        executable,
+                    },
+                )
+            })
+            .collect()
+    }
+}
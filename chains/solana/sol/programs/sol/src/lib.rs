@@ -1,27 +1,63 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    clock::DEFAULT_MS_PER_SLOT,
+    ed25519_program, hash, keccak,
+    sysvar::instructions::{
+        self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked,
+    },
+};
 use anchor_lang::system_program;
-use sha2::{Digest, Sha256};
-use std::mem::size_of;
 declare_id!("2XfmTmnhz8kDnryZSJKKV53tLN7DKZbrN9Q1sZbJo5bc");
+
+/// @dev Seconds after the timelock during which a redeemed / refunded HTLC account is kept
+/// around before it can be closed, converted to slots for slot timelocks.
+pub const CLOSE_GRACE_PERIOD: u64 = 24 * 60 * 60;
+/// @dev Maximum number of entries in each of the hop route vectors of a commit.
+pub const MAX_HOPS: usize = 5;
+/// @dev Maximum length in bytes of a chain, asset or address string stored in an HTLC.
+pub const MAX_STRING_LENGTH: usize = 128;
+/// @dev Lamports of the rent of a closed HTLC account paid to a keeper, any caller of
+/// `close` other than the sender; the rest of the rent goes back to the sender.
+pub const KEEPER_TIP: u64 = 10_000;
+
 /// @title Pre Hashed Timelock Contracts (PHTLCs) on Solana.
 ///
 /// This contract provides a way to create and keep PHTLCs for Solana.
+/// HTLC accounts are derived from the seeds [b"htlc", sender, Id], so an Id only has
+/// to be unique per sender and can not be taken over by anyone else.
+/// Timelocks are bounded by the config account [b"config"]: they have to lie between
+/// min_timelock_delta and max_timelock_delta seconds from now. The config is created by
+/// the upgrade authority of the program and updated by its admin, who can also pause the
+/// creation of new HTLCs; redeem and refund keep working while paused. Until the config
+/// is initialized, no HTLC can be created or locked.
+/// Timelocks are UNIX epoch seconds or, with TimelockKind::Slot, slot heights; the
+/// reward timelock of an HTLC has the kind of its timelock.
 ///
 /// Protocol:
 ///
 ///  1) commit(src_receiver, timelock, amount) - a
 ///      sender calls this to create a new HTLC
 ///      for a given amount. A [u8; 32] Id is returned.
-///  2) lock(src_receiver, hashlock, timelock, amount) - a
+///  2) lock(src_receiver, hashlock, hash_algorithm, reward, reward_timelock, timelock, amount) - a
 ///      sender calls this to create a new HTLC
-///      for a given amount. A [u8; 32] Id is returned.
-///  3) addLock(Id, hashlock, timelock) - the sender calls this function
+///      for a given amount. A [u8; 32] Id is returned. An optional reward
+///      is escrowed alongside the amount for whoever redeems the HTLC.
+///  3) addLock(Id, hashlock, hash_algorithm, timelock) - the sender calls this function
 ///      to add the hashlock to HTLC.
-///  4) redeem(Id, secret) - once the src_receiver knows the secret of
+///  4) addLockSig(Id, hashlock, hash_algorithm, timelock) - anyone calls this function with
+///      an ed25519 signature of the sender to add the hashlock to HTLC.
+///  5) extendTimelock(Id, new_timelock) - the sender calls this function to push
+///      back the timelock of an HTLC that is not yet redeemed or refunded.
+///  6) redeem(Id, secret) - once the src_receiver knows the secret of
 ///      the hashlock hash they can claim the sol with this function
-///  5) refund(Id) - after timelock has expired and if the src_receiver did not
-///      redeem the sol the sender / creator of the HTLC can get their sol
-///      back with this function.
+///      or, with redeem_to(Id, secret), send them to another destination
+///      they sign for.
+///  7) refund(Id) - after timelock has expired and if the src_receiver did not
+///      redeem the sol, anyone, the sender or a keeper cranking expired HTLCs,
+///      can call this function to give the sender / creator of the HTLC their sol back.
+///  8) close(Id) - once the HTLC is redeemed or refunded and the close grace
+///      period has passed, anyone can close the HTLC account returning its rent
+///      to the sender, minus a KEEPER_TIP for a caller other than the sender.
 #[program]
 pub mod native_htlc {
     use super::*;
@@ -35,16 +71,15 @@ pub mod native_htlc {
         let sender = &ctx.accounts.sender.to_account_info().key;
         let receiver = &ctx.accounts.receiver.to_account_info().key;
 
-        let mut hasher = Sha256::new();
-        hasher.update(ctx.program_id);
-        hasher.update(sender);
-        hasher.update(receiver);
-        hasher.update(&amount.to_be_bytes());
-        hasher.update(&timelock.to_be_bytes());
+        let commitId = hash::hashv(&[
+            ctx.program_id.as_ref(),
+            sender.as_ref(),
+            receiver.as_ref(),
+            &amount.to_be_bytes(),
+            &timelock.to_be_bytes(),
+        ]);
 
-        let commitId = hasher.finalize();
-
-        Ok(commitId.into())
+        Ok(commitId.to_bytes())
     }
 
     /// @dev Sender / Payer sets up a new pre-hash time lock contract depositing the
@@ -52,6 +87,7 @@ pub mod native_htlc {
     /// @param src_receiver src_receiver of the funds.
     /// @param timelock UNIX epoch seconds time that the lock expires at.
     ///                  Refunds can be made after this time.
+    /// @param timelock_kind whether the timelocks are UNIX epoch seconds or slots.
     /// @return Id of the new HTLC. This is needed for subsequent calls.
     pub fn commit(
         ctx: Context<Commit>,
@@ -65,15 +101,31 @@ pub mod native_htlc {
         src_asset: String,
         src_receiver: Pubkey,
         timelock: u64,
+        timelock_kind: TimelockKind,
         amount: u64,
         commit_bump: u8,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
+            timelock > timelock_kind.now()?,
             HTLCError::NotFutureTimeLock
         );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
         require!(amount != 0, HTLCError::FundsNotSent);
+        for hops in [&hopChains, &hopAssets, &hopAddresses] {
+            require!(hops.len() <= MAX_HOPS, HTLCError::TooManyHops);
+            require!(
+                hops.iter().all(|hop| hop.len() <= MAX_STRING_LENGTH),
+                HTLCError::StringTooLong
+            );
+        }
+        require!(
+            [&dst_chain, &dst_asset, &dst_address, &src_asset]
+                .iter()
+                .all(|field| field.len() <= MAX_STRING_LENGTH),
+            HTLCError::StringTooLong
+        );
         let htlc = &mut ctx.accounts.htlc;
 
         htlc.dst_address = dst_address;
@@ -82,15 +134,27 @@ pub mod native_htlc {
         htlc.src_asset = src_asset;
         htlc.sender = *ctx.accounts.sender.to_account_info().key;
         htlc.src_receiver = src_receiver;
+        htlc.hop_chains = hopChains;
+        htlc.hop_assets = hopAssets;
+        htlc.hop_addresses = hopAddresses;
         htlc.hashlock = [0u8; 32];
+        htlc.hash_algorithm = HashAlgorithm::Sha256;
         htlc.amount = amount;
         htlc.timelock = timelock;
-        htlc.redeemed = false;
-        htlc.refunded = false;
+        htlc.timelock_kind = timelock_kind;
+        htlc.reward = 0;
+        htlc.reward_timelock = 0;
+        htlc.status = HtlcStatus::Committed;
         htlc.secret = [0u8; 32];
 
+        let sender_key = ctx.accounts.sender.key();
         let bump_vector = commit_bump.to_le_bytes();
-        let inner = vec![Id.as_ref(), bump_vector.as_ref()];
+        let inner = vec![
+            b"htlc".as_ref(),
+            sender_key.as_ref(),
+            Id.as_ref(),
+            bump_vector.as_ref(),
+        ];
         let outer = vec![inner.as_slice()];
 
         let transfer_context = CpiContext::new_with_signer(
@@ -102,25 +166,45 @@ pub mod native_htlc {
             outer.as_slice(),
         );
         system_program::transfer(transfer_context, amount)?;
-        // msg!("hop chains: {:?}", hopChains);
-        // msg!("hop assets: {:?}", hopAssets);
-        // msg!("hop addresses: {:?}", hopAddresses);
 
+        emit!(TokenCommitted {
+            Id,
+            hopChains: htlc.hop_chains.clone(),
+            hopAssets: htlc.hop_assets.clone(),
+            hopAddresses: htlc.hop_addresses.clone(),
+            dst_chain: htlc.dst_chain.clone(),
+            dst_address: htlc.dst_address.clone(),
+            dst_asset: htlc.dst_asset.clone(),
+            sender: htlc.sender,
+            src_receiver: htlc.src_receiver,
+            src_asset: htlc.src_asset.clone(),
+            amount,
+            timelock,
+            timelock_kind,
+        });
         Ok(Id)
     }
 
     /// @dev Sender / Payer sets up a new hash time lock contract depositing the
     /// funds and providing the reciever and terms.
     /// @param src_receiver receiver of the funds.
-    /// @param hashlock A sha-256 hash hashlock.
+    /// @param hashlock A sha-256 or keccak-256 hash hashlock.
+    /// @param hash_algorithm The hash function of the hashlock.
+    /// @param reward The reward in lamports granted to the caller of redeem.
+    /// @param reward_timelock UNIX epoch seconds time after which the reward can be claimed.
     /// @param timelock UNIX epoch seconds time that the lock expires at.
     ///                  Refunds can be made after this time.
+    /// @param timelock_kind whether the timelocks are UNIX epoch seconds or slots.
     /// @return Id of the new HTLC. This is needed for subsequent calls.
     pub fn lock(
         ctx: Context<Lock>,
         Id: [u8; 32],
         hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        reward: u64,
+        reward_timelock: u64,
         timelock: u64,
+        timelock_kind: TimelockKind,
         amount: u64,
         dst_chain: String,
         dst_address: String,
@@ -129,12 +213,24 @@ pub mod native_htlc {
         src_receiver: Pubkey,
         lock_bump: u8,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
+        let now = timelock_kind.now()?;
+        require!(timelock > now, HTLCError::NotFutureTimeLock);
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+        require!(amount != 0, HTLCError::FundsNotSent);
+        if reward != 0 {
+            require!(
+                reward_timelock <= timelock && reward_timelock >= now,
+                HTLCError::InvalidRewardTimelock
+            );
+        }
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
-            HTLCError::NotFutureTimeLock
+            [&dst_chain, &dst_asset, &dst_address, &src_asset]
+                .iter()
+                .all(|field| field.len() <= MAX_STRING_LENGTH),
+            HTLCError::StringTooLong
         );
-        require!(amount != 0, HTLCError::FundsNotSent);
 
         let htlc = &mut ctx.accounts.htlc;
 
@@ -144,15 +240,27 @@ pub mod native_htlc {
         htlc.src_asset = src_asset;
         htlc.sender = *ctx.accounts.sender.to_account_info().key;
         htlc.src_receiver = src_receiver;
+        htlc.hop_chains = Vec::new();
+        htlc.hop_assets = Vec::new();
+        htlc.hop_addresses = Vec::new();
         htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
         htlc.secret = [0u8; 32];
         htlc.amount = amount;
         htlc.timelock = timelock;
-        htlc.redeemed = false;
-        htlc.refunded = false;
+        htlc.timelock_kind = timelock_kind;
+        htlc.reward = reward;
+        htlc.reward_timelock = reward_timelock;
+        htlc.status = HtlcStatus::Locked;
 
+        let sender_key = ctx.accounts.sender.key();
         let bump_vector = lock_bump.to_le_bytes();
-        let inner = vec![Id.as_ref(), bump_vector.as_ref()];
+        let inner = vec![
+            b"htlc".as_ref(),
+            sender_key.as_ref(),
+            Id.as_ref(),
+            bump_vector.as_ref(),
+        ];
         let outer = vec![inner.as_slice()];
         let transfer_context = CpiContext::new_with_signer(
             ctx.accounts.system_program.to_account_info(),
@@ -162,8 +270,27 @@ pub mod native_htlc {
             },
             outer.as_slice(),
         );
-        system_program::transfer(transfer_context, amount)?;
+        let total = amount
+            .checked_add(reward)
+            .ok_or(HTLCError::AmountOverflow)?;
+        system_program::transfer(transfer_context, total)?;
 
+        emit!(TokenLocked {
+            Id,
+            hashlock,
+            hash_algorithm,
+            dst_chain: htlc.dst_chain.clone(),
+            dst_address: htlc.dst_address.clone(),
+            dst_asset: htlc.dst_asset.clone(),
+            sender: htlc.sender,
+            src_receiver: htlc.src_receiver,
+            src_asset: htlc.src_asset.clone(),
+            amount,
+            reward,
+            reward_timelock,
+            timelock,
+            timelock_kind,
+        });
         Ok(Id)
     }
 
@@ -171,63 +298,389 @@ pub mod native_htlc {
     ///
     /// @param Id of the HTLC to addLock.
     /// @param hashlock of the HTLC to be locked.
+    /// @param hash_algorithm The hash function of the hashlock.
     pub fn add_lock(
         ctx: Context<AddLock>,
         Id: [u8; 32],
         hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
         timelock: u64,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
+        let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::AddLock)?;
+
+        let timelock_kind = htlc.timelock_kind;
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
+            timelock > timelock_kind.now()?,
             HTLCError::NotFutureTimeLock
         );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+
+        htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
+        htlc.timelock = timelock;
 
+        emit!(TokenLockAdded {
+            Id,
+            sender: htlc.sender,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        });
+        Ok(Id)
+    }
+
+    /// @dev Called by anyone to add hashlock to the HTLC on behalf of the sender,
+    /// using a signature of the sender over (program id, sender, Id, src_receiver,
+    /// amount, current timelock, hashlock, timelock, hash_algorithm), integers big-endian.
+    /// The transaction must contain an Ed25519 program instruction verifying that
+    /// signature right before this instruction. Binding the message to the committed
+    /// HTLC keeps it from being replayed against an HTLC the sender recreates under the
+    /// same Id after closing this one.
+    ///
+    /// @param Id of the HTLC.
+    /// @param hashlock to be added.
+    /// @param hash_algorithm The hash function of the hashlock.
+    /// @param timelock to be set.
+    pub fn add_lock_sig(
+        ctx: Context<AddLockSig>,
+        Id: [u8; 32],
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: u64,
+    ) -> Result<[u8; 32]> {
         let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::AddLock)?;
+
+        let timelock_kind = htlc.timelock_kind;
+        require!(
+            timelock > timelock_kind.now()?,
+            HTLCError::NotFutureTimeLock
+        );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+
+        let mut message = Vec::with_capacity(185);
+        message.extend_from_slice(ctx.program_id.as_ref());
+        message.extend_from_slice(htlc.sender.as_ref());
+        message.extend_from_slice(&Id);
+        message.extend_from_slice(htlc.src_receiver.as_ref());
+        message.extend_from_slice(&htlc.amount.to_be_bytes());
+        message.extend_from_slice(&htlc.timelock.to_be_bytes());
+        message.extend_from_slice(&hashlock);
+        message.extend_from_slice(&timelock.to_be_bytes());
+        message.push(hash_algorithm as u8);
+        verify_ed25519_ix(
+            &ctx.accounts.ix_sysvar.to_account_info(),
+            &htlc.sender,
+            &message,
+        )?;
+
         htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
         htlc.timelock = timelock;
 
+        emit!(TokenLockAdded {
+            Id,
+            sender: htlc.sender,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        });
         Ok(Id)
     }
 
+    /// @dev Called by the sender to push back the timelock of an HTLC that is neither
+    /// redeemed nor refunded, e.g. when the destination chain is congested. The timelock
+    /// can only be increased, only before it expires and only up to the maximum of the
+    /// config.
+    ///
+    /// @param Id of the HTLC.
+    /// @param new_timelock to be set, later than the current timelock.
+    pub fn extend_timelock(
+        ctx: Context<ExtendTimelock>,
+        Id: [u8; 32],
+        new_timelock: u64,
+    ) -> Result<bool> {
+        let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::Extend)?;
+
+        require!(
+            htlc.timelock > htlc.timelock_kind.now()?,
+            HTLCError::TimeLockExpired
+        );
+        require!(
+            new_timelock > htlc.timelock,
+            HTLCError::TimelockNotIncreased
+        );
+        require!(
+            new_timelock <= ctx.accounts.config.max_timelock(htlc.timelock_kind)?,
+            HTLCError::TimelockTooLong
+        );
+        htlc.timelock = new_timelock;
+
+        emit!(TokenTimelockExtended {
+            Id,
+            sender: htlc.sender,
+            timelock: new_timelock,
+        });
+        Ok(true)
+    }
+
     /// @dev Called by the src_receiver once they know the secret of the hashlock.
     /// This will transfer the locked funds to the HTLC's src_receiver's address.
+    /// If the HTLC carries a reward, it goes back to the sender before the reward
+    /// timelock, and afterwards to the caller (or to the src_receiver, if they
+    /// redeem themselves).
+    /// Redeeming is only possible before the timelock; from the timelock on
+    /// the HTLC can only be refunded.
     ///
     /// @param Id of the HTLC.
-    /// @param secret sha256(secret) should equal the contract hashlock.
+    /// @param secret the hash of the secret under the HTLC's hash algorithm should equal
+    /// the contract hashlock.
     pub fn redeem(ctx: Context<Redeem>, Id: [u8; 32], secret: [u8; 32]) -> Result<bool> {
-        let htlc = &mut ctx.accounts.htlc;
-        let mut hasher = Sha256::new();
-        hasher.update(secret);
-        let hash = hasher.finalize();
-        require!([0u8; 32] != htlc.hashlock, HTLCError::HashlockNotSet);
-        require!(hash == htlc.hashlock.into(), HTLCError::HashlockNoMatch);
+        redeem_htlc(
+            Id,
+            secret,
+            &mut ctx.accounts.htlc,
+            &ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.src_receiver.to_account_info(),
+            &ctx.accounts.user_signing.to_account_info(),
+        )?;
+        Ok(true)
+    }
 
-        htlc.redeemed = true;
-        htlc.secret = secret;
+    /// @dev Redeems like `redeem`, but transfers the funds of the src_receiver to another
+    /// destination it authorized: either the src_receiver signs the transaction, or the
+    /// transaction contains an Ed25519 program instruction right before this instruction
+    /// verifying a signature of the src_receiver over (program id, sender, Id, hashlock,
+    /// amount, destination), the amount big-endian. The hashlock and amount bind the
+    /// signature to this HTLC, so it can not be replayed on an HTLC recreated under the
+    /// same Id after a close.
+    ///
+    /// @param Id of the HTLC.
+    /// @param secret the hash of the secret under the HTLC's hash algorithm should equal
+    /// the contract hashlock.
+    pub fn redeem_to(ctx: Context<RedeemTo>, Id: [u8; 32], secret: [u8; 32]) -> Result<bool> {
+        let src_receiver = &ctx.accounts.src_receiver;
+        if !src_receiver.is_signer {
+            let mut message = Vec::with_capacity(128);
+            message.extend_from_slice(ctx.program_id.as_ref());
+            message.extend_from_slice(ctx.accounts.sender.key.as_ref());
+            message.extend_from_slice(&Id);
+            message.extend_from_slice(&ctx.accounts.htlc.hashlock);
+            message.extend_from_slice(&ctx.accounts.htlc.amount.to_be_bytes());
+            message.extend_from_slice(ctx.accounts.destination.key.as_ref());
+            verify_ed25519_ix(
+                &ctx.accounts.ix_sysvar.to_account_info(),
+                src_receiver.key,
+                &message,
+            )?;
+        }
 
-        let amount = htlc.amount;
+        redeem_htlc(
+            Id,
+            secret,
+            &mut ctx.accounts.htlc,
+            &ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.destination.to_account_info(),
+            &ctx.accounts.user_signing.to_account_info(),
+        )?;
+        Ok(true)
+    }
 
-        htlc.sub_lamports(amount)?;
-        ctx.accounts.src_receiver.add_lamports(amount)?;
+    /// @dev Redeems several HTLCs in one instruction, paying out like `redeem`.
+    /// For every Id the remaining accounts hold the HTLC, its sender and its src_receiver,
+    /// in this order.
+    ///
+    /// @param ids of the HTLCs.
+    /// @param secrets of the HTLCs, one for every Id.
+    /// @param bumps of the HTLC accounts, one for every Id.
+    /// @param atomic whether an HTLC that can not be redeemed fails the whole batch, or is
+    /// skipped.
+    /// @return whether each HTLC was redeemed.
+    pub fn redeem_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemBatch<'info>>,
+        ids: Vec<[u8; 32]>,
+        secrets: Vec<[u8; 32]>,
+        bumps: Vec<u8>,
+        atomic: bool,
+    ) -> Result<Vec<bool>> {
+        require!(
+            ids.len() == secrets.len()
+                && ids.len() == bumps.len()
+                && ctx.remaining_accounts.len() == 3 * ids.len(),
+            HTLCError::InvalidBatch
+        );
 
-        Ok(true)
+        let user_signing = ctx.accounts.user_signing.to_account_info();
+        let mut redeemed = Vec::with_capacity(ids.len());
+        for (((Id, secret), bump), accounts) in ids
+            .into_iter()
+            .zip(secrets)
+            .zip(bumps)
+            .zip(ctx.remaining_accounts.chunks(3))
+        {
+            match redeem_batch_item(ctx.program_id, Id, secret, bump, accounts, &user_signing) {
+                Ok(()) => redeemed.push(true),
+                Err(error) if !atomic => {
+                    msg!("HTLC {} not redeemed: {}", hex::encode(Id), error);
+                    redeemed.push(false);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(redeemed)
     }
 
-    /// @dev Called by the sender if there was no redeem AND the time lock has
-    /// expired. This will refund the contract amount.
+    /// @dev Called by anyone if there was no redeem AND the time lock has expired, so keepers
+    /// can crank refunds of expired HTLCs. This will refund the contract amount, always to
+    /// the sender.
     ///
     /// @param Id of the HTLC to refund from.
     pub fn refund(ctx: Context<Refund>, Id: [u8; 32]) -> Result<bool> {
-        let htlc = &mut ctx.accounts.htlc;
+        refund_htlc(
+            Id,
+            &mut ctx.accounts.htlc,
+            &ctx.accounts.sender.to_account_info(),
+        )?;
+        Ok(true)
+    }
 
-        htlc.refunded = true;
+    /// @dev Refunds several expired HTLCs in one instruction, paying back like `refund`.
+    /// For every Id the remaining accounts hold the HTLC and its sender, in this order.
+    /// HTLCs that can not be refunded are skipped.
+    ///
+    /// @param ids of the HTLCs.
+    /// @param bumps of the HTLC accounts, one for every Id.
+    /// @return whether each HTLC was refunded.
+    pub fn refund_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundBatch<'info>>,
+        ids: Vec<[u8; 32]>,
+        bumps: Vec<u8>,
+    ) -> Result<Vec<bool>> {
+        require!(
+            ids.len() == bumps.len() && ctx.remaining_accounts.len() == 2 * ids.len(),
+            HTLCError::InvalidBatch
+        );
 
-        let amount = htlc.amount;
+        let mut refunded = Vec::with_capacity(ids.len());
+        for ((Id, bump), accounts) in ids
+            .into_iter()
+            .zip(bumps)
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            match refund_batch_item(ctx.program_id, Id, bump, accounts) {
+                Ok(()) => refunded.push(true),
+                Err(error) => {
+                    msg!("HTLC {} not refunded: {}", hex::encode(Id), error);
+                    refunded.push(false);
+                }
+            }
+        }
+        Ok(refunded)
+    }
+
+    /// @dev Called by anyone once the HTLC is redeemed or refunded and the close grace period
+    /// after its timelock has passed. This will close the HTLC account and return its rent
+    /// to the sender, who paid for it. The secret stays available in the TokenRedeemed event.
+    /// A caller other than the sender is paid the KEEPER_TIP out of the rent.
+    ///
+    /// @param Id of the HTLC to close.
+    pub fn close(ctx: Context<Close>, Id: [u8; 32]) -> Result<bool> {
+        let keeper_tip = if ctx.accounts.user_signing.key() == ctx.accounts.sender.key() {
+            0
+        } else {
+            KEEPER_TIP
+        };
+        ctx.accounts.htlc.sub_lamports(keeper_tip)?;
+        ctx.accounts.user_signing.add_lamports(keeper_tip)?;
+
+        emit!(TokenClosed {
+            Id,
+            sender: ctx.accounts.sender.key(),
+            keeper_tip,
+        });
+        Ok(true)
+    }
 
-        htlc.sub_lamports(amount)?;
-        ctx.accounts.sender.add_lamports(amount)?;
+    /// @dev Called once by the upgrade authority of the program to create the config,
+    /// becoming its admin.
+    ///
+    /// @param min_timelock_delta Minimum number of seconds from now of a timelock.
+    /// @param max_timelock_delta Maximum number of seconds from now of a timelock.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.authority.key();
+        config.set_timelock_bounds(min_timelock_delta, max_timelock_delta)?;
 
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            min_timelock_delta,
+            max_timelock_delta,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to change the timelock bounds of the config. HTLCs
+    /// created before keep their timelock.
+    ///
+    /// @param min_timelock_delta Minimum number of seconds from now of a timelock.
+    /// @param max_timelock_delta Maximum number of seconds from now of a timelock.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        config.set_timelock_bounds(min_timelock_delta, max_timelock_delta)?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            min_timelock_delta,
+            max_timelock_delta,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to stop new HTLCs: commit, lock, add_lock and add_lock_sig
+    /// fail until unpause is called. Redeem, refund and close keep working, so existing
+    /// HTLCs can still be settled.
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<bool> {
+        ctx.accounts.config.paused = true;
+        emit!(Paused {
+            admin: ctx.accounts.admin.key(),
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to allow new HTLCs again after pause.
+    pub fn unpause(ctx: Context<UpdateConfig>) -> Result<bool> {
+        ctx.accounts.config.paused = false;
+        emit!(Unpaused {
+            admin: ctx.accounts.admin.key(),
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to hand the config over to a new admin.
+    ///
+    /// @param new_admin the account becoming the admin.
+    pub fn transfer_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin,
+        });
         Ok(true)
     }
 
@@ -241,24 +694,338 @@ pub mod native_htlc {
             dst_chain: htlc.dst_chain.clone(),
             dst_asset: htlc.dst_asset.clone(),
             src_asset: htlc.src_asset.clone(),
+            hop_chains: htlc.hop_chains.clone(),
+            hop_assets: htlc.hop_assets.clone(),
+            hop_addresses: htlc.hop_addresses.clone(),
             sender: htlc.sender,
             src_receiver: htlc.src_receiver,
             hashlock: htlc.hashlock,
+            hash_algorithm: htlc.hash_algorithm,
             secret: htlc.secret.clone(),
             amount: htlc.amount,
             timelock: htlc.timelock,
-            redeemed: htlc.redeemed,
-            refunded: htlc.refunded,
+            timelock_kind: htlc.timelock_kind,
+            reward: htlc.reward,
+            reward_timelock: htlc.reward_timelock,
+            status: htlc.status,
+        })
+    }
+
+    /// @dev Get HTLC reward details.
+    /// @param Id of the HTLC.
+    pub fn getRewardDetails(ctx: Context<GetDetails>, Id: [u8; 32]) -> Result<Reward> {
+        let htlc = &ctx.accounts.htlc;
+        Ok(Reward {
+            amount: htlc.reward,
+            timelock: htlc.reward_timelock,
         })
     }
 }
 
+/// @dev A small utility function that redeems an HTLC: it checks the secret and the timelock,
+/// marks the HTLC redeemed and pays out its amount and reward.
+///
+/// * `Id` - the Id of the HTLC
+/// * `secret` - the secret of the hashlock
+/// * `htlc` - the HTLC account, checked to belong to `sender` and `src_receiver`
+/// * `sender` - the sender of the HTLC, who gets the reward before the reward timelock
+/// * `destination` - the src_receiver of the HTLC, or the destination it authorized
+/// * `user_signing` - the caller, who gets the reward from the reward timelock on
+fn redeem_htlc<'info>(
+    Id: [u8; 32],
+    secret: [u8; 32],
+    htlc: &mut Account<'info, HTLC>,
+    sender: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    user_signing: &AccountInfo<'info>,
+) -> Result<()> {
+    htlc.transition(HtlcAction::Redeem)?;
+
+    let now = htlc.timelock_kind.now()?;
+    require!(now < htlc.timelock, HTLCError::TimeLockExpired);
+    require!(
+        htlc.hash_algorithm.hash(&secret) == htlc.hashlock,
+        HTLCError::HashlockNoMatch
+    );
+
+    htlc.secret = secret;
+
+    let amount = htlc.amount;
+    let reward = htlc.reward;
+    let total = amount
+        .checked_add(reward)
+        .ok_or(HTLCError::AmountOverflow)?;
+
+    htlc.sub_lamports(total)?;
+    if reward == 0 {
+        destination.add_lamports(amount)?;
+    } else if htlc.reward_timelock > now {
+        destination.add_lamports(amount)?;
+        sender.add_lamports(reward)?;
+    } else if user_signing.key() == htlc.src_receiver {
+        destination.add_lamports(total)?;
+    } else {
+        destination.add_lamports(amount)?;
+        user_signing.add_lamports(reward)?;
+    }
+
+    emit!(TokenRedeemed {
+        Id,
+        sender: htlc.sender,
+        redeem_address: user_signing.key(),
+        destination: destination.key(),
+        secret,
+        hashlock: htlc.hashlock,
+    });
+    Ok(())
+}
+
+/// @dev A small utility function that redeems one HTLC of `redeem_batch`, checking the
+/// accounts the `Redeem` context would check.
+///
+/// * `program_id` - the id of this program
+/// * `Id` - the Id of the HTLC
+/// * `secret` - the secret of the hashlock
+/// * `bump` - the bump of the HTLC account
+/// * `accounts` - the HTLC, its sender and its src_receiver
+/// * `user_signing` - the caller
+fn redeem_batch_item<'info>(
+    program_id: &Pubkey,
+    Id: [u8; 32],
+    secret: [u8; 32],
+    bump: u8,
+    accounts: &'info [AccountInfo<'info>],
+    user_signing: &AccountInfo<'info>,
+) -> Result<()> {
+    let [htlc, sender, src_receiver] = accounts else {
+        return err!(HTLCError::InvalidBatch);
+    };
+    let mut htlc = Account::<HTLC>::try_from(htlc)?;
+    check_htlc_address(program_id, &htlc.key(), sender.key, &Id, bump)?;
+    require_keys_eq!(htlc.sender, sender.key(), HTLCError::NotSender);
+    require_keys_eq!(
+        htlc.src_receiver,
+        src_receiver.key(),
+        HTLCError::NotReciever
+    );
+    redeem_htlc(Id, secret, &mut htlc, sender, src_receiver, user_signing)?;
+    htlc.exit(program_id)
+}
+
+/// @dev A small utility function that refunds an HTLC: it checks the timelock, marks the
+/// HTLC refunded and pays its amount and reward back to the sender.
+///
+/// * `Id` - the Id of the HTLC
+/// * `htlc` - the HTLC account, checked to belong to `sender`
+/// * `sender` - the sender of the HTLC
+fn refund_htlc<'info>(
+    Id: [u8; 32],
+    htlc: &mut Account<'info, HTLC>,
+    sender: &AccountInfo<'info>,
+) -> Result<()> {
+    htlc.transition(HtlcAction::Refund)?;
+
+    require!(
+        htlc.timelock_kind.now()? >= htlc.timelock,
+        HTLCError::NotPastTimeLock
+    );
+
+    let amount = htlc
+        .amount
+        .checked_add(htlc.reward)
+        .ok_or(HTLCError::AmountOverflow)?;
+
+    htlc.sub_lamports(amount)?;
+    sender.add_lamports(amount)?;
+
+    emit!(TokenRefunded {
+        Id,
+        sender: htlc.sender,
+    });
+    Ok(())
+}
+
+/// @dev A small utility function that refunds one HTLC of `refund_batch`, checking the
+/// accounts the `Refund` context would check.
+///
+/// * `program_id` - the id of this program
+/// * `Id` - the Id of the HTLC
+/// * `bump` - the bump of the HTLC account
+/// * `accounts` - the HTLC and its sender
+fn refund_batch_item<'info>(
+    program_id: &Pubkey,
+    Id: [u8; 32],
+    bump: u8,
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let [htlc, sender] = accounts else {
+        return err!(HTLCError::InvalidBatch);
+    };
+    let mut htlc = Account::<HTLC>::try_from(htlc)?;
+    check_htlc_address(program_id, &htlc.key(), sender.key, &Id, bump)?;
+    require_keys_eq!(htlc.sender, sender.key(), HTLCError::NotSender);
+    refund_htlc(Id, &mut htlc, sender)?;
+    htlc.exit(program_id)
+}
+
+/// @dev A small utility function that checks the HTLC account of a batch item against its
+/// seeds and the bump passed by the caller, which is cheaper than searching for the bump.
+///
+/// * `program_id` - the id of this program
+/// * `htlc` - the address of the HTLC account
+/// * `sender` - the sender of the HTLC
+/// * `Id` - the Id of the HTLC
+/// * `bump` - the bump of the HTLC account
+fn check_htlc_address(
+    program_id: &Pubkey,
+    htlc: &Pubkey,
+    sender: &Pubkey,
+    Id: &[u8; 32],
+    bump: u8,
+) -> Result<()> {
+    let address = Pubkey::create_program_address(
+        &[b"htlc".as_ref(), sender.as_ref(), Id.as_ref(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+    require_keys_eq!(*htlc, address, ErrorCode::ConstraintSeeds);
+    Ok(())
+}
+
+/// @dev A small utility function that checks the Ed25519 signature verification instruction
+/// placed right before the current instruction.
+///
+/// * `ix_sysvar` - the instructions sysvar account
+/// * `signer` - the public key that must have produced the signature
+/// * `message` - the message that must have been signed
+fn verify_ed25519_ix(ix_sysvar: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = load_current_index_checked(ix_sysvar)?;
+    require!(current_index > 0, HTLCError::InvalidSignature);
+    let ix = load_instruction_at_checked((current_index - 1) as usize, ix_sysvar)?;
+    require!(
+        ix.program_id == ed25519_program::ID && ix.accounts.is_empty(),
+        HTLCError::InvalidSignature
+    );
+
+    // Layout: [num_signatures: u8, padding: u8, offsets: 7 x u16] followed by the data.
+    let data = &ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        HTLCError::InvalidSignature
+    );
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix_index = read_u16(14);
+
+    // Everything must live in the Ed25519 instruction itself, otherwise the verified
+    // data could be taken from an unrelated instruction.
+    require!(
+        signature_ix_index == u16::MAX
+            && public_key_ix_index == u16::MAX
+            && message_ix_index == u16::MAX,
+        HTLCError::InvalidSignature
+    );
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(HTLCError::InvalidSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(HTLCError::InvalidSignature)?;
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        HTLCError::InvalidSignature
+    );
+
+    Ok(())
+}
+
 #[account]
 #[derive(Default)]
 pub struct IdStruct {
     pub id: [u8; 32],
 }
 
+/// @dev Hash function a hashlock is computed with. SHA-256 is the default; Keccak-256
+/// pairs an HTLC with counterparty chains using keccak256 hashlocks, such as EVM chains.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    /// @dev Hashes the secret of an HTLC.
+    pub fn hash(&self, secret: &[u8; 32]) -> [u8; 32] {
+        match self {
+            HashAlgorithm::Sha256 => hash::hash(secret).to_bytes(),
+            HashAlgorithm::Keccak256 => keccak::hash(secret).to_bytes(),
+        }
+    }
+}
+
+/// @dev Unit of the timelock and reward timelock of an HTLC: UNIX epoch seconds compared
+/// against the unix_timestamp of the clock, or a slot height compared against its slot,
+/// which does not drift with the stake-weighted timestamp.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimelockKind {
+    #[default]
+    Timestamp,
+    Slot,
+}
+
+impl TimelockKind {
+    /// @dev The current time in the unit of the kind.
+    pub fn now(&self) -> Result<u64> {
+        let clock = Clock::get()?;
+        Ok(match self {
+            TimelockKind::Timestamp => clock.unix_timestamp.try_into().unwrap(),
+            TimelockKind::Slot => clock.slot,
+        })
+    }
+
+    /// @dev Converts a number of seconds into the unit of the kind, counting
+    /// DEFAULT_MS_PER_SLOT milliseconds per slot.
+    pub fn duration(&self, seconds: u64) -> u64 {
+        match self {
+            TimelockKind::Timestamp => seconds,
+            TimelockKind::Slot => seconds.saturating_mul(1000) / DEFAULT_MS_PER_SLOT,
+        }
+    }
+}
+
+/// @dev Lifecycle of an HTLC. A commit starts out `Committed` and becomes `Locked` once its
+/// hashlock is added, a lock starts out `Locked`. Both end up `Redeemed` or `Refunded`; the
+/// actions allowed in each status are defined by `HTLC::transition`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HtlcStatus {
+    #[default]
+    Committed,
+    Locked,
+    Redeemed,
+    Refunded,
+}
+
+impl HtlcStatus {
+    /// @dev Whether the funds of the HTLC were paid out.
+    pub fn is_settled(&self) -> bool {
+        matches!(self, HtlcStatus::Redeemed | HtlcStatus::Refunded)
+    }
+}
+
+/// @dev An instruction acting on an HTLC, checked against its status by `HTLC::transition`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HtlcAction {
+    AddLock,
+    Extend,
+    Redeem,
+    Refund,
+}
+
 #[account]
 #[derive(Default)]
 pub struct HTLC {
@@ -266,14 +1033,127 @@ pub struct HTLC {
     pub dst_chain: String,
     pub dst_asset: String,
     pub src_asset: String,
+    pub hop_chains: Vec<String>,
+    pub hop_assets: Vec<String>,
+    pub hop_addresses: Vec<String>,
     pub sender: Pubkey,
     pub src_receiver: Pubkey,
     pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
     pub secret: [u8; 32],
     pub amount: u64,
     pub timelock: u64,
-    pub redeemed: bool,
-    pub refunded: bool,
+    pub timelock_kind: TimelockKind,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub status: HtlcStatus,
+}
+
+impl HTLC {
+    /// @dev Moves the HTLC to the status following `action`, failing if the action is not
+    /// allowed in its current status. Extending the timelock keeps the status.
+    pub fn transition(&mut self, action: HtlcAction) -> Result<()> {
+        self.status = match (self.status, action) {
+            (HtlcStatus::Redeemed, _) => return err!(HTLCError::AlreadyRedeemed),
+            (HtlcStatus::Refunded, _) => return err!(HTLCError::AlreadyRefunded),
+            (status, HtlcAction::Extend) => status,
+            (HtlcStatus::Committed, HtlcAction::AddLock) => HtlcStatus::Locked,
+            (HtlcStatus::Committed, HtlcAction::Redeem) => return err!(HTLCError::HashlockNotSet),
+            (HtlcStatus::Locked, HtlcAction::AddLock) => {
+                return err!(HTLCError::HashlockAlreadySet)
+            }
+            (HtlcStatus::Locked, HtlcAction::Redeem) => HtlcStatus::Redeemed,
+            (_, HtlcAction::Refund) => HtlcStatus::Refunded,
+        };
+        Ok(())
+    }
+
+    /// @dev Whether the close grace period after the timelock has passed.
+    pub fn is_past_grace_period(&self) -> Result<bool> {
+        let grace_period = self.timelock_kind.duration(CLOSE_GRACE_PERIOD);
+        Ok(self.timelock_kind.now()? >= self.timelock.saturating_add(grace_period))
+    }
+
+    /// @dev Space taken by the fixed size fields of an HTLC.
+    const FIXED_SPACE: usize = 32 + 32 + 32 + 1 + 32 + 8 + 8 + 1 + 8 + 8 + 1;
+
+    /// @dev Space of an HTLC account holding the given strings: the 8 byte discriminator,
+    /// the fixed size fields and the 4 byte length prefix of every String and Vec.
+    pub fn space(
+        dst_address: &str,
+        dst_chain: &str,
+        dst_asset: &str,
+        src_asset: &str,
+        hop_chains: &[String],
+        hop_assets: &[String],
+        hop_addresses: &[String],
+    ) -> usize {
+        let fields: usize = [dst_address, dst_chain, dst_asset, src_asset]
+            .iter()
+            .map(|field| 4 + field.len())
+            .sum();
+        let hops: usize = hop_chains
+            .iter()
+            .chain(hop_assets)
+            .chain(hop_addresses)
+            .map(|hop| 4 + hop.len())
+            .sum();
+        8 + fields + 3 * 4 + hops + HTLC::FIXED_SPACE
+    }
+}
+
+/// @dev Protocol-wide settings, stored in the account [b"config"].
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub min_timelock_delta: u64,
+    pub max_timelock_delta: u64,
+    pub paused: bool,
+}
+
+impl Config {
+    /// @dev Sets the timelock bounds, failing if the minimum exceeds the maximum.
+    fn set_timelock_bounds(
+        &mut self,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<()> {
+        require!(
+            min_timelock_delta <= max_timelock_delta,
+            HTLCError::InvalidTimelockBounds
+        );
+        self.min_timelock_delta = min_timelock_delta;
+        self.max_timelock_delta = max_timelock_delta;
+        Ok(())
+    }
+
+    /// @dev Checks that `timelock` of the given kind lies between the minimum and the
+    /// maximum delta from now, both converted to the unit of the kind.
+    pub fn check_timelock(&self, timelock_kind: TimelockKind, timelock: u64) -> Result<()> {
+        let min_timelock = timelock_kind
+            .now()?
+            .saturating_add(timelock_kind.duration(self.min_timelock_delta));
+        require!(timelock >= min_timelock, HTLCError::TimelockTooShort);
+        require!(
+            timelock <= self.max_timelock(timelock_kind)?,
+            HTLCError::TimelockTooLong
+        );
+        Ok(())
+    }
+
+    /// @dev The latest timelock of the given kind accepted now, the maximum delta from now.
+    pub fn max_timelock(&self, timelock_kind: TimelockKind) -> Result<u64> {
+        Ok(timelock_kind
+            .now()?
+            .saturating_add(timelock_kind.duration(self.max_timelock_delta)))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct Reward {
+    pub amount: u64,
+    pub timelock: u64,
 }
 #[derive(Accounts)]
 pub struct GetCommitId<'info> {
@@ -284,7 +1164,16 @@ pub struct GetCommitId<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(Id: [u8; 32], commit_bump: u8)]
+#[instruction(
+    Id: [u8; 32],
+    hopChains: Vec<String>,
+    hopAssets: Vec<String>,
+    hopAddresses: Vec<String>,
+    dst_chain: String,
+    dst_asset: String,
+    dst_address: String,
+    src_asset: String,
+)]
 pub struct Commit<'info> {
     #[account(mut)]
     pub sender: Signer<'info>,
@@ -292,20 +1181,37 @@ pub struct Commit<'info> {
     #[account(
         init,
         payer = sender,
-        space = size_of::<HTLC>() + 28,
+        space = HTLC::space(&dst_address, &dst_chain, &dst_asset, &src_asset, &hopChains, &hopAssets, &hopAddresses),
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
 
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(Id: [u8; 32], lock_bump: u8)]
+#[instruction(
+    Id: [u8; 32],
+    hashlock: [u8; 32],
+    hash_algorithm: HashAlgorithm,
+    reward: u64,
+    reward_timelock: u64,
+    timelock: u64,
+    timelock_kind: TimelockKind,
+    amount: u64,
+    dst_chain: String,
+    dst_address: String,
+    dst_asset: String,
+    src_asset: String,
+)]
 pub struct Lock<'info> {
     #[account(mut)]
     pub sender: Signer<'info>,
@@ -313,13 +1219,17 @@ pub struct Lock<'info> {
     #[account(
         init,
         payer = sender,
-        space = size_of::<HTLC>() + 28,
+        space = HTLC::space(&dst_address, &dst_chain, &dst_asset, &src_asset, &[], &[], &[]),
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
 
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
@@ -334,15 +1244,19 @@ pub struct Redeem<'info> {
     #[account(
         mut,
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
+        has_one = sender @HTLCError::NotSender,
         has_one = src_receiver @HTLCError::NotReciever,
-        constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-        constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
 
+    ///CHECK: The sender
+    #[account(mut)]
+    sender: UncheckedAccount<'info>,
     ///CHECK: The reciever
     #[account(mut)]
     pub src_receiver: UncheckedAccount<'info>,
@@ -351,6 +1265,49 @@ pub struct Redeem<'info> {
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(Id: [u8; 32])]
+pub struct RedeemTo<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
+            Id.as_ref()
+        ],
+        bump,
+        has_one = sender @HTLCError::NotSender,
+        has_one = src_receiver @HTLCError::NotReciever,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+
+    ///CHECK: The sender
+    #[account(mut)]
+    sender: UncheckedAccount<'info>,
+    ///CHECK: The reciever, signing or authorizing the destination by an ed25519 signature
+    pub src_receiver: UncheckedAccount<'info>,
+    ///CHECK: The destination authorized by the reciever
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+
+    ///CHECK: The instructions sysvar
+    #[account(address = sysvar_instructions::ID)]
+    ix_sysvar: UncheckedAccount<'info>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemBatch<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8; 32])]
 pub struct Refund<'info> {
@@ -359,13 +1316,12 @@ pub struct Refund<'info> {
 
     #[account(mut,
     seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
         Id.as_ref()
     ],
     bump,
     has_one = sender @HTLCError::NotSender,
-    constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
-    constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-    constraint = Clock::get().unwrap().unix_timestamp >= htlc.timelock.try_into().unwrap() @ HTLCError::NotPastTimeLock,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
 
@@ -377,6 +1333,14 @@ pub struct Refund<'info> {
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct RefundBatch<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8; 32])]
 pub struct AddLock<'info> {
@@ -385,25 +1349,135 @@ pub struct AddLock<'info> {
     payer: Signer<'info>,
     #[account(mut,
     seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
         Id.as_ref()
     ],
     bump,
-    constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-    constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
     constraint = htlc.sender == sender.key() @ HTLCError::UnauthorizedAccess,
-    constraint = htlc.hashlock == [0u8;32] @ HTLCError::HashlockAlreadySet,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
 
     system_program: Program<'info, System>,
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(Id: [u8; 32])]
+pub struct AddLockSig<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    ///CHECK: The sender
+    sender: UncheckedAccount<'info>,
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
+
+    ///CHECK: The instructions sysvar
+    #[account(address = sysvar_instructions::ID)]
+    ix_sysvar: UncheckedAccount<'info>,
+
+    system_program: Program<'info, System>,
+    rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(Id: [u8; 32])]
+pub struct ExtendTimelock<'info> {
+    sender: Signer<'info>,
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump)]
+    pub config: Box<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(Id: [u8; 32])]
+pub struct Close<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    constraint = htlc.status.is_settled() @ HTLCError::NotClaimed,
+    constraint = htlc.is_past_grace_period()? @ HTLCError::NotPastGracePeriod,
+    close = sender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+
+    ///CHECK: The sender
+    #[account(mut)]
+    sender: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config".as_ref()],
+        bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ HTLCError::UnauthorizedAccess)]
+    program: Program<'info, crate::program::NativeHtlc>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ HTLCError::UnauthorizedAccess)]
+    program_data: Account<'info, ProgramData>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    admin: Signer<'info>,
+
+    #[account(mut,
+    seeds = [b"config".as_ref()],
+    bump,
+    has_one = admin @HTLCError::UnauthorizedAccess,
+    )]
+    pub config: Box<Account<'info, Config>>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8; 32])]
 pub struct GetDetails<'info> {
+    ///CHECK: The sender
+    sender: UncheckedAccount<'info>,
     #[account(
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
@@ -411,6 +1485,116 @@ pub struct GetDetails<'info> {
     pub htlc: Box<Account<'info, HTLC>>,
 }
 
+/// @dev Emitted when an HTLC is created and sol is committed.
+#[event]
+pub struct TokenCommitted {
+    pub Id: [u8; 32],
+    pub hopChains: Vec<String>,
+    pub hopAssets: Vec<String>,
+    pub hopAddresses: Vec<String>,
+    pub dst_chain: String,
+    pub dst_address: String,
+    pub dst_asset: String,
+    pub sender: Pubkey,
+    pub src_receiver: Pubkey,
+    pub src_asset: String,
+    pub amount: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+}
+
+/// @dev Emitted when an HTLC is locked with a hashlock and timelock.
+#[event]
+pub struct TokenLocked {
+    pub Id: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub dst_chain: String,
+    pub dst_address: String,
+    pub dst_asset: String,
+    pub sender: Pubkey,
+    pub src_receiver: Pubkey,
+    pub src_asset: String,
+    pub amount: u64,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+}
+
+/// @dev Emitted when a hashlock and timelock are added to an existing HTLC.
+#[event]
+pub struct TokenLockAdded {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub timelock: u64,
+}
+
+/// @dev Emitted when the sender extends the timelock of an HTLC, with the new timelock.
+#[event]
+pub struct TokenTimelockExtended {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub timelock: u64,
+}
+
+/// @dev Emitted when funds are redeemed from an HTLC using the correct secret, recording
+/// the account the funds went to.
+#[event]
+pub struct TokenRedeemed {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub redeem_address: Pubkey,
+    pub destination: Pubkey,
+    pub secret: [u8; 32],
+    pub hashlock: [u8; 32],
+}
+
+/// @dev Emitted when funds are refunded from an HTLC after the timelock expired.
+#[event]
+pub struct TokenRefunded {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+}
+
+/// @dev Emitted when a redeemed or refunded HTLC account is closed, with the part of its
+/// rent paid to the keeper closing it.
+#[event]
+pub struct TokenClosed {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub keeper_tip: u64,
+}
+
+/// @dev Emitted when the config is created or its timelock bounds are updated.
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub min_timelock_delta: u64,
+    pub max_timelock_delta: u64,
+}
+
+/// @dev Emitted when the admin pauses the creation of new HTLCs.
+#[event]
+pub struct Paused {
+    pub admin: Pubkey,
+}
+
+/// @dev Emitted when the admin allows new HTLCs again.
+#[event]
+pub struct Unpaused {
+    pub admin: Pubkey,
+}
+
+/// @dev Emitted when the admin of the config is handed over.
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
 #[error_code]
 pub enum HTLCError {
     #[msg("Not Future TimeLock.")]
@@ -437,4 +1621,32 @@ pub enum HTLCError {
     NotSender,
     #[msg("Not The Reciever.")]
     NotReciever,
+    #[msg("Invalid Signature.")]
+    InvalidSignature,
+    #[msg("Invalid Reward TimeLock.")]
+    InvalidRewardTimelock,
+    #[msg("Funds Are Not Redeemed Or Refunded.")]
+    NotClaimed,
+    #[msg("Not Past Close Grace Period.")]
+    NotPastGracePeriod,
+    #[msg("Too Many Hops.")]
+    TooManyHops,
+    #[msg("String Too Long.")]
+    StringTooLong,
+    #[msg("TimeLock Expired.")]
+    TimeLockExpired,
+    #[msg("Invalid Batch.")]
+    InvalidBatch,
+    #[msg("TimeLock Not Increased.")]
+    TimelockNotIncreased,
+    #[msg("Invalid TimeLock Bounds.")]
+    InvalidTimelockBounds,
+    #[msg("TimeLock Too Short.")]
+    TimelockTooShort,
+    #[msg("TimeLock Too Long.")]
+    TimelockTooLong,
+    #[msg("Program Is Paused.")]
+    Paused,
+    #[msg("Amount Overflow.")]
+    AmountOverflow,
 }
@@ -0,0 +1,125 @@
+import assert from "assert";
+import * as fs from "fs";
+import * as anchor from "@coral-xyz/anchor";
+import { randomBytes, createHash } from "crypto";
+import { keccak_256 } from "@noble/hashes/sha3";
+import { NativeHtlc } from '../target/types/native_htlc';
+import { initializeConfig } from './config';
+
+// Records the compute units every instruction of the program consumes on the validator
+// and writes them to target/compute-units/native_htlc.json, so changes in consumption
+// show up between runs.
+describe("HTLC compute units", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const program = anchor.workspace.NativeHtlc as anchor.Program<NativeHtlc>;
+  const wallet = provider.wallet as anchor.Wallet;
+  const connection = provider.connection;
+  const SECRET = randomBytes(32);
+  const SECRETArray: number[] = Array.from(SECRET);
+  const SHA256_HASHLOCK: number[] = Array.from(createHash("sha256").update(SECRET).digest());
+  const KECCAK256_HASHLOCK: number[] = Array.from(keccak_256(SECRET));
+  const AMOUNT = new anchor.BN(0.01 * anchor.web3.LAMPORTS_PER_SOL);
+  const ZERO = new anchor.BN(0);
+  const DSTCHAIN = "ETHEREUM_SEPOLIA";
+  const DSTASSET = "ETH";
+  const SRCASSET = "SOL";
+  const DSTADDRESS = "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3a934279011e2f2b923d3a782";
+  const consumed: Record<string, number> = {};
+
+  const getHTLC = (id: Buffer): [anchor.web3.PublicKey, number] =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("htlc"), wallet.publicKey.toBuffer(), id],
+      program.programId
+    );
+
+  // Reads the units the program consumed from the logs of its instruction.
+  const unitsFromLogs = (logs: string[]): number => {
+    const pattern = new RegExp(`^Program ${program.programId.toBase58()} consumed (\\d+) of`);
+    const units = logs.map((log) => log.match(pattern)).find((match) => match !== null);
+    assert.ok(units, "the program did not log its compute units");
+    return Number(units[1]);
+  };
+
+  const measure = async (name: string, method: any): Promise<void> => {
+    const signature = await method.signers([wallet.payer]).rpc({ commitment: "confirmed" });
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    consumed[name] = unitsFromLogs(tx.meta.logMessages);
+  };
+
+  const measureView = async (name: string, method: any): Promise<void> => {
+    const simulation = await method.simulate();
+    consumed[name] = unitsFromLogs(simulation.raw);
+  };
+
+  const lock = (id: Buffer, hashlock: number[], hashAlgorithm: object, timelock: anchor.BN) => {
+    const [htlc, htlcBump] = getHTLC(id);
+    return program.methods
+      .lock(Array.from(id), hashlock, hashAlgorithm, ZERO, ZERO, timelock, { timestamp: {} }, AMOUNT, DSTCHAIN, DSTADDRESS, DSTASSET, SRCASSET, wallet.publicKey, htlcBump)
+      .accountsPartial({ sender: wallet.publicKey, htlc });
+  };
+
+  const redeem = (id: Buffer) =>
+    program.methods.redeem(Array.from(id), SECRETArray).accountsPartial({
+      userSigning: wallet.publicKey,
+      htlc: getHTLC(id)[0],
+      sender: wallet.publicKey,
+      srcReceiver: wallet.publicKey,
+    });
+
+  const waitForClock = async (timestamp: number): Promise<void> => {
+    while ((await connection.getBlockTime(await connection.getSlot())) < timestamp) {
+      await new Promise((resolve) => setTimeout(resolve, 400));
+    }
+  };
+
+  before(async () => {
+    await initializeConfig(program);
+  });
+
+  after(() => {
+    console.table(consumed);
+    fs.mkdirSync("target/compute-units", { recursive: true });
+    fs.writeFileSync("target/compute-units/native_htlc.json", JSON.stringify(consumed, null, 2) + "\n");
+  });
+
+  it("measures every instruction", async () => {
+    const now = await connection.getBlockTime(await connection.getSlot());
+    const TIMELOCK = new anchor.BN(now + 600);
+
+    await measureView("get_commit_id", program.methods.getCommitId(AMOUNT, TIMELOCK).accountsPartial({
+      sender: wallet.publicKey,
+      receiver: wallet.publicKey,
+    }));
+
+    const commitId = randomBytes(32);
+    const [commitHtlc, commitBump] = getHTLC(commitId);
+    await measure("commit", program.methods
+      .commit(Array.from(commitId), [DSTCHAIN], [DSTASSET], [DSTADDRESS], DSTCHAIN, DSTASSET, DSTADDRESS, SRCASSET, wallet.publicKey, TIMELOCK, { timestamp: {} }, AMOUNT, commitBump)
+      .accountsPartial({ sender: wallet.publicKey, htlc: commitHtlc }));
+    await measure("add_lock", program.methods
+      .addLock(Array.from(commitId), SHA256_HASHLOCK, { sha256: {} }, TIMELOCK)
+      .accountsPartial({ sender: wallet.publicKey, payer: wallet.publicKey, htlc: commitHtlc }));
+    await measureView("getDetails", program.methods
+      .getDetails(Array.from(commitId))
+      .accountsPartial({ sender: wallet.publicKey, htlc: commitHtlc }));
+    await measure("redeem (sha256)", redeem(commitId));
+
+    const keccakId = randomBytes(32);
+    await measure("lock", lock(keccakId, KECCAK256_HASHLOCK, { keccak256: {} }, TIMELOCK));
+    await measure("redeem (keccak256)", redeem(keccakId));
+
+    const refundId = randomBytes(32);
+    const REFUND_TIME = (await connection.getBlockTime(await connection.getSlot())) + 3;
+    await lock(refundId, SHA256_HASHLOCK, { sha256: {} }, new anchor.BN(REFUND_TIME)).signers([wallet.payer]).rpc();
+    await waitForClock(REFUND_TIME);
+    await measure("refund", program.methods.refund(Array.from(refundId)).accountsPartial({
+      userSigning: wallet.publicKey,
+      htlc: getHTLC(refundId)[0],
+      sender: wallet.publicKey,
+    }));
+  });
+});
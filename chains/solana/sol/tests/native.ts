@@ -5,6 +5,7 @@ import { Keypair, PublicKey } from "@solana/web3.js";
 import { randomBytes, createHash } from "crypto";
 import bs58 from 'bs58';
 import { NativeHtlc } from '../target/types/native_htlc';
+import { initializeConfig } from './config';
 interface HTLCParameters {
   htlc: anchor.web3.PublicKey;
   htlcBump: number;
@@ -43,10 +44,11 @@ describe("HTLC", () => {
   let pda: HTLCParameters;
 
   const getHTLC = async (
+    sender: PublicKey,
     id: Buffer,
   ): Promise<HTLCParameters> => {
     let [htlc, htlcBump] = await anchor.web3.PublicKey.findProgramAddressSync(
-      [id],
+      [Buffer.from("htlc"), sender.toBuffer(), id],
       program.programId
     );
     console.log(`[${htlc}] derived htlc`);
@@ -77,11 +79,20 @@ describe("HTLC", () => {
     return new Promise((resolve) => setTimeout(resolve, ms));
   }
 
+  // Waits until the cluster clock, which the program compares the timelock to, reaches `timestamp`.
+  const waitForClock = async (timestamp: number): Promise<void> => {
+    const connection = anchor.getProvider().connection;
+    while ((await connection.getBlockTime(await connection.getSlot())) < timestamp) {
+      await wait(400);
+    }
+  };
+
   before(async () => {
+    await initializeConfig(program);
     user = wallet;
     alice = await createUser();
     bob = await createUser();
-    pda = await getHTLC(ID);
+    pda = await getHTLC(wallet.publicKey, ID);
     signature = await ed.sign(MSG, alice.secretKey.slice(0, 32));
   });
   it("create prehtlc", async () => {
@@ -91,7 +102,7 @@ describe("HTLC", () => {
     console.log("lamport balance of wallet before commit",
       await anchor.getProvider().connection.getBalance(wallet.publicKey));
     const commitTx = await program.methods
-      .commit(IDArray, HOPCHAINS, HOPASSETS, HOPADDRESSES, DSTCHAIN, DSTASSET, DSTADDRESS, SRCASSET, wallet.publicKey, TIMELOCK, new anchor.BN(AMOUNT), pda.htlcBump)
+      .commit(IDArray, HOPCHAINS, HOPASSETS, HOPADDRESSES, DSTCHAIN, DSTASSET, DSTADDRESS, SRCASSET, wallet.publicKey, TIMELOCK, { timestamp: {} }, new anchor.BN(AMOUNT), pda.htlcBump)
       .accountsPartial({
         sender: user.publicKey,
         htlc: pda.htlc,
@@ -104,7 +115,7 @@ describe("HTLC", () => {
     console.log("lamport balance of Alice-wallet before addLock",
       await anchor.getProvider().connection.getBalance(user.publicKey));
 
-    const signAddLock = await program.methods.addLock(IDArray, HASHLOCKArray, TIMELOCK).
+    const signAddLock = await program.methods.addLock(IDArray, HASHLOCKArray, { sha256: {} }, TIMELOCK).
       accountsPartial({
         sender: user.publicKey,
         payer: wallet.publicKey,
@@ -253,4 +264,90 @@ describe("HTLC", () => {
   //     await anchor.getProvider().connection.getBalance(wallet.publicKey));
   // });
 
+  it("can not redeem from the timelock second on, only refund", async () => {
+    const ID2 = randomBytes(32);
+    const ID2Array: number[] = Array.from(ID2);
+    const pda2 = await getHTLC(wallet.publicKey, ID2);
+    const connection = anchor.getProvider().connection;
+    const TIME = (await connection.getBlockTime(await connection.getSlot())) + 5;
+    const TIMELOCK = new anchor.BN(TIME);
+
+    await program.methods
+      .lock(ID2Array, HASHLOCKArray, { sha256: {} }, new anchor.BN(0), new anchor.BN(0), TIMELOCK, { timestamp: {} }, new anchor.BN(AMOUNT), DSTCHAIN, DSTADDRESS, DSTASSET, SRCASSET, bob.publicKey, pda2.htlcBump)
+      .accountsPartial({
+        sender: wallet.publicKey,
+        htlc: pda2.htlc,
+      })
+      .signers([wallet.payer])
+      .rpc();
+
+    // Refunding one second before the timelock is rejected.
+    await waitForClock(TIME - 1);
+    await assert.rejects(
+      program.methods.refund(ID2Array).
+        accountsPartial({
+          userSigning: wallet.publicKey,
+          htlc: pda2.htlc,
+          sender: wallet.publicKey,
+        })
+        .signers([wallet.payer])
+        .rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "NotPastTimeLock"
+    );
+
+    // Redeeming at the timelock second is rejected.
+    await waitForClock(TIME);
+    await assert.rejects(
+      program.methods.redeem(ID2Array, SECRETArray).
+        accountsPartial({
+          userSigning: bob.publicKey,
+          htlc: pda2.htlc,
+          sender: wallet.publicKey,
+          srcReceiver: bob.publicKey,
+        })
+        .signers([bob])
+        .rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "TimeLockExpired"
+    );
+
+    await program.methods.refund(ID2Array).
+      accountsPartial({
+        userSigning: wallet.publicKey,
+        htlc: pda2.htlc,
+        sender: wallet.publicKey,
+      })
+      .signers([wallet.payer])
+      .rpc();
+    const details = await program.methods.getDetails(ID2Array).accountsPartial({ htlc: pda2.htlc, sender: wallet.publicKey }).view();
+    assert.deepEqual(details.status, { refunded: {} });
+  });
+
+  it("bounds the length of the stored strings", async () => {
+    const TIME = new Date().getTime() + 60000;
+    const TIMELOCK = new anchor.BN(TIME / 1000);
+    const commit = async (dstAddress: string) => {
+      const id = randomBytes(32);
+      const htlc = await getHTLC(wallet.publicKey, id);
+      await program.methods
+        .commit(Array.from(id), HOPCHAINS, HOPASSETS, HOPADDRESSES, DSTCHAIN, DSTASSET, dstAddress, SRCASSET, bob.publicKey, TIMELOCK, { timestamp: {} }, new anchor.BN(AMOUNT), htlc.htlcBump)
+        .accountsPartial({
+          sender: wallet.publicKey,
+          htlc: htlc.htlc,
+        })
+        .signers([wallet.payer])
+        .rpc();
+      return id;
+    };
+
+    // 128 bytes is the longest string an HTLC stores.
+    const longest = await commit("a".repeat(128));
+    const details = await program.methods.getDetails(Array.from(longest)).accountsPartial({ htlc: (await getHTLC(wallet.publicKey, longest)).htlc, sender: wallet.publicKey }).view();
+    assert.equal(details.dstAddress, "a".repeat(128));
+
+    await assert.rejects(
+      commit("a".repeat(129)),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "StringTooLong"
+    );
+  });
+
 });
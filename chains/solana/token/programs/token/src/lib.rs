@@ -8,32 +8,84 @@
 */
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    clock::DEFAULT_MS_PER_SLOT,
+    ed25519_program, hash, keccak,
+    sysvar::instructions::{
+        self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked,
+    },
+};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{CloseAccount, Mint, Token, TokenAccount, Transfer},
+    associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::TransferFeeAmount, BaseStateWithExtensions, ExtensionType,
+            StateWithExtensions,
+        },
+        onchain::invoke_transfer_checked,
+    },
+    token_interface::{
+        close_account, harvest_withheld_tokens_to_mint, CloseAccount, HarvestWithheldTokensToMint,
+        Mint, TokenAccount, TokenInterface,
+    },
 };
-use sha2::{Digest, Sha256};
-use std::mem::size_of;
 declare_id!("3TTb3BF3H273DS8hCJT9w8wuhtchN7fi7tX2sZDZ3p3Q");
+
+/// @dev Seconds after the timelock during which a redeemed / refunded HTLC account is kept
+/// around before it can be closed, converted to slots for slot timelocks.
+pub const CLOSE_GRACE_PERIOD: u64 = 24 * 60 * 60;
+/// @dev Maximum number of entries in each of the hop route vectors of a commit.
+pub const MAX_HOPS: usize = 5;
+/// @dev Maximum length in bytes of a chain, asset or address string stored in an HTLC.
+pub const MAX_STRING_LENGTH: usize = 128;
+/// @dev Lamports of the rent of a closed HTLC account paid to a keeper, any caller of
+/// `close` other than the sender; the rest of the rent goes back to the sender.
+pub const KEEPER_TIP: u64 = 10_000;
+
 /// @title Pre Hashed Timelock Contracts (PHTLCs) on Solana SPL tokens.
 ///
-/// This contract provides a way to lock and keep PHTLCs for SPL tokens.
+/// This contract provides a way to lock and keep PHTLCs for SPL tokens, both of the
+/// Token and the Token-2022 program. Accounts required by a transfer hook of a Token-2022
+/// mint are passed through the remaining accounts of the instructions moving funds.
+/// HTLC accounts are derived from the seeds [b"htlc", sender, Id], so an Id only has
+/// to be unique per sender and can not be taken over by anyone else.
+/// Timelocks are bounded by the config account [b"config"]: they have to lie between
+/// min_timelock_delta and max_timelock_delta seconds from now. The config is created by
+/// the upgrade authority of the program and updated by its admin, who can also pause the
+/// creation of new HTLCs; redeem and refund keep working while paused. Until the config
+/// is initialized, no HTLC can be created or locked.
+/// The admin can also restrict commit and lock to an allowlist of mints, each with its own
+/// amount limits, held in the accounts [b"allowed_mint", mint].
+/// Timelocks are UNIX epoch seconds or, with TimelockKind::Slot, slot heights; the
+/// reward timelock of an HTLC has the kind of its timelock.
 ///
 /// Protocol:
 ///
 ///  1) commit(src_receiver, timelock, tokenContract, amount) - a
 ///      sender calls this to create a new HTLC on a given token (tokenContract)
 ///      for the given amount. A [u8; 32] Id is returned.
-///  2) lock(src_receiver, hashlock, timelock, tokenContract, amount) - a
+///  2) lock(src_receiver, hashlock, hash_algorithm, reward, reward_timelock, timelock, tokenContract,
+///      amount) - a
 ///      sender calls this to create a new HTLC on a given token (tokenContract)
-///      for the given amount. A [u8; 32] Id is returned.
-///  3) add_lock(Id, hashlock) - the sender calls this function
+///      for the given amount. A [u8; 32] Id is returned. An optional reward
+///      is escrowed alongside the amount for whoever redeems the HTLC.
+///  3) add_lock(Id, hashlock, hash_algorithm, timelock) - the sender calls this function
 ///      to add hashlock to the HTLC.
-///  4) redeem(Id, secret) - once the src_receiver knows the secret of
+///  4) add_lock_sig(Id, hashlock, hash_algorithm, timelock) - anyone calls this function with
+///      an ed25519 signature of the sender to add hashlock to the HTLC.
+///  5) extendTimelock(Id, new_timelock) - the sender calls this function to push
+///      back the timelock of an HTLC that is not yet redeemed or refunded.
+///  6) redeem(Id, secret) - once the src_receiver knows the secret of
 ///      the hashlock hash they can claim the tokens with this function
-///  5) refund(Id) - after timelock has expired and if the src_receiver did not
-///      redeem the tokens the sender / creator of the HTLC can get their tokens
-///      back with this function.
+///      or, with redeem_to(Id, secret), send them to another destination
+///      they sign for.
+///  7) refund(Id) - after timelock has expired and if the src_receiver did not
+///      redeem the tokens, anyone, the sender or a keeper cranking expired HTLCs,
+///      can call this function to give the sender / creator of the HTLC their tokens back.
+///  8) close(Id) - once the HTLC is redeemed or refunded and the close grace
+///      period has passed, anyone can close the HTLC account returning its rent
+///      to the sender, minus a KEEPER_TIP for a caller other than the sender.
 
 /// @dev A small utility function that allows us to transfer funds out of the htlc / htlc.
 ///
@@ -42,7 +94,9 @@ declare_id!("3TTb3BF3H273DS8hCJT9w8wuhtchN7fi7tX2sZDZ3p3Q");
 /// * `htlc` - the htlc public key (PDA)
 /// * `htlc_bump` - the htlc public key (PDA) bump
 /// * `htlc_token_account` - The htlc Token account
+/// * `token_contract` - the mint of the htlc Token account
 /// * `token_program` - the token program address
+/// * `remaining_accounts` - the accounts required by a transfer hook of the mint
 /// * `destination_wallet` - The public key of the destination address (where to send funds)
 /// * `amount` - the amount of token that is sent from `htlc_token_account` to `destination_wallet`
 fn transfer_htlc_out<'info>(
@@ -50,27 +104,34 @@ fn transfer_htlc_out<'info>(
     Id: [u8; 32],
     htlc: AccountInfo<'info>,
     htlc_bump: u8,
-    htlc_token_account: &mut Account<'info, TokenAccount>,
+    htlc_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    token_contract: &InterfaceAccount<'info, Mint>,
     token_program: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
     destination_wallet: AccountInfo<'info>,
     amount: u64,
 ) -> Result<()> {
     let bump_vector = htlc_bump.to_le_bytes();
-    let inner = vec![Id.as_ref(), bump_vector.as_ref()];
+    let inner = vec![
+        b"htlc".as_ref(),
+        sender.key.as_ref(),
+        Id.as_ref(),
+        bump_vector.as_ref(),
+    ];
     let outer = vec![inner.as_slice()];
 
     // Perform the actual transfer
-    let transfer_instruction = Transfer {
-        from: htlc_token_account.to_account_info(),
-        to: destination_wallet,
-        authority: htlc.to_account_info(),
-    };
-    let cpi_ctx = CpiContext::new_with_signer(
-        token_program.to_account_info(),
-        transfer_instruction,
+    invoke_transfer_checked(
+        token_program.key,
+        htlc_token_account.to_account_info(),
+        token_contract.to_account_info(),
+        destination_wallet,
+        htlc.to_account_info(),
+        remaining_accounts,
+        amount,
+        token_contract.decimals,
         outer.as_slice(),
-    );
-    anchor_spl::token::transfer(cpi_ctx, amount)?;
+    )?;
 
     // Use the `reload()` function on an account to reload it's state. Since we performed the
     // transfer, we are expecting the `amount` field to have changed.
@@ -81,15 +142,501 @@ fn transfer_htlc_out<'info>(
 
     // If token account has no more tokens, it should be wiped out since it has no other use case.
     if should_close {
-        let ca = CloseAccount {
-            account: htlc_token_account.to_account_info(),
-            destination: sender.to_account_info(),
-            authority: htlc.to_account_info(),
+        close_htlc_token_account(
+            sender,
+            htlc,
+            htlc_token_account.to_account_info(),
+            token_contract.to_account_info(),
+            token_program,
+            outer.as_slice(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// @dev Closes the empty escrow token account of an HTLC, returning its rent to the sender.
+///
+/// * `sender` - htlc creator's account
+/// * `htlc` - the htlc public key (PDA), owner of the escrow
+/// * `htlc_token_account` - The htlc Token account
+/// * `token_contract` - the mint of the htlc Token account
+/// * `token_program` - the token program address
+/// * `signer_seeds` - the seeds of the htlc PDA
+fn close_htlc_token_account<'info>(
+    sender: AccountInfo<'info>,
+    htlc: AccountInfo<'info>,
+    htlc_token_account: AccountInfo<'info>,
+    token_contract: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // Transfer fees withheld in the account block closing it, so they are moved to the mint.
+    if has_withheld_fees(&htlc_token_account)? {
+        let harvest = HarvestWithheldTokensToMint {
+            token_program_id: token_program.clone(),
+            mint: token_contract,
         };
-        let cpi_ctx =
-            CpiContext::new_with_signer(token_program.to_account_info(), ca, outer.as_slice());
-        anchor_spl::token::close_account(cpi_ctx)?;
+        let cpi_ctx = CpiContext::new(token_program.clone(), harvest);
+        harvest_withheld_tokens_to_mint(cpi_ctx, vec![htlc_token_account.clone()])?;
+    }
+
+    let ca = CloseAccount {
+        account: htlc_token_account,
+        destination: sender,
+        authority: htlc,
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, ca, signer_seeds);
+    close_account(cpi_ctx)
+}
+
+/// @dev The accounts besides the HTLC and its escrow token account taking part in a redeem.
+struct RedeemAccounts<'a, 'info> {
+    /// the sender of the HTLC, receiving the rent of the escrow token account
+    sender: AccountInfo<'info>,
+    /// the token account of the src_receiver, or of the destination it authorized
+    destination_token_account: AccountInfo<'info>,
+    /// the token account of the sender, for a reward paid back to the sender
+    sender_token_account: Option<AccountInfo<'info>>,
+    /// the caller
+    user_signing: AccountInfo<'info>,
+    /// the token account of the caller, for a reward paid to the caller
+    user_signing_token_account: Option<AccountInfo<'info>>,
+    /// the mint of the HTLC
+    token_contract: &'a InterfaceAccount<'info, Mint>,
+    /// the token program
+    token_program: AccountInfo<'info>,
+    /// the accounts required by a transfer hook of the mint
+    remaining_accounts: &'a [AccountInfo<'info>],
+}
+
+/// @dev A small utility function that redeems an HTLC: it checks the secret and the timelock,
+/// marks the HTLC redeemed and transfers out its amount and reward.
+///
+/// * `Id` - the Id of the HTLC
+/// * `secret` - the secret of the hashlock
+/// * `htlc` - the HTLC account, checked to belong to the sender and the token accounts
+/// * `htlc_bump` - the bump of the HTLC account
+/// * `htlc_token_account` - the escrow token account of the HTLC
+/// * `accounts` - the other accounts taking part in the redeem
+fn redeem_htlc<'info>(
+    Id: [u8; 32],
+    secret: [u8; 32],
+    htlc: &mut Account<'info, HTLC>,
+    htlc_bump: u8,
+    htlc_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    accounts: RedeemAccounts<'_, 'info>,
+) -> Result<()> {
+    htlc.transition(HtlcAction::Redeem)?;
+
+    let now = htlc.timelock_kind.now()?;
+    require!(now < htlc.timelock, HTLCError::TimeLockExpired);
+    require!(
+        htlc.hash_algorithm.hash(&secret) == htlc.hashlock,
+        HTLCError::HashlockNoMatch
+    );
+
+    htlc.secret = secret;
+
+    let amount = htlc.amount;
+    let reward = htlc.reward;
+    let (receiver_amount, reward_wallet) = if reward == 0 {
+        (amount, None)
+    } else if htlc.reward_timelock > now {
+        let sender_token_account = accounts
+            .sender_token_account
+            .ok_or(HTLCError::NoRewardAccount)?;
+        (amount, Some(sender_token_account))
+    } else if accounts.user_signing.key() == htlc.src_receiver {
+        (
+            amount
+                .checked_add(reward)
+                .ok_or(HTLCError::AmountOverflow)?,
+            None,
+        )
+    } else {
+        let user_signing_token_account = accounts
+            .user_signing_token_account
+            .ok_or(HTLCError::NoRewardAccount)?;
+        (amount, Some(user_signing_token_account))
+    };
+
+    transfer_htlc_out(
+        accounts.sender.clone(),
+        Id,
+        htlc.to_account_info(),
+        htlc_bump,
+        htlc_token_account,
+        accounts.token_contract,
+        accounts.token_program.clone(),
+        accounts.remaining_accounts,
+        accounts.destination_token_account.clone(),
+        receiver_amount,
+    )?;
+    if let Some(reward_wallet) = reward_wallet {
+        transfer_htlc_out(
+            accounts.sender,
+            Id,
+            htlc.to_account_info(),
+            htlc_bump,
+            htlc_token_account,
+            accounts.token_contract,
+            accounts.token_program,
+            accounts.remaining_accounts,
+            reward_wallet,
+            reward,
+        )?;
+    }
+
+    emit!(TokenRedeemed {
+        Id,
+        sender: htlc.sender,
+        redeem_address: accounts.user_signing.key(),
+        destination: accounts.destination_token_account.key(),
+        secret,
+        hashlock: htlc.hashlock,
+    });
+    Ok(())
+}
+
+/// @dev A small utility function that redeems one HTLC of `redeem_batch`, checking the
+/// accounts the `Redeem` context would check.
+///
+/// * `batch` - the accounts of the `redeem_batch` instruction
+/// * `Id` - the Id of the HTLC
+/// * `secret` - the secret of the hashlock
+/// * `bumps` - the bumps of the HTLC account and of the token account of its src_receiver
+/// * `accounts` - the HTLC, its escrow token account, its sender, the token account of its
+///   src_receiver and a token account of its sender, or the program id if no reward is
+///   paid back to the sender
+/// * `hook_accounts` - the accounts required by a transfer hook of the mint
+fn redeem_batch_item<'info>(
+    batch: &RedeemBatch<'info>,
+    Id: [u8; 32],
+    secret: [u8; 32],
+    bumps: BatchBumps,
+    accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let [htlc, htlc_token_account, sender, src_receiver_token_account, sender_token_account] =
+        accounts
+    else {
+        return err!(HTLCError::InvalidBatch);
+    };
+    let mut htlc = Account::<HTLC>::try_from(htlc)?;
+    let mut htlc_token_account = InterfaceAccount::<TokenAccount>::try_from(htlc_token_account)?;
+    let receiver_token_account =
+        InterfaceAccount::<TokenAccount>::try_from(src_receiver_token_account)?;
+    // Like an optional account of a context, the program id stands in for a left out one.
+    let sender_token_account = if sender_token_account.key() == crate::ID {
+        None
+    } else {
+        let token_account = InterfaceAccount::<TokenAccount>::try_from(sender_token_account)?;
+        require_keys_eq!(token_account.owner, sender.key(), HTLCError::NotSender);
+        require_keys_eq!(
+            token_account.mint,
+            batch.token_contract.key(),
+            HTLCError::NoToken
+        );
+        Some(sender_token_account.clone())
+    };
+
+    check_htlc_address(&htlc.key(), sender.key, &Id, bumps.htlc)?;
+    require_keys_eq!(
+        htlc_token_account.key(),
+        htlc.token_wallet,
+        ErrorCode::ConstraintSeeds
+    );
+    require_keys_eq!(htlc.sender, sender.key(), HTLCError::NotSender);
+    require_keys_eq!(
+        htlc.token_contract,
+        batch.token_contract.key(),
+        HTLCError::NoToken
+    );
+    check_associated_token_account(
+        &receiver_token_account.key(),
+        &htlc.src_receiver,
+        &htlc.token_contract,
+        batch.token_program.key,
+        bumps.associated_token_account,
+        HTLCError::NotReciever,
+    )?;
+
+    redeem_htlc(
+        Id,
+        secret,
+        &mut htlc,
+        bumps.htlc,
+        &mut htlc_token_account,
+        RedeemAccounts {
+            sender: sender.clone(),
+            destination_token_account: src_receiver_token_account.clone(),
+            sender_token_account,
+            user_signing: batch.user_signing.to_account_info(),
+            user_signing_token_account: batch
+                .user_signing_token_account
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            token_contract: &batch.token_contract,
+            token_program: batch.token_program.to_account_info(),
+            remaining_accounts: hook_accounts,
+        },
+    )?;
+    htlc.exit(&crate::ID)
+}
+
+/// @dev A small utility function that refunds an HTLC: it checks the timelock, marks the
+/// HTLC refunded and transfers its amount and reward back to the sender.
+///
+/// * `Id` - the Id of the HTLC
+/// * `htlc` - the HTLC account, checked to belong to the sender and the token accounts
+/// * `htlc_bump` - the bump of the HTLC account
+/// * `htlc_token_account` - the escrow token account of the HTLC
+/// * `sender` - the sender of the HTLC, receiving the rent of the escrow token account
+/// * `sender_token_account` - the token account of the sender
+/// * `token_contract` - the mint of the HTLC
+/// * `token_program` - the token program
+/// * `remaining_accounts` - the accounts required by a transfer hook of the mint
+fn refund_htlc<'info>(
+    Id: [u8; 32],
+    htlc: &mut Account<'info, HTLC>,
+    htlc_bump: u8,
+    htlc_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    sender: AccountInfo<'info>,
+    sender_token_account: AccountInfo<'info>,
+    token_contract: &InterfaceAccount<'info, Mint>,
+    token_program: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    htlc.transition(HtlcAction::Refund)?;
+
+    require!(
+        htlc.timelock_kind.now()? >= htlc.timelock,
+        HTLCError::NotPastTimeLock
+    );
+
+    transfer_htlc_out(
+        sender,
+        Id,
+        htlc.to_account_info(),
+        htlc_bump,
+        htlc_token_account,
+        token_contract,
+        token_program,
+        remaining_accounts,
+        sender_token_account,
+        htlc.amount
+            .checked_add(htlc.reward)
+            .ok_or(HTLCError::AmountOverflow)?,
+    )?;
+
+    emit!(TokenRefunded {
+        Id,
+        sender: htlc.sender,
+    });
+    Ok(())
+}
+
+/// @dev A small utility function that refunds one HTLC of `refund_batch`, checking the
+/// accounts the `Refund` context would check.
+///
+/// * `batch` - the accounts of the `refund_batch` instruction
+/// * `Id` - the Id of the HTLC
+/// * `bumps` - the bumps of the HTLC account and of the associated token account of its
+///   sender
+/// * `accounts` - the HTLC, its escrow token account, its sender and a token account of
+///   its sender
+/// * `hook_accounts` - the accounts required by a transfer hook of the mint
+fn refund_batch_item<'info>(
+    batch: &RefundBatch<'info>,
+    Id: [u8; 32],
+    bumps: BatchBumps,
+    accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let [htlc, htlc_token_account, sender, sender_token_account] = accounts else {
+        return err!(HTLCError::InvalidBatch);
+    };
+    let mut htlc = Account::<HTLC>::try_from(htlc)?;
+    let mut htlc_token_account = InterfaceAccount::<TokenAccount>::try_from(htlc_token_account)?;
+    let sender_token = InterfaceAccount::<TokenAccount>::try_from(sender_token_account)?;
+
+    check_htlc_address(&htlc.key(), sender.key, &Id, bumps.htlc)?;
+    require_keys_eq!(
+        htlc_token_account.key(),
+        htlc.token_wallet,
+        ErrorCode::ConstraintSeeds
+    );
+    require_keys_eq!(htlc.sender, sender.key(), HTLCError::NotSender);
+    require_keys_eq!(
+        htlc.token_contract,
+        batch.token_contract.key(),
+        HTLCError::NoToken
+    );
+    require_keys_eq!(sender_token.owner, sender.key(), HTLCError::NotSender);
+    require_keys_eq!(
+        sender_token.mint,
+        batch.token_contract.key(),
+        HTLCError::NoToken
+    );
+    if batch.user_signing.key() != sender.key() {
+        check_associated_token_account(
+            &sender_token_account.key(),
+            sender.key,
+            &htlc.token_contract,
+            batch.token_program.key,
+            bumps.associated_token_account,
+            HTLCError::UnauthorizedAccess,
+        )?;
+    }
+
+    refund_htlc(
+        Id,
+        &mut htlc,
+        bumps.htlc,
+        &mut htlc_token_account,
+        sender.clone(),
+        sender_token_account.clone(),
+        &batch.token_contract,
+        batch.token_program.to_account_info(),
+        hook_accounts,
+    )?;
+    htlc.exit(&crate::ID)
+}
+
+/// @dev A small utility function that checks the HTLC account of a batch item against its
+/// seeds and the bump passed by the caller, which is cheaper than searching for the bump.
+///
+/// * `htlc` - the address of the HTLC account
+/// * `sender` - the sender of the HTLC
+/// * `Id` - the Id of the HTLC
+/// * `bump` - the bump of the HTLC account
+fn check_htlc_address(htlc: &Pubkey, sender: &Pubkey, Id: &[u8; 32], bump: u8) -> Result<()> {
+    let address = Pubkey::create_program_address(
+        &[b"htlc".as_ref(), sender.as_ref(), Id.as_ref(), &[bump]],
+        &crate::ID,
+    )
+    .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+    require_keys_eq!(*htlc, address, ErrorCode::ConstraintSeeds);
+    Ok(())
+}
+
+/// @dev A small utility function that checks a token account of a batch item to be the
+/// associated token account of `owner`, given its bump, failing with `error` otherwise.
+///
+/// * `token_account` - the address of the token account
+/// * `owner` - the owner of the associated token account
+/// * `token_contract` - the mint of the associated token account
+/// * `token_program` - the token program owning the mint
+/// * `bump` - the bump of the associated token account
+/// * `error` - the error to fail with
+fn check_associated_token_account(
+    token_account: &Pubkey,
+    owner: &Pubkey,
+    token_contract: &Pubkey,
+    token_program: &Pubkey,
+    bump: u8,
+    error: HTLCError,
+) -> Result<()> {
+    let address = Pubkey::create_program_address(
+        &[
+            owner.as_ref(),
+            token_program.as_ref(),
+            token_contract.as_ref(),
+            &[bump],
+        ],
+        &associated_token::ID,
+    )
+    .map_err(|_| error!(error))?;
+    require_keys_eq!(*token_account, address, error);
+    Ok(())
+}
+
+/// @dev A small utility function that rejects Token-2022 mints whose extensions make them
+/// unusable for an HTLC.
+///
+/// * `token_contract` - the mint account
+fn check_mint_extensions(token_contract: &AccountInfo) -> Result<()> {
+    if *token_contract.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = token_contract.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let extensions = mint.get_extension_types()?;
+    require!(
+        !extensions.contains(&ExtensionType::NonTransferable),
+        HTLCError::NonTransferableMint
+    );
+    require!(
+        !extensions.contains(&ExtensionType::ConfidentialTransferMint),
+        HTLCError::ConfidentialTransferMint
+    );
+
+    Ok(())
+}
+
+/// @dev A small utility function that checks whether a Token-2022 account holds withheld
+/// transfer fees.
+///
+/// * `token_account` - the token account
+fn has_withheld_fees(token_account: &AccountInfo) -> Result<bool> {
+    if *token_account.owner != spl_token_2022::ID {
+        return Ok(false);
     }
+    let data = token_account.try_borrow_data()?;
+    let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    Ok(account
+        .get_extension::<TransferFeeAmount>()
+        .is_ok_and(|fee| u64::from(fee.withheld_amount) > 0))
+}
+
+/// @dev A small utility function that checks the Ed25519 signature verification instruction
+/// placed right before the current instruction.
+///
+/// * `ix_sysvar` - the instructions sysvar account
+/// * `signer` - the public key that must have produced the signature
+/// * `message` - the message that must have been signed
+fn verify_ed25519_ix(ix_sysvar: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = load_current_index_checked(ix_sysvar)?;
+    require!(current_index > 0, HTLCError::InvalidSignature);
+    let ix = load_instruction_at_checked((current_index - 1) as usize, ix_sysvar)?;
+    require!(
+        ix.program_id == ed25519_program::ID && ix.accounts.is_empty(),
+        HTLCError::InvalidSignature
+    );
+
+    // Layout: [num_signatures: u8, padding: u8, offsets: 7 x u16] followed by the data.
+    let data = &ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        HTLCError::InvalidSignature
+    );
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix_index = read_u16(14);
+
+    // Everything must live in the Ed25519 instruction itself, otherwise the verified
+    // data could be taken from an unrelated instruction.
+    require!(
+        signature_ix_index == u16::MAX
+            && public_key_ix_index == u16::MAX
+            && message_ix_index == u16::MAX,
+        HTLCError::InvalidSignature
+    );
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(HTLCError::InvalidSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(HTLCError::InvalidSignature)?;
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        HTLCError::InvalidSignature
+    );
 
     Ok(())
 }
@@ -98,7 +645,6 @@ fn transfer_htlc_out<'info>(
 pub mod anchor_htlc {
 
     use super::*;
-    use anchor_spl::token::Transfer;
 
     /// @dev Called by the Sender to get the commitId from the given parameters.
     pub fn get_commit_id(
@@ -111,60 +657,91 @@ pub mod anchor_htlc {
         let clock = Clock::get().unwrap();
         let time: u64 = clock.unix_timestamp.try_into().unwrap();
 
-        let mut hasher = Sha256::new();
-        hasher.update(ctx.program_id);
-        hasher.update(sender);
-        hasher.update(receiver);
-        hasher.update(&amount.to_be_bytes());
-        hasher.update(&time.to_be_bytes());
-        hasher.update(&timelock.to_be_bytes());
-
-        let commitId = hasher.finalize();
+        let commitId = hash::hashv(&[
+            ctx.program_id.as_ref(),
+            sender.as_ref(),
+            receiver.as_ref(),
+            &amount.to_be_bytes(),
+            &time.to_be_bytes(),
+            &timelock.to_be_bytes(),
+        ]);
         // let commitId = hex::encode(commitId);
         // msg!("commit id: {}", commitId);
-        Ok(commitId.into())
+        Ok(commitId.to_bytes())
     }
     /// @dev Sender / Payer sets up a new pre-hash time lock contract depositing the
     /// funds and providing the reciever/src_receiver and terms.
     /// @param src_receiver reciever of the funds.
     /// @param timelock UNIX epoch seconds time that the lock expires at.
     ///                  Refunds can be made after this time.
+    /// @param timelock_kind whether the timelocks are UNIX epoch seconds or slots.
     /// @return Id of the new HTLC. This is needed for subsequent calls.
-    pub fn commit(
-        ctx: Context<Commit>,
+    pub fn commit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Commit<'info>>,
         Id: [u8; 32],
         hopChains: Vec<String>,
         hopAssets: Vec<String>,
-        hopAddress: Vec<String>,
+        hopAddresses: Vec<String>,
         dst_chain: String,
         dst_asset: String,
         dst_address: String,
         src_asset: String,
         src_receiver: Pubkey,
         timelock: u64,
+        timelock_kind: TimelockKind,
         amount: u64,
         commit_bump: u8,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
+            timelock > timelock_kind.now()?,
             HTLCError::NotFutureTimeLock
         );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
         require!(amount != 0, HTLCError::FundsNotSent);
+        ctx.accounts
+            .config
+            .check_allowed_mint(&ctx.accounts.allowed_mint, amount)?;
+        for hops in [&hopChains, &hopAssets, &hopAddresses] {
+            require!(hops.len() <= MAX_HOPS, HTLCError::TooManyHops);
+            require!(
+                hops.iter().all(|hop| hop.len() <= MAX_STRING_LENGTH),
+                HTLCError::StringTooLong
+            );
+        }
+        require!(
+            [&dst_chain, &dst_asset, &dst_address, &src_asset]
+                .iter()
+                .all(|field| field.len() <= MAX_STRING_LENGTH),
+            HTLCError::StringTooLong
+        );
+        check_mint_extensions(&ctx.accounts.token_contract.to_account_info())?;
         let htlc = &mut ctx.accounts.htlc;
+        let sender_key = ctx.accounts.sender.key();
         let bump_vector = commit_bump.to_le_bytes();
-        let inner = vec![Id.as_ref(), bump_vector.as_ref()];
+        let inner = vec![
+            b"htlc".as_ref(),
+            sender_key.as_ref(),
+            Id.as_ref(),
+            bump_vector.as_ref(),
+        ];
         let outer = vec![inner.as_slice()];
-        let transfer_context = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.sender_token_account.to_account_info(),
-                to: ctx.accounts.htlc_token_account.to_account_info(),
-                authority: ctx.accounts.sender.to_account_info(),
-            },
+        invoke_transfer_checked(
+            ctx.accounts.token_program.key,
+            ctx.accounts.sender_token_account.to_account_info(),
+            ctx.accounts.token_contract.to_account_info(),
+            ctx.accounts.htlc_token_account.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            ctx.accounts.token_contract.decimals,
             outer.as_slice(),
-        );
-        anchor_spl::token::transfer(transfer_context, amount)?;
+        )?;
+        // Token-2022 transfer fees are withheld from what arrives, only that is locked.
+        ctx.accounts.htlc_token_account.reload()?;
+        let amount = ctx.accounts.htlc_token_account.amount;
+        require!(amount != 0, HTLCError::FundsNotSent);
 
         htlc.dst_address = dst_address;
         htlc.dst_chain = dst_chain;
@@ -172,33 +749,60 @@ pub mod anchor_htlc {
         htlc.src_asset = src_asset;
         htlc.sender = *ctx.accounts.sender.to_account_info().key;
         htlc.src_receiver = src_receiver;
+        htlc.hop_chains = hopChains;
+        htlc.hop_assets = hopAssets;
+        htlc.hop_addresses = hopAddresses;
         htlc.hashlock = [0u8; 32];
+        htlc.hash_algorithm = HashAlgorithm::Sha256;
         htlc.secret = [0u8; 32];
         htlc.amount = amount;
         htlc.timelock = timelock;
+        htlc.timelock_kind = timelock_kind;
+        htlc.reward = 0;
+        htlc.reward_timelock = 0;
         htlc.token_contract = *ctx.accounts.token_contract.to_account_info().key;
         htlc.token_wallet = *ctx.accounts.htlc_token_account.to_account_info().key;
-        htlc.redeemed = false;
-        htlc.refunded = false;
+        htlc.status = HtlcStatus::Committed;
 
-        // msg!("hop chains: {:?}", hopChains);
-        // msg!("hop assets: {:?}", hopAssets);
-        // msg!("hop addresses: {:?}", hopAddresses);
+        emit!(TokenCommitted {
+            Id,
+            hopChains: htlc.hop_chains.clone(),
+            hopAssets: htlc.hop_assets.clone(),
+            hopAddresses: htlc.hop_addresses.clone(),
+            dst_chain: htlc.dst_chain.clone(),
+            dst_address: htlc.dst_address.clone(),
+            dst_asset: htlc.dst_asset.clone(),
+            sender: htlc.sender,
+            src_receiver: htlc.src_receiver,
+            src_asset: htlc.src_asset.clone(),
+            amount,
+            timelock,
+            timelock_kind,
+            token_contract: htlc.token_contract,
+        });
         Ok(Id)
     }
 
     /// @dev Sender / Payer sets up a new hash time lock contract depositing the
     /// funds and providing the reciever and terms.
     /// @param src_receiver receiver of the funds.
-    /// @param hashlock A sha-256 hash hashlock.
+    /// @param hashlock A sha-256 or keccak-256 hash hashlock.
+    /// @param hash_algorithm The hash function of the hashlock.
+    /// @param reward The reward in tokens granted to the caller of redeem.
+    /// @param reward_timelock UNIX epoch seconds time after which the reward can be claimed.
     /// @param timelock UNIX epoch seconds time that the lock expires at.
     ///                  Refunds can be made after this time.
+    /// @param timelock_kind whether the timelocks are UNIX epoch seconds or slots.
     /// @return Id of the new HTLC. This is needed for subsequent calls.
-    pub fn lock(
-        ctx: Context<Lock>,
+    pub fn lock<'info>(
+        ctx: Context<'_, '_, '_, 'info, Lock<'info>>,
         Id: [u8; 32],
         hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        reward: u64,
+        reward_timelock: u64,
         timelock: u64,
+        timelock_kind: TimelockKind,
         dst_chain: String,
         dst_address: String,
         dst_asset: String,
@@ -207,27 +811,57 @@ pub mod anchor_htlc {
         amount: u64,
         lock_bump: u8,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
+        let now = timelock_kind.now()?;
+        require!(timelock > now, HTLCError::NotFutureTimeLock);
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+        require!(amount != 0, HTLCError::FundsNotSent);
+        ctx.accounts
+            .config
+            .check_allowed_mint(&ctx.accounts.allowed_mint, amount)?;
+        if reward != 0 {
+            require!(
+                reward_timelock <= timelock && reward_timelock >= now,
+                HTLCError::InvalidRewardTimelock
+            );
+        }
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
-            HTLCError::NotFutureTimeLock
+            [&dst_chain, &dst_asset, &dst_address, &src_asset]
+                .iter()
+                .all(|field| field.len() <= MAX_STRING_LENGTH),
+            HTLCError::StringTooLong
         );
-        require!(amount != 0, HTLCError::FundsNotSent);
+        check_mint_extensions(&ctx.accounts.token_contract.to_account_info())?;
         let htlc = &mut ctx.accounts.htlc;
 
+        let sender_key = ctx.accounts.sender.key();
         let bump_vector = lock_bump.to_le_bytes();
-        let inner = vec![Id.as_ref(), bump_vector.as_ref()];
+        let inner = vec![
+            b"htlc".as_ref(),
+            sender_key.as_ref(),
+            Id.as_ref(),
+            bump_vector.as_ref(),
+        ];
         let outer = vec![inner.as_slice()];
-        let transfer_context = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.sender_token_account.to_account_info(),
-                to: ctx.accounts.htlc_token_account.to_account_info(),
-                authority: ctx.accounts.sender.to_account_info(),
-            },
+        invoke_transfer_checked(
+            ctx.accounts.token_program.key,
+            ctx.accounts.sender_token_account.to_account_info(),
+            ctx.accounts.token_contract.to_account_info(),
+            ctx.accounts.htlc_token_account.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            ctx.remaining_accounts,
+            amount
+                .checked_add(reward)
+                .ok_or(HTLCError::AmountOverflow)?,
+            ctx.accounts.token_contract.decimals,
             outer.as_slice(),
-        );
-        anchor_spl::token::transfer(transfer_context, amount)?;
+        )?;
+        // Token-2022 transfer fees are withheld from what arrives, only that is locked.
+        ctx.accounts.htlc_token_account.reload()?;
+        let received = ctx.accounts.htlc_token_account.amount;
+        require!(received > reward, HTLCError::FundsNotSent);
+        let amount = received - reward;
 
         htlc.dst_address = dst_address;
         htlc.dst_chain = dst_chain;
@@ -235,139 +869,731 @@ pub mod anchor_htlc {
         htlc.src_asset = src_asset;
         htlc.sender = *ctx.accounts.sender.to_account_info().key;
         htlc.src_receiver = src_receiver;
+        htlc.hop_chains = Vec::new();
+        htlc.hop_assets = Vec::new();
+        htlc.hop_addresses = Vec::new();
         htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
         htlc.secret = [0u8; 32];
         htlc.amount = amount;
         htlc.timelock = timelock;
+        htlc.timelock_kind = timelock_kind;
+        htlc.reward = reward;
+        htlc.reward_timelock = reward_timelock;
         htlc.token_contract = *ctx.accounts.token_contract.to_account_info().key;
         htlc.token_wallet = *ctx.accounts.htlc_token_account.to_account_info().key;
-        htlc.redeemed = false;
-        htlc.refunded = false;
+        htlc.status = HtlcStatus::Locked;
 
+        emit!(TokenLocked {
+            Id,
+            hashlock,
+            hash_algorithm,
+            dst_chain: htlc.dst_chain.clone(),
+            dst_address: htlc.dst_address.clone(),
+            dst_asset: htlc.dst_asset.clone(),
+            sender: htlc.sender,
+            src_receiver: htlc.src_receiver,
+            src_asset: htlc.src_asset.clone(),
+            amount,
+            reward,
+            reward_timelock,
+            timelock,
+            timelock_kind,
+            token_contract: htlc.token_contract,
+        });
         Ok(Id)
     }
 
     /// @dev Called by the sender to add hashlock to the HTLC
     ///
     /// @param Id of the HTLC.
-    /// @param hashlock to be added.
+    /// @param hashlock of the HTLC to be locked.
+    /// @param hash_algorithm The hash function of the hashlock.
     pub fn add_lock(
         ctx: Context<AddLock>,
         Id: [u8; 32],
         hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
         timelock: u64,
     ) -> Result<[u8; 32]> {
-        let clock = Clock::get().unwrap();
+        let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::AddLock)?;
+
+        let timelock_kind = htlc.timelock_kind;
         require!(
-            timelock > clock.unix_timestamp.try_into().unwrap(),
+            timelock > timelock_kind.now()?,
             HTLCError::NotFutureTimeLock
         );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+
+        htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
+        htlc.timelock = timelock;
+
+        emit!(TokenLockAdded {
+            Id,
+            sender: htlc.sender,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        });
+        Ok(Id)
+    }
 
+    /// @dev Called by anyone to add hashlock to the HTLC on behalf of the sender,
+    /// using a signature of the sender over (program id, sender, Id, src_receiver,
+    /// amount, current timelock, hashlock, timelock, hash_algorithm), integers big-endian.
+    /// The transaction must contain an Ed25519 program instruction verifying that
+    /// signature right before this instruction. Binding the message to the committed
+    /// HTLC keeps it from being replayed against an HTLC the sender recreates under the
+    /// same Id after closing this one.
+    ///
+    /// @param Id of the HTLC.
+    /// @param hashlock to be added.
+    /// @param hash_algorithm The hash function of the hashlock.
+    /// @param timelock to be set.
+    pub fn add_lock_sig(
+        ctx: Context<AddLockSig>,
+        Id: [u8; 32],
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: u64,
+    ) -> Result<[u8; 32]> {
         let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::AddLock)?;
+
+        let timelock_kind = htlc.timelock_kind;
+        require!(
+            timelock > timelock_kind.now()?,
+            HTLCError::NotFutureTimeLock
+        );
+        ctx.accounts
+            .config
+            .check_timelock(timelock_kind, timelock)?;
+
+        let mut message = Vec::with_capacity(185);
+        message.extend_from_slice(ctx.program_id.as_ref());
+        message.extend_from_slice(htlc.sender.as_ref());
+        message.extend_from_slice(&Id);
+        message.extend_from_slice(htlc.src_receiver.as_ref());
+        message.extend_from_slice(&htlc.amount.to_be_bytes());
+        message.extend_from_slice(&htlc.timelock.to_be_bytes());
+        message.extend_from_slice(&hashlock);
+        message.extend_from_slice(&timelock.to_be_bytes());
+        message.push(hash_algorithm as u8);
+        verify_ed25519_ix(
+            &ctx.accounts.ix_sysvar.to_account_info(),
+            &htlc.sender,
+            &message,
+        )?;
 
         htlc.hashlock = hashlock;
+        htlc.hash_algorithm = hash_algorithm;
         htlc.timelock = timelock;
 
+        emit!(TokenLockAdded {
+            Id,
+            sender: htlc.sender,
+            hashlock,
+            hash_algorithm,
+            timelock,
+        });
         Ok(Id)
     }
 
+    /// @dev Called by the sender to push back the timelock of an HTLC that is neither
+    /// redeemed nor refunded, e.g. when the destination chain is congested. The timelock
+    /// can only be increased, only before it expires and only up to the maximum of the
+    /// config.
+    ///
+    /// @param Id of the HTLC.
+    /// @param new_timelock to be set, later than the current timelock.
+    pub fn extend_timelock(
+        ctx: Context<ExtendTimelock>,
+        Id: [u8; 32],
+        new_timelock: u64,
+    ) -> Result<bool> {
+        let htlc = &mut ctx.accounts.htlc;
+        htlc.transition(HtlcAction::Extend)?;
+
+        require!(
+            htlc.timelock > htlc.timelock_kind.now()?,
+            HTLCError::TimeLockExpired
+        );
+        require!(
+            new_timelock > htlc.timelock,
+            HTLCError::TimelockNotIncreased
+        );
+        require!(
+            new_timelock <= ctx.accounts.config.max_timelock(htlc.timelock_kind)?,
+            HTLCError::TimelockTooLong
+        );
+        htlc.timelock = new_timelock;
+
+        emit!(TokenTimelockExtended {
+            Id,
+            sender: htlc.sender,
+            timelock: new_timelock,
+        });
+        Ok(true)
+    }
+
     /// @dev Called by the src_receiver once they know the secret of the hashlock.
     /// This will transfer the locked funds to the HTLC's src_receiver's address.
+    /// If the HTLC carries a reward, it goes back to the sender before the reward
+    /// timelock, and afterwards to the caller (or to the src_receiver, if they
+    /// redeem themselves).
+    /// Redeeming is only possible before the timelock; from the timelock on
+    /// the HTLC can only be refunded.
     ///
     /// @param Id of the HTLC.
-    /// @param secret sha256(secret) should equal the contract hashlock.
-    pub fn redeem(
-        ctx: Context<Redeem>,
+    /// @param secret the hash of the secret under the HTLC's hash algorithm should equal
+    /// the contract hashlock.
+    pub fn redeem<'info>(
+        ctx: Context<'_, '_, '_, 'info, Redeem<'info>>,
         Id: [u8; 32],
         secret: [u8; 32],
         htlc_bump: u8,
     ) -> Result<bool> {
-        let htlc = &mut ctx.accounts.htlc;
-        let mut hasher = Sha256::new();
-        hasher.update(secret.clone());
-        let hash = hasher.finalize();
-        require!([0u8; 32] != htlc.hashlock, HTLCError::HashlockNotSet);
-        require!(hash == htlc.hashlock.into(), HTLCError::HashlockNoMatch);
+        let accounts = ctx.accounts;
+        redeem_htlc(
+            Id,
+            secret,
+            &mut accounts.htlc,
+            htlc_bump,
+            &mut accounts.htlc_token_account,
+            RedeemAccounts {
+                sender: accounts.sender.to_account_info(),
+                destination_token_account: accounts.src_receiver_token_account.to_account_info(),
+                sender_token_account: accounts
+                    .sender_token_account
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                user_signing: accounts.user_signing.to_account_info(),
+                user_signing_token_account: accounts
+                    .user_signing_token_account
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                token_contract: &accounts.token_contract,
+                token_program: accounts.token_program.to_account_info(),
+                remaining_accounts: ctx.remaining_accounts,
+            },
+        )?;
+        Ok(true)
+    }
 
-        htlc.redeemed = true;
-        htlc.secret = secret;
+    /// @dev Redeems like `redeem`, but transfers the tokens of the src_receiver to another
+    /// token account of the mint it authorized: either the src_receiver signs the
+    /// transaction, or the transaction contains an Ed25519 program instruction right before
+    /// this instruction verifying a signature of the src_receiver over (program id, sender,
+    /// Id, hashlock, amount, destination_token_account), the amount big-endian. The
+    /// hashlock and amount bind the signature to this HTLC, so it can not be replayed on an
+    /// HTLC recreated under the same Id after a close.
+    ///
+    /// @param Id of the HTLC.
+    /// @param secret the hash of the secret under the HTLC's hash algorithm should equal
+    /// the contract hashlock.
+    pub fn redeem_to<'info>(
+        ctx: Context<'_, '_, '_, 'info, RedeemTo<'info>>,
+        Id: [u8; 32],
+        secret: [u8; 32],
+        htlc_bump: u8,
+    ) -> Result<bool> {
+        let accounts = ctx.accounts;
+        if !accounts.src_receiver.is_signer {
+            let mut message = Vec::with_capacity(128);
+            message.extend_from_slice(ctx.program_id.as_ref());
+            message.extend_from_slice(accounts.sender.key.as_ref());
+            message.extend_from_slice(&Id);
+            message.extend_from_slice(&accounts.htlc.hashlock);
+            message.extend_from_slice(&accounts.htlc.amount.to_be_bytes());
+            message.extend_from_slice(accounts.destination_token_account.key().as_ref());
+            verify_ed25519_ix(
+                &accounts.ix_sysvar.to_account_info(),
+                accounts.src_receiver.key,
+                &message,
+            )?;
+        }
 
-        transfer_htlc_out(
-            ctx.accounts.sender.to_account_info(),
+        redeem_htlc(
             Id,
-            htlc.to_account_info(),
+            secret,
+            &mut accounts.htlc,
             htlc_bump,
-            &mut ctx.accounts.htlc_token_account,
-            ctx.accounts.token_program.to_account_info(),
-            ctx.accounts.src_receiver_token_account.to_account_info(),
-            ctx.accounts.htlc.amount,
+            &mut accounts.htlc_token_account,
+            RedeemAccounts {
+                sender: accounts.sender.to_account_info(),
+                destination_token_account: accounts.destination_token_account.to_account_info(),
+                sender_token_account: accounts
+                    .sender_token_account
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                user_signing: accounts.user_signing.to_account_info(),
+                user_signing_token_account: accounts
+                    .user_signing_token_account
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                token_contract: &accounts.token_contract,
+                token_program: accounts.token_program.to_account_info(),
+                remaining_accounts: ctx.remaining_accounts,
+            },
         )?;
-
         Ok(true)
     }
 
-    /// @dev Called by the sender if there was no redeem AND the time lock has
-    /// expired. This will refund the contract amount.
+    /// @dev Redeems several HTLCs of the same token in one instruction, paying out like
+    /// `redeem`. For every Id the remaining accounts hold the HTLC, its escrow token account,
+    /// its sender, the associated token account of its src_receiver and a token account of
+    /// its sender, in this order; the program id can stand in for the latter unless a
+    /// reward is paid back to the sender. Accounts required by a transfer hook of the mint
+    /// follow after those of the last HTLC. The token accounts of the src_receivers must
+    /// exist.
     ///
-    /// @param Id of the HTLC to refund from.
-    pub fn refund(ctx: Context<Refund>, Id: [u8; 32], htlc_bump: u8) -> Result<bool> {
-        let htlc = &mut ctx.accounts.htlc;
+    /// @param ids of the HTLCs.
+    /// @param secrets of the HTLCs, one for every Id.
+    /// @param bumps of the HTLC accounts and the associated token accounts of the
+    /// src_receivers, one for every Id.
+    /// @param atomic whether an HTLC that can not be redeemed fails the whole batch, or is
+    /// skipped. Failing token transfers always fail the whole batch.
+    /// @return whether each HTLC was redeemed.
+    pub fn redeem_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemBatch<'info>>,
+        ids: Vec<[u8; 32]>,
+        secrets: Vec<[u8; 32]>,
+        bumps: Vec<BatchBumps>,
+        atomic: bool,
+    ) -> Result<Vec<bool>> {
+        require!(
+            ids.len() == secrets.len()
+                && ids.len() == bumps.len()
+                && ctx.remaining_accounts.len() >= 5 * ids.len(),
+            HTLCError::InvalidBatch
+        );
 
-        htlc.refunded = true;
+        let (items, hook_accounts) = ctx.remaining_accounts.split_at(5 * ids.len());
+        let mut redeemed = Vec::with_capacity(ids.len());
+        for (((Id, secret), bumps), accounts) in
+            ids.into_iter().zip(secrets).zip(bumps).zip(items.chunks(5))
+        {
+            match redeem_batch_item(ctx.accounts, Id, secret, bumps, accounts, hook_accounts) {
+                Ok(()) => redeemed.push(true),
+                Err(error) if !atomic => {
+                    msg!("HTLC {} not redeemed: {}", hex::encode(Id), error);
+                    redeemed.push(false);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(redeemed)
+    }
 
-        transfer_htlc_out(
-            ctx.accounts.sender.to_account_info(),
+    /// @dev Called by anyone if there was no redeem AND the time lock has expired, so keepers
+    /// can crank refunds of expired HTLCs. This will refund the contract amount, always to
+    /// the sender: to `sender_token_account` if given, which may be any token account of the
+    /// sender when the sender signs but only the associated token account of the sender
+    /// otherwise, else to the associated token account of the sender, which is created if
+    /// needed and paid for by the caller.
+    ///
+    /// @param Id of the HTLC to refund from.
+    pub fn refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+        Id: [u8; 32],
+        htlc_bump: u8,
+    ) -> Result<bool> {
+        let accounts = ctx.accounts;
+        let refund_account = match (
+            &accounts.sender_token_account,
+            &accounts.sender_associated_token_account,
+        ) {
+            (Some(token_account), _) => token_account.to_account_info(),
+            (None, Some(associated_token_account)) => associated_token_account.to_account_info(),
+            (None, None) => return err!(HTLCError::NoRefundAccount),
+        };
+        refund_htlc(
             Id,
-            htlc.to_account_info(),
+            &mut accounts.htlc,
             htlc_bump,
-            &mut ctx.accounts.htlc_token_account,
-            ctx.accounts.token_program.to_account_info(),
-            ctx.accounts.sender_token_account.to_account_info(),
-            ctx.accounts.htlc.amount,
+            &mut accounts.htlc_token_account,
+            accounts.sender.to_account_info(),
+            refund_account,
+            &accounts.token_contract,
+            accounts.token_program.to_account_info(),
+            ctx.remaining_accounts,
         )?;
-
         Ok(true)
     }
 
-    /// @dev Get HTLC details.
-    /// @param Id of the HTLC.
-    pub fn getDetails(ctx: Context<GetDetails>, Id: [u8; 32]) -> Result<HTLC> {
-        let htlc = &ctx.accounts.htlc;
+    /// @dev Refunds several expired HTLCs of the same token in one instruction, paying back
+    /// like `refund`. For every Id the remaining accounts hold the HTLC, its escrow token
+    /// account, its sender and a token account of its sender, in this order; the associated
+    /// one unless the caller is the sender. Accounts
+    /// required by a transfer hook of the mint follow after those of the last HTLC. HTLCs
+    /// that can not be refunded are skipped, but failing token transfers fail the whole batch.
+    ///
+    /// @param ids of the HTLCs.
+    /// @param bumps of the HTLC accounts and the associated token accounts of the senders,
+    /// one for every Id.
+    /// @return whether each HTLC was refunded.
+    pub fn refund_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundBatch<'info>>,
+        ids: Vec<[u8; 32]>,
+        bumps: Vec<BatchBumps>,
+    ) -> Result<Vec<bool>> {
+        require!(
+            ids.len() == bumps.len() && ctx.remaining_accounts.len() >= 4 * ids.len(),
+            HTLCError::InvalidBatch
+        );
 
-        msg!("dst_address: {:?}", htlc.dst_address.clone());
-        msg!("dst_chain: {:?}", htlc.dst_chain.clone());
-        msg!("dst_asset: {:?}", htlc.dst_asset.clone());
-        msg!("src_asset: {:?}", htlc.src_asset);
-        msg!("sender: {:?}", htlc.sender);
-        msg!("src_receiver: {:?}", htlc.src_receiver);
-        msg!("hashlock: {:?}", hex::encode(htlc.hashlock));
-        msg!("secret: {:?}", hex::encode(htlc.secret.clone()));
-        msg!("amount: {:?}", htlc.amount);
-        msg!("timelock: {:?}", htlc.timelock);
-        msg!("token_contract: {:?}", htlc.token_contract);
-        msg!("token_wallet: {:?}", htlc.token_wallet);
-        msg!("redeemed: {:?}", htlc.redeemed);
-        msg!("refunded: {:?}", htlc.refunded);
+        let (items, hook_accounts) = ctx.remaining_accounts.split_at(4 * ids.len());
+        let mut refunded = Vec::with_capacity(ids.len());
+        for ((Id, bumps), accounts) in ids.into_iter().zip(bumps).zip(items.chunks(4)) {
+            match refund_batch_item(ctx.accounts, Id, bumps, accounts, hook_accounts) {
+                Ok(()) => refunded.push(true),
+                Err(error) => {
+                    msg!("HTLC {} not refunded: {}", hex::encode(Id), error);
+                    refunded.push(false);
+                }
+            }
+        }
+        Ok(refunded)
+    }
 
-        Ok(HTLC {
-            dst_address: htlc.dst_address.clone(),
-            dst_chain: htlc.dst_chain.clone(),
-            dst_asset: htlc.dst_asset.clone(),
-            src_asset: htlc.src_asset.clone(),
-            sender: htlc.sender,
+    /// @dev Called by anyone once the HTLC is redeemed or refunded and the close grace period
+    /// after its timelock has passed. This will close the HTLC account and return its rent
+    /// to the sender, who paid for it. The secret stays available in the TokenRedeemed event.
+    /// A caller other than the sender is paid the KEEPER_TIP out of the rent.
+    /// Tokens sent to the escrow token account after it was paid out would keep it open
+    /// and block the Id, so they are swept to the associated token account of the sender,
+    /// created if needed and paid for by the caller, and the escrow is closed.
+    ///
+    /// @param Id of the HTLC to close.
+    pub fn close<'info>(
+        ctx: Context<'_, '_, '_, 'info, Close<'info>>,
+        Id: [u8; 32],
+    ) -> Result<bool> {
+        let accounts = &ctx.accounts;
+        let htlc_token_account = accounts.htlc_token_account.to_account_info();
+        if !htlc_token_account.data_is_empty() {
+            let sender_token_account = accounts
+                .sender_token_account
+                .as_ref()
+                .ok_or(HTLCError::NoRefundAccount)?;
+            let balance =
+                TokenAccount::try_deserialize(&mut &htlc_token_account.try_borrow_data()?[..])?
+                    .amount;
+            let bump_vector = ctx.bumps.htlc.to_le_bytes();
+            let inner = vec![
+                b"htlc".as_ref(),
+                accounts.sender.key.as_ref(),
+                Id.as_ref(),
+                bump_vector.as_ref(),
+            ];
+            let outer = vec![inner.as_slice()];
+            if balance > 0 {
+                invoke_transfer_checked(
+                    accounts.token_program.key,
+                    htlc_token_account.clone(),
+                    accounts.token_contract.to_account_info(),
+                    sender_token_account.to_account_info(),
+                    accounts.htlc.to_account_info(),
+                    ctx.remaining_accounts,
+                    balance,
+                    accounts.token_contract.decimals,
+                    outer.as_slice(),
+                )?;
+            }
+            close_htlc_token_account(
+                accounts.sender.to_account_info(),
+                accounts.htlc.to_account_info(),
+                htlc_token_account,
+                accounts.token_contract.to_account_info(),
+                accounts.token_program.to_account_info(),
+                outer.as_slice(),
+            )?;
+        }
+
+        let keeper_tip = if ctx.accounts.user_signing.key() == ctx.accounts.sender.key() {
+            0
+        } else {
+            KEEPER_TIP
+        };
+        ctx.accounts.htlc.sub_lamports(keeper_tip)?;
+        ctx.accounts.user_signing.add_lamports(keeper_tip)?;
+
+        emit!(TokenClosed {
+            Id,
+            sender: ctx.accounts.sender.key(),
+            keeper_tip,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called once by the upgrade authority of the program to create the config,
+    /// becoming its admin.
+    ///
+    /// @param min_timelock_delta Minimum number of seconds from now of a timelock.
+    /// @param max_timelock_delta Maximum number of seconds from now of a timelock.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.authority.key();
+        config.set_timelock_bounds(min_timelock_delta, max_timelock_delta)?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            min_timelock_delta,
+            max_timelock_delta,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to change the timelock bounds of the config. HTLCs
+    /// created before keep their timelock.
+    ///
+    /// @param min_timelock_delta Minimum number of seconds from now of a timelock.
+    /// @param max_timelock_delta Maximum number of seconds from now of a timelock.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        config.set_timelock_bounds(min_timelock_delta, max_timelock_delta)?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            min_timelock_delta,
+            max_timelock_delta,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to stop new HTLCs: commit, lock, add_lock and add_lock_sig
+    /// fail until unpause is called. Redeem, refund and close keep working, so existing
+    /// HTLCs can still be settled.
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<bool> {
+        ctx.accounts.config.paused = true;
+        emit!(Paused {
+            admin: ctx.accounts.admin.key(),
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to allow new HTLCs again after pause.
+    pub fn unpause(ctx: Context<UpdateConfig>) -> Result<bool> {
+        ctx.accounts.config.paused = false;
+        emit!(Unpaused {
+            admin: ctx.accounts.admin.key(),
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to hand the config over to a new admin.
+    ///
+    /// @param new_admin the account becoming the admin.
+    pub fn transfer_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<bool> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to turn the mint allowlist on or off. While it is on,
+    /// commit and lock only accept mints allowed by allow_mint, within their amount limits.
+    ///
+    /// @param enabled whether the allowlist applies.
+    pub fn set_allowlist_enabled(ctx: Context<UpdateConfig>, enabled: bool) -> Result<bool> {
+        ctx.accounts.config.allowlist_enabled = enabled;
+        emit!(AllowlistUpdated { enabled });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to add a mint to the allowlist, or to change the amount
+    /// limits of an allowed mint.
+    ///
+    /// @param min_amount Minimum amount of an HTLC of the mint.
+    /// @param max_amount Maximum amount of an HTLC of the mint.
+    pub fn allow_mint(ctx: Context<AllowMint>, min_amount: u64, max_amount: u64) -> Result<bool> {
+        require!(min_amount <= max_amount, HTLCError::InvalidAmountLimits);
+        let allowed_mint = &mut ctx.accounts.allowed_mint;
+        allowed_mint.token_contract = ctx.accounts.token_contract.key();
+        allowed_mint.min_amount = min_amount;
+        allowed_mint.max_amount = max_amount;
+
+        emit!(MintAllowed {
+            token_contract: allowed_mint.token_contract,
+            min_amount,
+            max_amount,
+        });
+        Ok(true)
+    }
+
+    /// @dev Called by the admin to remove a mint from the allowlist, closing its account.
+    /// HTLCs of the mint created before can still be redeemed and refunded.
+    pub fn disallow_mint(ctx: Context<DisallowMint>) -> Result<bool> {
+        emit!(MintDisallowed {
+            token_contract: ctx.accounts.token_contract.key(),
+        });
+        Ok(true)
+    }
+
+    /// @dev Get HTLC details.
+    /// @param Id of the HTLC.
+    pub fn getDetails(ctx: Context<GetDetails>, Id: [u8; 32]) -> Result<HTLC> {
+        let htlc = &ctx.accounts.htlc;
+
+        msg!("dst_address: {:?}", htlc.dst_address.clone());
+        msg!("dst_chain: {:?}", htlc.dst_chain.clone());
+        msg!("dst_asset: {:?}", htlc.dst_asset.clone());
+        msg!("src_asset: {:?}", htlc.src_asset);
+        msg!("hop_chains: {:?}", htlc.hop_chains);
+        msg!("hop_assets: {:?}", htlc.hop_assets);
+        msg!("hop_addresses: {:?}", htlc.hop_addresses);
+        msg!("sender: {:?}", htlc.sender);
+        msg!("src_receiver: {:?}", htlc.src_receiver);
+        msg!("hashlock: {:?}", hex::encode(htlc.hashlock));
+        msg!("hash_algorithm: {:?}", htlc.hash_algorithm);
+        msg!("secret: {:?}", hex::encode(htlc.secret.clone()));
+        msg!("amount: {:?}", htlc.amount);
+        msg!("timelock: {:?}", htlc.timelock);
+        msg!("reward: {:?}", htlc.reward);
+        msg!("reward_timelock: {:?}", htlc.reward_timelock);
+        msg!("token_contract: {:?}", htlc.token_contract);
+        msg!("token_wallet: {:?}", htlc.token_wallet);
+        msg!("status: {:?}", htlc.status);
+
+        Ok(HTLC {
+            dst_address: htlc.dst_address.clone(),
+            dst_chain: htlc.dst_chain.clone(),
+            dst_asset: htlc.dst_asset.clone(),
+            src_asset: htlc.src_asset.clone(),
+            hop_chains: htlc.hop_chains.clone(),
+            hop_assets: htlc.hop_assets.clone(),
+            hop_addresses: htlc.hop_addresses.clone(),
+            sender: htlc.sender,
             src_receiver: htlc.src_receiver,
             hashlock: htlc.hashlock,
+            hash_algorithm: htlc.hash_algorithm,
             secret: htlc.secret.clone(),
             amount: htlc.amount,
             timelock: htlc.timelock,
+            timelock_kind: htlc.timelock_kind,
+            reward: htlc.reward,
+            reward_timelock: htlc.reward_timelock,
             token_contract: htlc.token_contract,
             token_wallet: htlc.token_wallet,
-            redeemed: htlc.redeemed,
-            refunded: htlc.refunded,
+            status: htlc.status,
+        })
+    }
+
+    /// @dev Get HTLC reward details.
+    /// @param Id of the HTLC.
+    pub fn getRewardDetails(ctx: Context<GetDetails>, Id: [u8; 32]) -> Result<Reward> {
+        let htlc = &ctx.accounts.htlc;
+        Ok(Reward {
+            amount: htlc.reward,
+            timelock: htlc.reward_timelock,
         })
     }
 }
 
+/// @dev Hash function a hashlock is computed with. SHA-256 is the default; Keccak-256
+/// pairs an HTLC with counterparty chains using keccak256 hashlocks, such as EVM chains.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    /// @dev Hashes the secret of an HTLC.
+    pub fn hash(&self, secret: &[u8; 32]) -> [u8; 32] {
+        match self {
+            HashAlgorithm::Sha256 => hash::hash(secret).to_bytes(),
+            HashAlgorithm::Keccak256 => keccak::hash(secret).to_bytes(),
+        }
+    }
+}
+
+/// @dev Unit of the timelock and reward timelock of an HTLC: UNIX epoch seconds compared
+/// against the unix_timestamp of the clock, or a slot height compared against its slot,
+/// which does not drift with the stake-weighted timestamp.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimelockKind {
+    #[default]
+    Timestamp,
+    Slot,
+}
+
+impl TimelockKind {
+    /// @dev The current time in the unit of the kind.
+    pub fn now(&self) -> Result<u64> {
+        let clock = Clock::get()?;
+        Ok(match self {
+            TimelockKind::Timestamp => clock.unix_timestamp.try_into().unwrap(),
+            TimelockKind::Slot => clock.slot,
+        })
+    }
+
+    /// @dev Converts a number of seconds into the unit of the kind, counting
+    /// DEFAULT_MS_PER_SLOT milliseconds per slot.
+    pub fn duration(&self, seconds: u64) -> u64 {
+        match self {
+            TimelockKind::Timestamp => seconds,
+            TimelockKind::Slot => seconds.saturating_mul(1000) / DEFAULT_MS_PER_SLOT,
+        }
+    }
+}
+
+/// @dev Lifecycle of an HTLC. A commit starts out `Committed` and becomes `Locked` once its
+/// hashlock is added, a lock starts out `Locked`. Both end up `Redeemed` or `Refunded`; the
+/// actions allowed in each status are defined by `HTLC::transition`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HtlcStatus {
+    #[default]
+    Committed,
+    Locked,
+    Redeemed,
+    Refunded,
+}
+
+impl HtlcStatus {
+    /// @dev Whether the tokens of the HTLC were paid out.
+    pub fn is_settled(&self) -> bool {
+        matches!(self, HtlcStatus::Redeemed | HtlcStatus::Refunded)
+    }
+}
+
+/// @dev An instruction acting on an HTLC, checked against its status by `HTLC::transition`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HtlcAction {
+    AddLock,
+    Extend,
+    Redeem,
+    Refund,
+}
+
+/// @dev The bumps of the accounts of one HTLC of `redeem_batch` or `refund_batch`, derived
+/// by the caller so the accounts can be checked without searching for the bumps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchBumps {
+    /// the bump of the HTLC account
+    pub htlc: u8,
+    /// the bump of the associated token account of the src_receiver on a redeem, or of the
+    /// sender on a refund
+    pub associated_token_account: u8,
+}
+
 #[account]
 #[derive(Default)]
 pub struct HTLC {
@@ -375,19 +1601,171 @@ pub struct HTLC {
     pub dst_chain: String,
     pub dst_asset: String,
     pub src_asset: String,
+    pub hop_chains: Vec<String>,
+    pub hop_assets: Vec<String>,
+    pub hop_addresses: Vec<String>,
     pub sender: Pubkey,
     pub src_receiver: Pubkey,
     pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
     pub secret: [u8; 32],
     pub amount: u64,   //TODO: check if this should be u256, though the spl uses u64
     pub timelock: u64, //TODO: check if this should be u256
+    pub timelock_kind: TimelockKind,
+    pub reward: u64,
+    pub reward_timelock: u64,
     pub token_contract: Pubkey,
     pub token_wallet: Pubkey,
-    pub redeemed: bool,
-    pub refunded: bool,
+    pub status: HtlcStatus,
+}
+
+impl HTLC {
+    /// @dev Moves the HTLC to the status following `action`, failing if the action is not
+    /// allowed in its current status. Extending the timelock keeps the status.
+    pub fn transition(&mut self, action: HtlcAction) -> Result<()> {
+        self.status = match (self.status, action) {
+            (HtlcStatus::Redeemed, _) => return err!(HTLCError::AlreadyRedeemed),
+            (HtlcStatus::Refunded, _) => return err!(HTLCError::AlreadyRefunded),
+            (status, HtlcAction::Extend) => status,
+            (HtlcStatus::Committed, HtlcAction::AddLock) => HtlcStatus::Locked,
+            (HtlcStatus::Committed, HtlcAction::Redeem) => return err!(HTLCError::HashlockNotSet),
+            (HtlcStatus::Locked, HtlcAction::AddLock) => {
+                return err!(HTLCError::HashlockAlreadySet)
+            }
+            (HtlcStatus::Locked, HtlcAction::Redeem) => HtlcStatus::Redeemed,
+            (_, HtlcAction::Refund) => HtlcStatus::Refunded,
+        };
+        Ok(())
+    }
+
+    /// @dev Whether the close grace period after the timelock has passed.
+    pub fn is_past_grace_period(&self) -> Result<bool> {
+        let grace_period = self.timelock_kind.duration(CLOSE_GRACE_PERIOD);
+        Ok(self.timelock_kind.now()? >= self.timelock.saturating_add(grace_period))
+    }
+
+    /// @dev Space taken by the fixed size fields of an HTLC.
+    const FIXED_SPACE: usize = 32 + 32 + 32 + 1 + 32 + 8 + 8 + 1 + 8 + 8 + 32 + 32 + 1;
+
+    /// @dev Space of an HTLC account holding the given strings: the 8 byte discriminator,
+    /// the fixed size fields and the 4 byte length prefix of every String and Vec.
+    pub fn space(
+        dst_address: &str,
+        dst_chain: &str,
+        dst_asset: &str,
+        src_asset: &str,
+        hop_chains: &[String],
+        hop_assets: &[String],
+        hop_addresses: &[String],
+    ) -> usize {
+        let fields: usize = [dst_address, dst_chain, dst_asset, src_asset]
+            .iter()
+            .map(|field| 4 + field.len())
+            .sum();
+        let hops: usize = hop_chains
+            .iter()
+            .chain(hop_assets)
+            .chain(hop_addresses)
+            .map(|hop| 4 + hop.len())
+            .sum();
+        8 + fields + 3 * 4 + hops + HTLC::FIXED_SPACE
+    }
+}
+
+/// @dev Protocol-wide settings, stored in the account [b"config"].
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub min_timelock_delta: u64,
+    pub max_timelock_delta: u64,
+    pub paused: bool,
+    pub allowlist_enabled: bool,
+}
+
+impl Config {
+    /// @dev Sets the timelock bounds, failing if the minimum exceeds the maximum.
+    fn set_timelock_bounds(
+        &mut self,
+        min_timelock_delta: u64,
+        max_timelock_delta: u64,
+    ) -> Result<()> {
+        require!(
+            min_timelock_delta <= max_timelock_delta,
+            HTLCError::InvalidTimelockBounds
+        );
+        self.min_timelock_delta = min_timelock_delta;
+        self.max_timelock_delta = max_timelock_delta;
+        Ok(())
+    }
+
+    /// @dev Checks that `timelock` of the given kind lies between the minimum and the
+    /// maximum delta from now, both converted to the unit of the kind.
+    pub fn check_timelock(&self, timelock_kind: TimelockKind, timelock: u64) -> Result<()> {
+        let min_timelock = timelock_kind
+            .now()?
+            .saturating_add(timelock_kind.duration(self.min_timelock_delta));
+        require!(timelock >= min_timelock, HTLCError::TimelockTooShort);
+        require!(
+            timelock <= self.max_timelock(timelock_kind)?,
+            HTLCError::TimelockTooLong
+        );
+        Ok(())
+    }
+
+    /// @dev The latest timelock of the given kind accepted now, the maximum delta from now.
+    pub fn max_timelock(&self, timelock_kind: TimelockKind) -> Result<u64> {
+        Ok(timelock_kind
+            .now()?
+            .saturating_add(timelock_kind.duration(self.max_timelock_delta)))
+    }
+
+    /// @dev Checks, while the allowlist is enabled, that the mint is allowed and that
+    /// `amount` lies within its limits.
+    ///
+    /// * `allowed_mint` - The account [b"allowed_mint", mint], empty unless the mint is allowed.
+    /// * `amount` - The amount of the HTLC.
+    pub fn check_allowed_mint(&self, allowed_mint: &AccountInfo, amount: u64) -> Result<()> {
+        if !self.allowlist_enabled {
+            return Ok(());
+        }
+        require!(
+            allowed_mint.owner == &crate::ID && !allowed_mint.data_is_empty(),
+            HTLCError::MintNotAllowed
+        );
+        let allowed_mint = AllowedMint::try_deserialize(&mut &allowed_mint.data.borrow()[..])?;
+        require!(amount >= allowed_mint.min_amount, HTLCError::AmountTooSmall);
+        require!(amount <= allowed_mint.max_amount, HTLCError::AmountTooLarge);
+        Ok(())
+    }
+}
+
+/// @dev A mint on the allowlist with the amount limits of its HTLCs, stored in the account
+/// [b"allowed_mint", mint].
+#[account]
+#[derive(InitSpace)]
+pub struct AllowedMint {
+    pub token_contract: Pubkey,
+    pub min_amount: u64,
+    pub max_amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct Reward {
+    pub amount: u64,
+    pub timelock: u64,
 }
 #[derive(Accounts)]
-#[instruction(Id: [u8;32], commit_bump: u8)]
+#[instruction(
+    Id: [u8; 32],
+    hopChains: Vec<String>,
+    hopAssets: Vec<String>,
+    hopAddresses: Vec<String>,
+    dst_chain: String,
+    dst_asset: String,
+    dst_address: String,
+    src_asset: String,
+)]
 pub struct Commit<'info> {
     #[account(mut)]
     pub sender: Signer<'info>,
@@ -395,40 +1773,64 @@ pub struct Commit<'info> {
     #[account(
         init,
         payer = sender,
-        space = size_of::<HTLC>() + 28,
+        space = HTLC::space(&dst_address, &dst_chain, &dst_asset, &src_asset, &hopChains, &hopAssets, &hopAddresses),
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
     #[account(
         init,
         payer = sender,
         seeds = [
             b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
         token::mint=token_contract,
         token::authority=htlc,
+        token::token_program=token_program,
+    )]
+    pub htlc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_contract: Box<InterfaceAccount<'info, Mint>>,
+    ///CHECK: The allowlist entry of the mint, only read while the allowlist is enabled
+    #[account(
+        seeds = [b"allowed_mint".as_ref(), token_contract.key().as_ref()],
+        bump,
     )]
-    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
-    pub token_contract: Account<'info, Mint>,
+    pub allowed_mint: UncheckedAccount<'info>,
     #[account(
         mut,
         constraint=sender_token_account.owner == sender.key() @HTLCError::NotSender,
         constraint=sender_token_account.mint == token_contract.key() @HTLCError::NoToken,
     )]
-    pub sender_token_account: Account<'info, TokenAccount>,
+    pub sender_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(Id: [u8; 32], lock_bump: u8)]
+#[instruction(
+    Id: [u8; 32],
+    hashlock: [u8; 32],
+    hash_algorithm: HashAlgorithm,
+    reward: u64,
+    reward_timelock: u64,
+    timelock: u64,
+    timelock_kind: TimelockKind,
+    dst_chain: String,
+    dst_address: String,
+    dst_asset: String,
+    src_asset: String,
+)]
 pub struct Lock<'info> {
     #[account(mut)]
     pub sender: Signer<'info>,
@@ -436,36 +1838,47 @@ pub struct Lock<'info> {
     #[account(
         init,
         payer = sender,
-        space = size_of::<HTLC>() + 28,
-        // space = 256,
+        space = HTLC::space(&dst_address, &dst_chain, &dst_asset, &src_asset, &[], &[], &[]),
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
     #[account(
         init,
         payer = sender,
         seeds = [
             b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
         token::mint=token_contract,
         token::authority=htlc,
+        token::token_program=token_program,
     )]
-    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+    pub htlc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_contract: Account<'info, Mint>,
+    pub token_contract: Box<InterfaceAccount<'info, Mint>>,
+    ///CHECK: The allowlist entry of the mint, only read while the allowlist is enabled
+    #[account(
+        seeds = [b"allowed_mint".as_ref(), token_contract.key().as_ref()],
+        bump,
+    )]
+    pub allowed_mint: UncheckedAccount<'info>,
     #[account(
         mut,
         constraint=sender_token_account.owner == sender.key() @HTLCError::NotSender,
         constraint=sender_token_account.mint == token_contract.key() @ HTLCError::NoToken,
     )]
-    pub sender_token_account: Box<Account<'info, TokenAccount>>,
+    pub sender_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -479,46 +1892,141 @@ pub struct Redeem<'info> {
     #[account(
         mut,
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
         has_one = sender @HTLCError::NotSender,
         has_one = src_receiver @HTLCError::NotReciever,
         has_one = token_contract @HTLCError::NoToken,
-        constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-        constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
     #[account(
         mut,
         seeds = [
             b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
-    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+    pub htlc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init_if_needed,
         payer = user_signing,
         associated_token::mint = token_contract,
         associated_token::authority = src_receiver,
+        associated_token::token_program = token_program,
+    )]
+    pub src_receiver_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint=sender_token_account.owner == sender.key() @HTLCError::NotSender,
+        constraint=sender_token_account.mint == token_contract.key() @HTLCError::NoToken,
+    )]
+    pub sender_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        constraint=user_signing_token_account.owner == user_signing.key() @HTLCError::UnauthorizedAccess,
+        constraint=user_signing_token_account.mint == token_contract.key() @HTLCError::NoToken,
     )]
-    pub src_receiver_token_account: Account<'info, TokenAccount>,
+    pub user_signing_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     ///CHECK: The sender
     #[account(mut)]
     sender: UncheckedAccount<'info>,
     ///CHECK: The reciever
     pub src_receiver: UncheckedAccount<'info>,
-    token_contract: Account<'info, Mint>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
 
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
     associated_token_program: Program<'info, AssociatedToken>,
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(Id: [u8;32])]
+pub struct RedeemTo<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
+            Id.as_ref()
+        ],
+        bump,
+        has_one = sender @HTLCError::NotSender,
+        has_one = src_receiver @HTLCError::NotReciever,
+        has_one = token_contract @HTLCError::NoToken,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    #[account(
+        mut,
+        seeds = [
+            b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
+            Id.as_ref()
+        ],
+        bump,
+    )]
+    pub htlc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint=destination_token_account.mint == token_contract.key() @HTLCError::NoToken,
+    )]
+    pub destination_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint=sender_token_account.owner == sender.key() @HTLCError::NotSender,
+        constraint=sender_token_account.mint == token_contract.key() @HTLCError::NoToken,
+    )]
+    pub sender_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        constraint=user_signing_token_account.owner == user_signing.key() @HTLCError::UnauthorizedAccess,
+        constraint=user_signing_token_account.mint == token_contract.key() @HTLCError::NoToken,
+    )]
+    pub user_signing_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    ///CHECK: The sender
+    #[account(mut)]
+    sender: UncheckedAccount<'info>,
+    ///CHECK: The reciever, signing or authorizing the destination by an ed25519 signature
+    pub src_receiver: UncheckedAccount<'info>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
+
+    ///CHECK: The instructions sysvar
+    #[account(address = sysvar_instructions::ID)]
+    ix_sysvar: UncheckedAccount<'info>,
+
+    system_program: Program<'info, System>,
+    token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemBatch<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+    #[account(
+        mut,
+        constraint=user_signing_token_account.owner == user_signing.key() @HTLCError::UnauthorizedAccess,
+        constraint=user_signing_token_account.mint == token_contract.key() @HTLCError::NoToken,
+    )]
+    pub user_signing_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
+
+    system_program: Program<'info, System>,
+    token_program: Interface<'info, TokenInterface>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8;32], htlc_bump: u8)]
 pub struct Refund<'info> {
@@ -527,43 +2035,65 @@ pub struct Refund<'info> {
 
     #[account(mut,
     seeds = [
-        //b"htlc",
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
         Id.as_ref()
     ],
     bump = htlc_bump,
     has_one = sender @HTLCError::NotSender,
     has_one = token_contract @HTLCError::NoToken,
-    constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
-    constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-    constraint = Clock::get().unwrap().unix_timestamp >= htlc.timelock.try_into().unwrap() @ HTLCError::NotPastTimeLock,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
     #[account(
         mut,
         seeds = [
             b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
     )]
-    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+    pub htlc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
     ///CHECK: The sender
     #[account(mut)]
     sender: UncheckedAccount<'info>,
-    token_contract: Account<'info, Mint>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
         constraint=htlc.sender.key() == sender_token_account.owner @HTLCError::NotSender,
-        constraint=sender_token_account.mint == token_contract.key() @HTLCError::NoToken,)]
-    pub sender_token_account: Account<'info, TokenAccount>,
+        constraint=sender_token_account.mint == token_contract.key() @HTLCError::NoToken,
+        constraint=user_signing.key() == htlc.sender || sender_token_account.key() == get_associated_token_address_with_program_id(&htlc.sender, &token_contract.key(), token_program.key) @HTLCError::UnauthorizedAccess,
+    )]
+    pub sender_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = user_signing,
+        associated_token::mint = token_contract,
+        associated_token::authority = sender,
+        associated_token::token_program = token_program,
+    )]
+    pub sender_associated_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
+    associated_token_program: Program<'info, AssociatedToken>,
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct RefundBatch<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
+
+    system_program: Program<'info, System>,
+    token_program: Interface<'info, TokenInterface>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8;32])]
 pub struct AddLock<'info> {
@@ -572,25 +2102,208 @@ pub struct AddLock<'info> {
 
     #[account(mut,
     seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
         Id.as_ref()
     ],
     bump,
-    constraint = !htlc.redeemed @ HTLCError::AlreadyRedeemed,
-    constraint = !htlc.refunded @ HTLCError::AlreadyRefunded,
     constraint = htlc.sender == sender.key() @ HTLCError::UnauthorizedAccess,
-    constraint = htlc.hashlock == [0u8;32] @ HTLCError::HashlockAlreadySet,
     )]
     pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
 
     system_program: Program<'info, System>,
     rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(Id: [u8;32])]
+pub struct AddLockSig<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    ///CHECK: The sender
+    sender: UncheckedAccount<'info>,
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump, constraint = !config.paused @ HTLCError::Paused)]
+    pub config: Box<Account<'info, Config>>,
+
+    ///CHECK: The instructions sysvar
+    #[account(address = sysvar_instructions::ID)]
+    ix_sysvar: UncheckedAccount<'info>,
+
+    system_program: Program<'info, System>,
+    rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(Id: [u8;32])]
+pub struct ExtendTimelock<'info> {
+    sender: Signer<'info>,
+
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    #[account(seeds = [b"config".as_ref()], bump)]
+    pub config: Box<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(Id: [u8;32])]
+pub struct Close<'info> {
+    #[account(mut)]
+    user_signing: Signer<'info>,
+
+    #[account(mut,
+    seeds = [
+        b"htlc".as_ref(),
+        sender.key().as_ref(),
+        Id.as_ref()
+    ],
+    bump,
+    has_one = sender @HTLCError::NotSender,
+    has_one = token_contract @HTLCError::NoToken,
+    constraint = htlc.status.is_settled() @ HTLCError::NotClaimed,
+    constraint = htlc.is_past_grace_period()? @ HTLCError::NotPastGracePeriod,
+    close = sender,
+    )]
+    pub htlc: Box<Account<'info, HTLC>>,
+    ///CHECK: The escrow token account of the HTLC, closed if it still exists
+    #[account(
+        mut,
+        seeds = [
+            b"htlc_token_account".as_ref(),
+            sender.key().as_ref(),
+            Id.as_ref()
+        ],
+        bump,
+    )]
+    pub htlc_token_account: UncheckedAccount<'info>,
+
+    ///CHECK: The sender
+    #[account(mut)]
+    sender: UncheckedAccount<'info>,
+    #[account(mut)]
+    token_contract: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = user_signing,
+        associated_token::mint = token_contract,
+        associated_token::authority = sender,
+        associated_token::token_program = token_program,
+    )]
+    pub sender_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    system_program: Program<'info, System>,
+    token_program: Interface<'info, TokenInterface>,
+    associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config".as_ref()],
+        bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ HTLCError::UnauthorizedAccess)]
+    program: Program<'info, crate::program::AnchorHtlc>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ HTLCError::UnauthorizedAccess)]
+    program_data: Account<'info, ProgramData>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    admin: Signer<'info>,
+
+    #[account(mut,
+    seeds = [b"config".as_ref()],
+    bump,
+    has_one = admin @HTLCError::UnauthorizedAccess,
+    )]
+    pub config: Box<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct AllowMint<'info> {
+    #[account(mut)]
+    admin: Signer<'info>,
+
+    #[account(
+    seeds = [b"config".as_ref()],
+    bump,
+    has_one = admin @HTLCError::UnauthorizedAccess,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    pub token_contract: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + AllowedMint::INIT_SPACE,
+        seeds = [b"allowed_mint".as_ref(), token_contract.key().as_ref()],
+        bump,
+    )]
+    pub allowed_mint: Box<Account<'info, AllowedMint>>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DisallowMint<'info> {
+    #[account(mut)]
+    admin: Signer<'info>,
+
+    #[account(
+    seeds = [b"config".as_ref()],
+    bump,
+    has_one = admin @HTLCError::UnauthorizedAccess,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    ///CHECK: The mint removed from the allowlist
+    token_contract: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"allowed_mint".as_ref(), token_contract.key().as_ref()],
+        bump,
+    )]
+    pub allowed_mint: Box<Account<'info, AllowedMint>>,
+}
+
 #[derive(Accounts)]
 #[instruction(Id: [u8;32])]
 pub struct GetDetails<'info> {
+    ///CHECK: The sender
+    sender: UncheckedAccount<'info>,
     #[account(
         seeds = [
+            b"htlc".as_ref(),
+            sender.key().as_ref(),
             Id.as_ref()
         ],
         bump,
@@ -605,29 +2318,138 @@ pub struct GetCommitId<'info> {
     ///CHECK: The reciever
     pub receiver: UncheckedAccount<'info>,
 }
-// #[event]
-// pub struct TokenCommitted {
-//     pub commitId: [u8; 32],
-//     pub hopChains: Vec<String>,
-//     pub hopAssets: Vec<String>,
-//     pub hopAddress: Vec<String>,
-//     pub dst_chain: String,
-//     pub dst_address: String,
-//     pub dst_asset: String,
-//     pub sender: Pubkey,
-//     pub src_receiver: Pubkey,
-//     pub src_asset: String,
-//     pub amount: u64,
-//     pub timelock: u64,
-//     pub token_contract: Pubkey,
-// }
-
-// #[event]
-// pub struct TokenRedeemed {
-//     #[index]
-//     Id: [u8; 32],
-//     redeem_address: Pubkey,
-// }
+/// @dev Emitted when an HTLC is created and tokens are committed.
+#[event]
+pub struct TokenCommitted {
+    pub Id: [u8; 32],
+    pub hopChains: Vec<String>,
+    pub hopAssets: Vec<String>,
+    pub hopAddresses: Vec<String>,
+    pub dst_chain: String,
+    pub dst_address: String,
+    pub dst_asset: String,
+    pub sender: Pubkey,
+    pub src_receiver: Pubkey,
+    pub src_asset: String,
+    pub amount: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+    pub token_contract: Pubkey,
+}
+
+/// @dev Emitted when an HTLC is locked with a hashlock and timelock.
+#[event]
+pub struct TokenLocked {
+    pub Id: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub dst_chain: String,
+    pub dst_address: String,
+    pub dst_asset: String,
+    pub sender: Pubkey,
+    pub src_receiver: Pubkey,
+    pub src_asset: String,
+    pub amount: u64,
+    pub reward: u64,
+    pub reward_timelock: u64,
+    pub timelock: u64,
+    pub timelock_kind: TimelockKind,
+    pub token_contract: Pubkey,
+}
+
+/// @dev Emitted when a hashlock and timelock are added to an existing HTLC.
+#[event]
+pub struct TokenLockAdded {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub hashlock: [u8; 32],
+    pub hash_algorithm: HashAlgorithm,
+    pub timelock: u64,
+}
+
+/// @dev Emitted when the sender extends the timelock of an HTLC, with the new timelock.
+#[event]
+pub struct TokenTimelockExtended {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub timelock: u64,
+}
+
+/// @dev Emitted when tokens are redeemed from an HTLC using the correct secret, recording
+/// the token account the tokens went to.
+#[event]
+pub struct TokenRedeemed {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub redeem_address: Pubkey,
+    pub destination: Pubkey,
+    pub secret: [u8; 32],
+    pub hashlock: [u8; 32],
+}
+
+/// @dev Emitted when tokens are refunded from an HTLC after the timelock expired.
+#[event]
+pub struct TokenRefunded {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+}
+
+/// @dev Emitted when a redeemed or refunded HTLC account is closed, with the part of its
+/// rent paid to the keeper closing it.
+#[event]
+pub struct TokenClosed {
+    pub Id: [u8; 32],
+    pub sender: Pubkey,
+    pub keeper_tip: u64,
+}
+
+/// @dev Emitted when the config is created or its timelock bounds are updated.
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub min_timelock_delta: u64,
+    pub max_timelock_delta: u64,
+}
+
+/// @dev Emitted when the admin pauses the creation of new HTLCs.
+#[event]
+pub struct Paused {
+    pub admin: Pubkey,
+}
+
+/// @dev Emitted when the admin allows new HTLCs again.
+#[event]
+pub struct Unpaused {
+    pub admin: Pubkey,
+}
+
+/// @dev Emitted when the admin of the config is handed over.
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+/// @dev Emitted when the admin turns the mint allowlist on or off.
+#[event]
+pub struct AllowlistUpdated {
+    pub enabled: bool,
+}
+
+/// @dev Emitted when a mint is added to the allowlist or its amount limits change.
+#[event]
+pub struct MintAllowed {
+    pub token_contract: Pubkey,
+    pub min_amount: u64,
+    pub max_amount: u64,
+}
+
+/// @dev Emitted when a mint is removed from the allowlist.
+#[event]
+pub struct MintDisallowed {
+    pub token_contract: Pubkey,
+}
+
 #[error_code]
 pub enum HTLCError {
     #[msg("Not Future TimeLock.")]
@@ -656,4 +2478,48 @@ pub enum HTLCError {
     NotReciever,
     #[msg("Wrong Token.")]
     NoToken,
+    #[msg("Invalid Signature.")]
+    InvalidSignature,
+    #[msg("Invalid Reward TimeLock.")]
+    InvalidRewardTimelock,
+    #[msg("Reward Token Account Not Provided.")]
+    NoRewardAccount,
+    #[msg("Funds Are Not Redeemed Or Refunded.")]
+    NotClaimed,
+    #[msg("Not Past Close Grace Period.")]
+    NotPastGracePeriod,
+    #[msg("Too Many Hops.")]
+    TooManyHops,
+    #[msg("String Too Long.")]
+    StringTooLong,
+    #[msg("Non-Transferable Mint.")]
+    NonTransferableMint,
+    #[msg("Confidential Transfer Mint.")]
+    ConfidentialTransferMint,
+    #[msg("TimeLock Expired.")]
+    TimeLockExpired,
+    #[msg("Invalid Batch.")]
+    InvalidBatch,
+    #[msg("No Refund Account.")]
+    NoRefundAccount,
+    #[msg("TimeLock Not Increased.")]
+    TimelockNotIncreased,
+    #[msg("Invalid TimeLock Bounds.")]
+    InvalidTimelockBounds,
+    #[msg("TimeLock Too Short.")]
+    TimelockTooShort,
+    #[msg("TimeLock Too Long.")]
+    TimelockTooLong,
+    #[msg("Program Is Paused.")]
+    Paused,
+    #[msg("Invalid Amount Limits.")]
+    InvalidAmountLimits,
+    #[msg("Mint Not Allowed.")]
+    MintNotAllowed,
+    #[msg("Amount Too Small.")]
+    AmountTooSmall,
+    #[msg("Amount Too Large.")]
+    AmountTooLarge,
+    #[msg("Amount Overflow.")]
+    AmountOverflow,
 }
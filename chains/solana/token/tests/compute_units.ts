@@ -0,0 +1,176 @@
+import assert from "assert";
+import * as fs from "fs";
+import * as anchor from "@coral-xyz/anchor";
+import { randomBytes, createHash } from "crypto";
+import { keccak_256 } from "@noble/hashes/sha3";
+import * as spl from '@solana/spl-token';
+import { AnchorHtlc } from '../target/types/anchor_htlc';
+import { initializeConfig } from './config';
+
+// Records the compute units every instruction of the program consumes on the validator
+// and writes them to target/compute-units/anchor_htlc.json, so changes in consumption
+// show up between runs.
+describe("HTLC compute units", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const program = anchor.workspace.AnchorHtlc as anchor.Program<AnchorHtlc>;
+  const wallet = provider.wallet as anchor.Wallet;
+  const connection = provider.connection;
+  const SECRET = randomBytes(32);
+  const SECRETArray: number[] = Array.from(SECRET);
+  const SHA256_HASHLOCK: number[] = Array.from(createHash("sha256").update(SECRET).digest());
+  const KECCAK256_HASHLOCK: number[] = Array.from(keccak_256(SECRET));
+  const AMOUNT = new anchor.BN(1000000);
+  const ZERO = new anchor.BN(0);
+  const DSTCHAIN = "STARKNET_SEPOLIA";
+  const DSTADDRESS = "0x021b6a2ff227f1c71cc6536e7b9e8ecd0d5599b3a934279011e2f2b923d3a782";
+  const SRCASSET = "ETH";
+  const DSTASSET = "ETH";
+  const consumed: Record<string, number> = {};
+
+  let tokenMint: anchor.web3.PublicKey;
+  let walletTokenAccount: anchor.web3.PublicKey;
+
+  const getPdaParams = (id: Buffer) => {
+    const [htlc, htlcBump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("htlc"), wallet.publicKey.toBuffer(), id],
+      program.programId
+    );
+    const [htlcTokenAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("htlc_token_account"), wallet.publicKey.toBuffer(), id],
+      program.programId
+    );
+    return { htlc, htlcBump, htlcTokenAccount };
+  };
+
+  // Reads the units the program consumed from the logs of its instruction.
+  const unitsFromLogs = (logs: string[]): number => {
+    const pattern = new RegExp(`^Program ${program.programId.toBase58()} consumed (\\d+) of`);
+    const units = logs.map((log) => log.match(pattern)).find((match) => match !== null);
+    assert.ok(units, "the program did not log its compute units");
+    return Number(units[1]);
+  };
+
+  const measure = async (name: string, method: any): Promise<void> => {
+    const signature = await method.signers([wallet.payer]).rpc({ commitment: "confirmed" });
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    consumed[name] = unitsFromLogs(tx.meta.logMessages);
+  };
+
+  const measureView = async (name: string, method: any): Promise<void> => {
+    const simulation = await method.simulate();
+    consumed[name] = unitsFromLogs(simulation.raw);
+  };
+
+  const lock = (id: Buffer, hashlock: number[], hashAlgorithm: object, timelock: anchor.BN) => {
+    const pda = getPdaParams(id);
+    return program.methods
+      .lock(Array.from(id), hashlock, hashAlgorithm, ZERO, ZERO, timelock, { timestamp: {} }, DSTCHAIN, DSTADDRESS, DSTASSET, SRCASSET, wallet.publicKey, AMOUNT, pda.htlcBump)
+      .accountsPartial({
+        sender: wallet.publicKey,
+        htlc: pda.htlc,
+        htlcTokenAccount: pda.htlcTokenAccount,
+        tokenContract: tokenMint,
+        senderTokenAccount: walletTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      });
+  };
+
+  const redeem = (id: Buffer) => {
+    const pda = getPdaParams(id);
+    return program.methods.redeem(Array.from(id), SECRETArray, pda.htlcBump).accountsPartial({
+      userSigning: wallet.publicKey,
+      htlc: pda.htlc,
+      htlcTokenAccount: pda.htlcTokenAccount,
+      sender: wallet.publicKey,
+      srcReceiver: wallet.publicKey,
+      srcReceiverTokenAccount: walletTokenAccount,
+      tokenContract: tokenMint,
+      tokenProgram: spl.TOKEN_PROGRAM_ID,
+    });
+  };
+
+  const waitForClock = async (timestamp: number): Promise<void> => {
+    while ((await connection.getBlockTime(await connection.getSlot())) < timestamp) {
+      await new Promise((resolve) => setTimeout(resolve, 400));
+    }
+  };
+
+  before(async () => {
+    await initializeConfig(program);
+    const mint = new anchor.web3.Keypair();
+    tokenMint = mint.publicKey;
+    walletTokenAccount = spl.getAssociatedTokenAddressSync(tokenMint, wallet.publicKey);
+    const tx = new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.createAccount({
+        programId: spl.TOKEN_PROGRAM_ID,
+        space: spl.MintLayout.span,
+        fromPubkey: wallet.publicKey,
+        newAccountPubkey: tokenMint,
+        lamports: await connection.getMinimumBalanceForRentExemption(spl.MintLayout.span),
+      }),
+      spl.createInitializeMintInstruction(tokenMint, 6, wallet.publicKey, wallet.publicKey),
+      spl.createAssociatedTokenAccountInstruction(wallet.publicKey, walletTokenAccount, wallet.publicKey, tokenMint),
+      spl.createMintToInstruction(tokenMint, walletTokenAccount, wallet.publicKey, 1337000000)
+    );
+    await provider.sendAndConfirm(tx, [mint]);
+  });
+
+  after(() => {
+    console.table(consumed);
+    fs.mkdirSync("target/compute-units", { recursive: true });
+    fs.writeFileSync("target/compute-units/anchor_htlc.json", JSON.stringify(consumed, null, 2) + "\n");
+  });
+
+  it("measures every instruction", async () => {
+    const now = await connection.getBlockTime(await connection.getSlot());
+    const TIMELOCK = new anchor.BN(now + 600);
+
+    await measureView("get_commit_id", program.methods.getCommitId(AMOUNT, TIMELOCK).accountsPartial({
+      sender: wallet.publicKey,
+      receiver: wallet.publicKey,
+    }));
+
+    const commitId = randomBytes(32);
+    const commit = getPdaParams(commitId);
+    await measure("commit", program.methods
+      .commit(Array.from(commitId), [DSTCHAIN], [DSTASSET], [DSTADDRESS], DSTCHAIN, DSTASSET, DSTADDRESS, SRCASSET, wallet.publicKey, TIMELOCK, { timestamp: {} }, AMOUNT, commit.htlcBump)
+      .accountsPartial({
+        sender: wallet.publicKey,
+        htlc: commit.htlc,
+        htlcTokenAccount: commit.htlcTokenAccount,
+        tokenContract: tokenMint,
+        senderTokenAccount: walletTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      }));
+    await measure("add_lock", program.methods
+      .addLock(Array.from(commitId), SHA256_HASHLOCK, { sha256: {} }, TIMELOCK)
+      .accountsPartial({ sender: wallet.publicKey, htlc: commit.htlc }));
+    await measureView("getDetails", program.methods
+      .getDetails(Array.from(commitId))
+      .accountsPartial({ sender: wallet.publicKey, htlc: commit.htlc }));
+    await measure("redeem (sha256)", redeem(commitId));
+
+    const keccakId = randomBytes(32);
+    await measure("lock", lock(keccakId, KECCAK256_HASHLOCK, { keccak256: {} }, TIMELOCK));
+    await measure("redeem (keccak256)", redeem(keccakId));
+
+    const refundId = randomBytes(32);
+    const refund = getPdaParams(refundId);
+    const REFUND_TIME = (await connection.getBlockTime(await connection.getSlot())) + 3;
+    await lock(refundId, SHA256_HASHLOCK, { sha256: {} }, new anchor.BN(REFUND_TIME)).signers([wallet.payer]).rpc();
+    await waitForClock(REFUND_TIME);
+    await measure("refund", program.methods.refund(Array.from(refundId), refund.htlcBump).accountsPartial({
+      userSigning: wallet.publicKey,
+      htlc: refund.htlc,
+      htlcTokenAccount: refund.htlcTokenAccount,
+      sender: wallet.publicKey,
+      tokenContract: tokenMint,
+      senderTokenAccount: walletTokenAccount,
+      tokenProgram: spl.TOKEN_PROGRAM_ID,
+    }));
+  });
+});
@@ -0,0 +1,25 @@
+import * as anchor from "@coral-xyz/anchor";
+
+// Timelock bounds the tests run with, loose enough for the short timelocks they wait out.
+export const MIN_TIMELOCK_DELTA = new anchor.BN(0);
+export const MAX_TIMELOCK_DELTA = new anchor.BN(30 * 24 * 60 * 60);
+
+// Creates the config account of the program unless an earlier test file did, signed by
+// the provider wallet `anchor test` deploys the program with, its upgrade authority.
+export const initializeConfig = async (program: anchor.Program<any>): Promise<void> => {
+  const [config] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  if (await program.provider.connection.getAccountInfo(config)) {
+    return;
+  }
+  const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  await program.methods
+    .initializeConfig(MIN_TIMELOCK_DELTA, MAX_TIMELOCK_DELTA)
+    .accountsPartial({ programData })
+    .rpc();
+};
@@ -6,6 +6,7 @@ import { randomBytes, createHash } from "crypto";
 import * as spl from '@solana/spl-token';
 //import { TOKEN_2022_PROGRAM_ID, getAssociatedTokenAddressSync } from "@solana/spl-token";
 import { AnchorHtlc } from '../target/types/anchor_htlc';
+import { initializeConfig } from './config';
 
 interface PDAParameters {
   htlcTokenAccount: anchor.web3.PublicKey;
@@ -52,11 +53,11 @@ describe("HTLC", () => {
   ): Promise<PDAParameters> => {
     // let pseed = ID.toBuffer('le', 8);
     let [htlc, htlcBump] = await anchor.web3.PublicKey.findProgramAddressSync(
-      [Id],
+      [Buffer.from("htlc"), user.toBuffer(), Id],
       program.programId
     );
     let [htlcTokenAccount, htlcTokenbump] = await anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("htlc_token_account"), Id],
+      [Buffer.from("htlc_token_account"), user.toBuffer(), Id],
       program.programId
     );
     console.log(`[${htlc}] derived htlc`);
@@ -171,6 +172,7 @@ describe("HTLC", () => {
   }
 
   before(async () => {
+    await initializeConfig(program);
     let _rest;
     tokenMint = await createMint();
     walletTokenAccount = await mintTokensForUser(wallet.publicKey, tokenMint);
@@ -368,13 +370,14 @@ describe("HTLC", () => {
     console.log(`[${TIME * 1000}] the Timelock`);
 
     const lockTx = await program.methods
-      .lock(IDArray, HASHLOCKArray, TIMELOCK, DSTCHAIN, DSTADDRESS, DSTASSET, SRCASSET, bob.publicKey, new anchor.BN(AMOUNT), pda.htlcBump)
+      .lock(IDArray, HASHLOCKArray, { sha256: {} }, new anchor.BN(0), new anchor.BN(0), TIMELOCK, { timestamp: {} }, DSTCHAIN, DSTADDRESS, DSTASSET, SRCASSET, bob.publicKey, new anchor.BN(AMOUNT), pda.htlcBump)
       .accountsPartial({
         sender: wallet.publicKey,
         htlc: pda.htlc,
         htlcTokenAccount: pda.htlcTokenAccount,
         tokenContract: tokenMint,
-        senderTokenAccount: walletTokenAccount
+        senderTokenAccount: walletTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
       })
       .signers([wallet.payer])
       .rpc();
@@ -384,10 +387,27 @@ describe("HTLC", () => {
     assert.equal(WalletBalancePost, "337000000");
     const [, htlcTokenBalance] = await readAccount(pda.htlcTokenAccount, provider);
     assert.equal(htlcTokenBalance, "1000000000");
-    // Withdraw the funds back
     await wait(20000);
     const CURTIME = new Date().getTime();
     console.log(`[${CURTIME}] CURRENT TIME`);
+    // The secret can no longer be redeemed once the timelock is reached.
+    await assert.rejects(
+      program.methods.redeem(IDArray, SECRETArray, pda.htlcBump).
+        accountsPartial({
+          userSigning: bob.publicKey,
+          htlc: pda.htlc,
+          htlcTokenAccount: pda.htlcTokenAccount,
+          sender: wallet.publicKey,
+          srcReceiver: bob.publicKey,
+          srcReceiverTokenAccount: spl.getAssociatedTokenAddressSync(tokenMint, bob.publicKey),
+          tokenContract: tokenMint,
+          tokenProgram: spl.TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "TimeLockExpired"
+    );
+    // Withdraw the funds back
     const refundTx = await program.methods.refund(IDArray, pda.htlcBump).
       accountsPartial({
         userSigning: wallet.publicKey,
@@ -396,6 +416,7 @@ describe("HTLC", () => {
         sender: wallet.publicKey,
         tokenContract: tokenMint,
         senderTokenAccount: walletTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
       })
       .signers([wallet.payer])
       .rpc();